@@ -1,6 +1,7 @@
 use crate::library::{Annotation, Arity, Library, SentenceIndex};
 use crate::opcode::Instruction;
 use crate::source::Error;
+use crate::value::Value;
 use std::collections::{HashMap, HashSet};
 
 /// Checks whether all sentences in the library obey their declared arity,
@@ -77,6 +78,15 @@ pub(crate) struct EarlyReturn {
     /// The sentence the `?` was written in. Both arms are `<inline>` blocks,
     /// which is no help to a reader looking for the `?` that went wrong.
     pub(crate) in_sentence: String,
+    /// How the early return was written, `?` or `?!`, for the error that
+    /// refuses it.
+    pub(crate) written: &'static str,
+    /// Set when the block being left is one a `?!` inside it has to be
+    /// carried out of: every way out of such a block pushes whether it
+    /// finished (`true`) or is carrying a `?!`'s error further (`false`), and
+    /// the failure arm pushes this after its drops. The rest arm already ends
+    /// with its own.
+    pub(crate) marker: Option<bool>,
 }
 
 /// Makes an early return leave the stack the way finishing the block would.
@@ -92,6 +102,10 @@ pub(crate) struct EarlyReturn {
 /// rest arm that passes a value through rather than consuming it asks for no
 /// drop, and the early return passes the same value through.
 ///
+/// In a block that ends with a marker, the rest arm's outputs count the marker
+/// and the failure arm has one more value of its own to push, so it drops one
+/// more.
+///
 /// This runs before [`check_arities`] and after every sentence has been
 /// emitted, because the rest arm's arity is not knowable any earlier: it can
 /// call sentences that had not been compiled when the `?` was met. Sites come
@@ -112,12 +126,12 @@ pub(crate) fn balance_early_returns(
             // job a moment from now. Anything added here would only bury it.
             continue;
         };
-        let drops = -arity.net();
+        let drops = -arity.net() + i64::from(site.marker.is_some());
         if drops < 0 {
             return Err(format!(
-                "the code after a `?` in '{}' leaves {} more values than it takes, \
+                "the code after a {} in '{}' leaves {} more values than it takes, \
                  so an early return there cannot match it: it would have to invent them",
-                site.in_sentence, -drops
+                site.written, site.in_sentence, -drops
             ));
         }
         if drops > 0 && deep_drop.is_none() {
@@ -130,6 +144,9 @@ pub(crate) fn balance_early_returns(
         for _ in 0..drops {
             library.sentences[site.fail].push(Instruction::Dip(deep_drop.unwrap()));
         }
+        if let Some(marker) = site.marker {
+            library.sentences[site.fail].push(Instruction::Push(Value::Bool(marker)));
+        }
     }
     Ok(())
 }
@@ -249,8 +266,9 @@ pub fn sentence_arity(library: &Library, s_idx: SentenceIndex) -> Option<Arity>
 
 /// What one instruction takes off the top of the stack and leaves there.
 ///
-/// `None` where the effect is not local to the instruction: `Dip` and `Branch`
-/// depend on the sentences they call.
+/// `None` where the effect is not local to the instruction: `Jump`, `Dip` and
+/// `Branch` depend on the sentences they call. `tuple_map` and `tuple_fold`
+/// call one too, and do not.
 ///
 /// This is the single source of truth for per-instruction stack effects, and it
 /// is deliberately public: anything reasoning about whether code may move past
@@ -280,16 +298,36 @@ pub fn op_arity(inst: &Instruction) -> Option<(i64, i64)> {
         | Instruction::Multiply
         | Instruction::Divide
         | Instruction::Modulo
-        | Instruction::ConstStringCharAt => (2, 1),
+        | Instruction::BitAnd
+        | Instruction::BitOr
+        | Instruction::BitXor
+        | Instruction::ShiftLeft
+        | Instruction::ShiftRight
+        | Instruction::ConstStringCharAt
+        | Instruction::ConstStringConcat
+        | Instruction::ConstStringLess
+        | Instruction::TupleSet(_)
+        | Instruction::TupleConcat
+        | Instruction::MapGet(..)
+        | Instruction::MapRemove => (2, 1),
+        Instruction::ConstStringSlice | Instruction::MapInsert => (3, 1),
+        Instruction::MapEmpty => (0, 1),
         Instruction::Not
         | Instruction::Negate
+        | Instruction::Popcount
         | Instruction::ConstStringLen
+        | Instruction::ConstStringFromChar
+        | Instruction::IntToConstString
         | Instruction::TupleLength
+        | Instruction::TupleGet(_)
+        | Instruction::TupleSlice(..)
+        | Instruction::MapKeys
         | Instruction::IsInt
         | Instruction::IsBool
         | Instruction::IsConstString
         | Instruction::IsSymbol
         | Instruction::IsTuple
+        | Instruction::IsMap
         // The coercions replace a value with one of the type they name.
         | Instruction::AsBool
         | Instruction::AsInt
@@ -298,6 +336,12 @@ pub fn op_arity(inst: &Instruction) -> Option<(i64, i64)> {
         // makes the pair a shape rather than a pair of numbers to keep in step.
         Instruction::Untuple(n) => (1, *n as i64),
         Instruction::Tuple(n) => (*n as i64, 1),
+        // The tuple walks call a sentence, but never on the stack they were
+        // given: whatever the target is, one tuple comes in and one value goes
+        // out, and `check_arities` holds the target to the shape that makes
+        // that true.
+        Instruction::TupleMap(_) => (1, 1),
+        Instruction::TupleFold(_) => (2, 1),
         Instruction::Jump(..) | Instruction::Dip(..) | Instruction::Branch(..) => return None,
     })
 }
@@ -317,6 +361,37 @@ fn infer_arity_of_instructions(
     for inst in sentence {
         depths.push(current_size);
         match inst {
+            // The target runs once per element, on values the walk hands it,
+            // so it has to take what it is given and leave exactly what the
+            // next step takes. That is all there is to check: the walk's own
+            // effect is local, and comes from the table below.
+            Instruction::TupleMap(target) | Instruction::TupleFold(target) => {
+                let (name, takes) = match inst {
+                    Instruction::TupleMap(_) => ("tuple_map", 1),
+                    _ => ("tuple_fold", 2),
+                };
+                let target_arity =
+                    get_or_infer_arity(*target, library, memo, in_progress, instruction_arities)?;
+                if target_arity.inputs > takes || target_arity.net() != 1 - takes {
+                    return Err(format!(
+                        "Sentence '{}' (index {:?}) passes '{}' to `{}`, which needs a \
+                         {} -> 1 sentence, but it is {} -> {}",
+                        library.names[s_idx],
+                        s_idx,
+                        library.names[*target],
+                        name,
+                        takes,
+                        target_arity.inputs,
+                        target_arity.outputs
+                    ));
+                }
+                let (n, m) = op_arity(inst).expect("the tuple walks have a local effect");
+                if current_size < n {
+                    initial_req += n - current_size;
+                    current_size = n;
+                }
+                current_size = current_size - n + m;
+            }
             // Both call instructions, reached through the accessor so that
             // neither can be walked past: `jump` hides nothing and `dip` hides
             // one, and that is the whole of what separates them here.
@@ -2,14 +2,16 @@ use crate::arity::EarlyReturn;
 use crate::ast::core;
 use crate::ast::sugar::{self, Composer, ModuleExpr};
 use crate::ast::{
-    ConstStringDecl, IdentityDecl, ParsedInstruction, ParsedSentence, ParsedValue, PrimitiveType,
-    SentenceDecl, SourceAnnotation, SymbolDecl, Target, TypeSpec,
+    ConstDecl, ConstStringDecl, DataDecl, IdentityDecl, Match, MatchArm, ParsedInstruction,
+    ParsedSentence, ParsedValue, Pattern, PrimitiveType, Repeat, SentenceDecl, SourceAnnotation,
+    StaticAssertDecl, SymbolDecl, Target, TypeSpec,
 };
+use crate::data;
 use crate::library::{
     Annotation, Identity, IdentityIndex, Library, SentenceAnnotation, SentenceIndex,
 };
 use crate::opcode::Instruction;
-use crate::resolve::{ModuleId, ModuleItem, ModuleTree, ResolvedItem};
+use crate::resolve::{ConstIndex, GenericIndex, ModuleId, ModuleItem, ModuleTree, ResolvedItem};
 use crate::source::{Error, FileId, SourceMap, Span};
 use crate::value::{Symbol, Value};
 use std::collections::{HashMap, HashSet};
@@ -28,7 +30,11 @@ enum Token {
     FunctionKeyword,
     TypeKeyword,
     EnumKeyword,
+    RecordKeyword,
     IdentityKeyword,
+    TemplateKeyword,
+    ConstKeyword,
+    StaticAssertKeyword,
     DoubleColon,
     Equals,
     Semicolon,
@@ -44,7 +50,10 @@ enum Token {
     Comma,
     Colon,
     Pipe,
+    LAngle,
+    RAngle,
     Question,
+    QuestionBang,
     Int(i64),
     Bool(bool),
 }
@@ -63,7 +72,11 @@ fn describe(token: &Token) -> String {
         Token::FunctionKeyword => "function",
         Token::TypeKeyword => "type",
         Token::EnumKeyword => "enum",
+        Token::RecordKeyword => "record",
         Token::IdentityKeyword => "identity",
+        Token::TemplateKeyword => "template",
+        Token::ConstKeyword => "const",
+        Token::StaticAssertKeyword => "static_assert",
         Token::DoubleColon => "::",
         Token::Equals => "=",
         Token::Semicolon => ";",
@@ -77,7 +90,10 @@ fn describe(token: &Token) -> String {
         Token::Comma => ",",
         Token::Colon => ":",
         Token::Pipe => "|",
+        Token::LAngle => "<",
+        Token::RAngle => ">",
         Token::Question => "?",
+        Token::QuestionBang => "?!",
         Token::Identifier(name) => return format!("`{}`", name),
         Token::StringLiteral(s) => return format!("string literal \"{}\"", s),
         Token::Int(i) => return format!("`{}`", i),
@@ -191,9 +207,24 @@ fn tokenize(input: &str, file: FileId) -> Result<Vec<SpannedToken>, Error> {
                 chars.next();
                 push!(tokens, start, chars, Token::Pipe);
             }
+            // Only generic sentences use these, as brackets. Each is a token
+            // of its own, so the `>>` in `f<g<h>>` closes two lists.
+            '<' => {
+                chars.next();
+                push!(tokens, start, chars, Token::LAngle);
+            }
+            '>' => {
+                chars.next();
+                push!(tokens, start, chars, Token::RAngle);
+            }
             '?' => {
                 chars.next();
-                push!(tokens, start, chars, Token::Question);
+                if chars.peek().map(|&(_, c)| c) == Some('!') {
+                    chars.next();
+                    push!(tokens, start, chars, Token::QuestionBang);
+                } else {
+                    push!(tokens, start, chars, Token::Question);
+                }
             }
             '"' => {
                 chars.next(); // consume '"'
@@ -259,7 +290,11 @@ fn tokenize(input: &str, file: FileId) -> Result<Vec<SpannedToken>, Error> {
                     "function" => Token::FunctionKeyword,
                     "type" => Token::TypeKeyword,
                     "enum" => Token::EnumKeyword,
+                    "record" => Token::RecordKeyword,
                     "identity" => Token::IdentityKeyword,
+                    "template" => Token::TemplateKeyword,
+                    "const" => Token::ConstKeyword,
+                    "static_assert" => Token::StaticAssertKeyword,
                     "true" => Token::Bool(true),
                     "false" => Token::Bool(false),
                     _ => Token::Identifier(ident),
@@ -489,6 +524,7 @@ fn parse_type_primary(stream: &mut TokenStream) -> Result<TypeSpec, Error> {
                 "bool" => Ok(TypeSpec::Primitive(PrimitiveType::Bool)),
                 "symbol" => Ok(TypeSpec::Primitive(PrimitiveType::Symbol)),
                 "tuple" => Ok(TypeSpec::Primitive(PrimitiveType::Tuple)),
+                "map" => Ok(TypeSpec::Primitive(PrimitiveType::Map)),
                 _ => {
                     // Otherwise, parse it as a path (which could be a user-defined type or a symbol reference)
                     let path = parse_path(stream, name_cloned)?;
@@ -500,13 +536,18 @@ fn parse_type_primary(stream: &mut TokenStream) -> Result<TypeSpec, Error> {
     }
 }
 
-/// Parses a target which is either a named label or an inline `{}` block.
+/// Parses a target which is either a named label, an instance of a generic
+/// sentence, or an inline `{}` block.
 fn parse_target(stream: &mut TokenStream) -> Result<Target, Error> {
     match stream.peek() {
         Some(&Token::Identifier(_)) => {
             if let Some(Token::Identifier(name)) = stream.next() {
                 let path = parse_path(stream, name)?;
-                Ok(Target::Label(path))
+                if stream.peek() == Some(&Token::LAngle) {
+                    Ok(Target::Instance(path, parse_generic_args(stream)?))
+                } else {
+                    Ok(Target::Label(path))
+                }
             } else {
                 unreachable!()
             }
@@ -519,6 +560,29 @@ fn parse_target(stream: &mut TokenStream) -> Result<Target, Error> {
     }
 }
 
+/// Parses the `<f, g>` after a generic sentence's name at a call site. Each
+/// argument names a sentence, possibly another instance, but is never a block:
+/// an instance is named after its arguments, and a block has no name.
+fn parse_generic_args(stream: &mut TokenStream) -> Result<Vec<Target>, Error> {
+    stream.expect(Token::LAngle)?;
+    let mut args = Vec::new();
+    loop {
+        if !matches!(stream.peek(), Some(&Token::Identifier(_))) {
+            return Err(stream.expected("a sentence name"));
+        }
+        args.push(parse_target(stream)?);
+        match stream.peek() {
+            Some(&Token::Comma) => {
+                stream.next();
+            }
+            Some(&Token::RAngle) => break,
+            _ => return Err(stream.expected("`,` or `>`")),
+        }
+    }
+    stream.expect(Token::RAngle)?;
+    Ok(args)
+}
+
 fn parse_sentence_body(stream: &mut TokenStream) -> Result<ParsedSentence, Error> {
     let open = stream.span();
     stream.expect(Token::LBrace)?;
@@ -550,12 +614,16 @@ fn parse_usize(stream: &mut TokenStream) -> Result<usize, Error> {
 }
 
 fn parse_instruction(stream: &mut TokenStream) -> Result<ParsedInstruction, Error> {
-    // The one instruction that is punctuation rather than a word, because it
-    // reads as a suffix on the call that produced the result.
+    // The two instructions that are punctuation rather than words, because
+    // they read as a suffix on the call that produced the result.
     if stream.peek() == Some(&Token::Question) {
         stream.next();
         return Ok(ParsedInstruction::Try);
     }
+    if stream.peek() == Some(&Token::QuestionBang) {
+        stream.next();
+        return Ok(ParsedInstruction::Return);
+    }
     let span = stream.span();
     let err = stream.expected("an instruction");
     let name = match stream.next() {
@@ -598,6 +666,12 @@ fn parse_instruction(stream: &mut TokenStream) -> Result<ParsedInstruction, Erro
         "and" => Ok(ParsedInstruction::And),
         "or" => Ok(ParsedInstruction::Or),
         "negate" | "neg" => Ok(ParsedInstruction::Negate),
+        "bit_and" => Ok(ParsedInstruction::BitAnd),
+        "bit_or" => Ok(ParsedInstruction::BitOr),
+        "bit_xor" => Ok(ParsedInstruction::BitXor),
+        "shift_left" => Ok(ParsedInstruction::ShiftLeft),
+        "shift_right" => Ok(ParsedInstruction::ShiftRight),
+        "popcount" => Ok(ParsedInstruction::Popcount),
         "jump" => {
             let target = parse_target(stream)?;
             Ok(ParsedInstruction::Jump(target))
@@ -625,13 +699,46 @@ fn parse_instruction(stream: &mut TokenStream) -> Result<ParsedInstruction, Erro
             let size = parse_usize(stream)?;
             Ok(ParsedInstruction::Untuple(size))
         }
+        "tuple_map" => {
+            let target = parse_target(stream)?;
+            Ok(ParsedInstruction::TupleMap(target))
+        }
+        "tuple_fold" => {
+            let target = parse_target(stream)?;
+            Ok(ParsedInstruction::TupleFold(target))
+        }
+        "tuple_get" => {
+            let index = parse_usize(stream)?;
+            Ok(ParsedInstruction::TupleGet(index))
+        }
+        "tuple_set" => {
+            let index = parse_usize(stream)?;
+            Ok(ParsedInstruction::TupleSet(index))
+        }
+        "tuple_concat" => Ok(ParsedInstruction::TupleConcat),
+        "tuple_slice" => {
+            let start = parse_usize(stream)?;
+            let end = parse_usize(stream)?;
+            Ok(ParsedInstruction::TupleSlice(start, end))
+        }
+        "map_empty" => Ok(ParsedInstruction::MapEmpty),
+        "map_insert" => Ok(ParsedInstruction::MapInsert),
+        "map_get" => Ok(ParsedInstruction::MapGet),
+        "map_remove" => Ok(ParsedInstruction::MapRemove),
+        "map_keys" => Ok(ParsedInstruction::MapKeys),
         "const_string_len" => Ok(ParsedInstruction::ConstStringLen),
         "const_string_char_at" => Ok(ParsedInstruction::ConstStringCharAt),
+        "const_string_concat" => Ok(ParsedInstruction::ConstStringConcat),
+        "const_string_slice" => Ok(ParsedInstruction::ConstStringSlice),
+        "const_string_from_char" => Ok(ParsedInstruction::ConstStringFromChar),
+        "int_to_const_string" => Ok(ParsedInstruction::IntToConstString),
+        "const_string_less" => Ok(ParsedInstruction::ConstStringLess),
         "is_int" => Ok(ParsedInstruction::IsInt),
         "is_bool" => Ok(ParsedInstruction::IsBool),
         "is_const_string" => Ok(ParsedInstruction::IsConstString),
         "is_symbol" => Ok(ParsedInstruction::IsSymbol),
         "is_tuple" => Ok(ParsedInstruction::IsTuple),
+        "is_map" => Ok(ParsedInstruction::IsMap),
         "tuple_length" => Ok(ParsedInstruction::TupleLength),
         "as_bool" => Ok(ParsedInstruction::AsBool),
         "as_int" => Ok(ParsedInstruction::AsInt),
@@ -642,26 +749,106 @@ fn parse_instruction(stream: &mut TokenStream) -> Result<ParsedInstruction, Erro
             let size = parse_usize(stream)?;
             Ok(ParsedInstruction::AsTuple(size))
         }
+        "match" => Ok(ParsedInstruction::Match(parse_match(stream)?)),
+        "repeat" => Ok(ParsedInstruction::Repeat(parse_repeat(stream)?)),
         other => Err(Error::at(format!("unknown instruction `{}`", other), span)),
     }
 }
 
+/// Parses what follows `match`: the enum's path, then a brace-delimited list
+/// of arms, each a variant name, `_` or `junk` followed by its block.
+///
+/// An arm named twice is refused here, where its span still exists; the
+/// first would always win and the second could never run.
+fn parse_match(stream: &mut TokenStream) -> Result<Match, Error> {
+    let enum_path = match stream.peek() {
+        Some(Token::Identifier(name)) => {
+            let name = name.clone();
+            stream.next();
+            parse_path(stream, name)?
+        }
+        _ => return Err(stream.expected("the path of the enum to match on")),
+    };
+    stream.expect(Token::LBrace)?;
+
+    let mut arms: Vec<MatchArm> = Vec::new();
+    while stream.peek() != Some(&Token::RBrace) {
+        let span = stream.span();
+        let pattern = match expect_name(stream, "variant name, `_` or `junk`")?.as_str() {
+            "_" => Pattern::Wildcard,
+            "junk" => Pattern::Junk,
+            name => Pattern::Variant(name.to_string()),
+        };
+        if arms.iter().any(|arm| arm.pattern == pattern) {
+            return Err(
+                Error::at(format!("duplicate `match` arm `{}`", pattern), span)
+                    .with_help("the first arm would always be taken and this one never"),
+            );
+        }
+        let body = parse_sentence_body(stream)?;
+        arms.push(MatchArm { pattern, body });
+    }
+    stream.expect(Token::RBrace)?;
+
+    Ok(Match { enum_path, arms })
+}
+
+/// Parses what follows `repeat`: the count, then `with index` and `while ok`
+/// in that order if present, then the body as a label or a block.
+fn parse_repeat(stream: &mut TokenStream) -> Result<Repeat, Error> {
+    let count = parse_usize(stream)?;
+    let clause = |stream: &mut TokenStream, first: &str, second: &str| {
+        if !matches!(stream.peek(), Some(Token::Identifier(w)) if w == first) {
+            return Ok(false);
+        }
+        stream.next();
+        if !matches!(stream.peek(), Some(Token::Identifier(w)) if w == second) {
+            return Err(stream.expected(&format!("`{}` after `{}`", second, first)));
+        }
+        stream.next();
+        Ok(true)
+    };
+    let with_index = clause(stream, "with", "index")?;
+    let while_ok = clause(stream, "while", "ok")?;
+    let body = parse_target(stream)?;
+    Ok(Repeat {
+        count,
+        with_index,
+        while_ok,
+        body,
+    })
+}
+
 fn parse_module_expr(stream: &mut TokenStream) -> Result<ModuleExpr, Error> {
     if let Some(Token::Identifier(ident)) = stream.peek().cloned()
         && let Some(composer) = Composer::from_name(&ident)
     {
+        let span = stream.span();
         stream.next(); // consume composer name
         let args = parse_composer_args(stream)?;
-        return Ok(ModuleExpr::Composed { composer, args });
+        return Ok(ModuleExpr::Composed {
+            composer,
+            args,
+            span,
+        });
     }
 
     match stream.peek() {
         Some(Token::Identifier(_)) => {
+            let span = stream.span();
             let first_ident = match stream.next() {
                 Some(Token::Identifier(id)) => id,
                 _ => unreachable!(),
             };
             let path = parse_path(stream, first_ident)?;
+            if stream.peek() == Some(&Token::LParen) {
+                let args = parse_composer_args(stream)?;
+                return Ok(ModuleExpr::Instance {
+                    template: path,
+                    args,
+                    span,
+                });
+            }
             Ok(ModuleExpr::Named(path))
         }
         Some(_) => {
@@ -905,6 +1092,11 @@ fn parse_items(
                     stream.next();
                     text
                 }
+                Some(Token::Identifier(from)) if from == "from" => {
+                    stream.next(); // consume 'from'
+                    let (_, text) = read_data_file(stream, base_dir, map)?;
+                    data::trim_last_line_ending(&text).to_string()
+                }
                 // The text is the declaration: a const string without one would
                 // be a symbol written the long way round.
                 _ => return Err(stream.expected("a string literal, the const string's text")),
@@ -913,6 +1105,39 @@ fn parse_items(
             continue;
         }
 
+        // `const name = { ... };`, written like one side of an identity. A
+        // value like the two above, so it takes no modifiers either.
+        if annotations.is_empty() && stream.peek() == Some(&Token::ConstKeyword) {
+            stream.next(); // consume 'const'
+            let span = stream.span();
+            let name = expect_name(stream, "const name")?;
+            // `const name from "path";` is a value read out of a file: there
+            // is no body to run, so it needs no evaluator either.
+            if matches!(stream.peek(), Some(Token::Identifier(from)) if from == "from") {
+                stream.next(); // consume 'from'
+                let (file, text) = read_data_file(stream, base_dir, map)?;
+                let value = data::read(map.name(file), &text, file)
+                    .map_err(|e| e.with_note(format!("read for `const {}`", name), span))?;
+                stream.expect(Token::Semicolon)?;
+                items.push(sugar::Item::Data(DataDecl { name, value }));
+                continue;
+            }
+            stream.expect(Token::Equals)?;
+            let body = parse_sentence_body(stream)?;
+            stream.expect(Token::Semicolon)?;
+            items.push(sugar::Item::Const(ConstDecl { name, body, span }));
+            continue;
+        }
+
+        // Names nothing, so nothing could call it or mark it.
+        if annotations.is_empty() && stream.peek() == Some(&Token::StaticAssertKeyword) {
+            let span = stream.span();
+            stream.next(); // consume 'static_assert'
+            let body = parse_sentence_body(stream)?;
+            items.push(sugar::Item::StaticAssert(StaticAssertDecl { body, span }));
+            continue;
+        }
+
         // `test mod` is a test machine, not a test sentence, so it is matched
         // before the modifier loop would swallow the `test`.
         let is_test_mod = stream.peek() == Some(&Token::TestKeyword)
@@ -932,6 +1157,21 @@ fn parse_items(
             continue;
         }
 
+        if stream.peek() == Some(&Token::TemplateKeyword) {
+            if !annotations.is_empty() {
+                return Err(Error::at(
+                    "annotations are not supported on templates",
+                    item_span,
+                ));
+            }
+            stream.next(); // consume 'template'
+            stream.expect(Token::ModKeyword)?;
+            items.push(sugar::Item::Template(parse_template_decl(
+                stream, base_dir, map,
+            )?));
+            continue;
+        }
+
         let (is_exported, is_test) = parse_modifiers(stream);
 
         if stream.peek() == Some(&Token::TypeKeyword) {
@@ -952,6 +1192,11 @@ fn parse_items(
             continue;
         }
 
+        if stream.peek() == Some(&Token::RecordKeyword) {
+            items.push(sugar::Item::Record(parse_record_decl(stream, annotations)?));
+            continue;
+        }
+
         // `identity name { A } = { B };`. Matched after the modifier loop so
         // that `export identity foo` is refused with a reason rather than
         // reported as a stray `export`.
@@ -999,7 +1244,23 @@ fn parse_items(
             }
         };
 
+        let name_span = stream.span();
         let name = expect_name(stream, "sentence name")?;
+        let generics = if stream.peek() == Some(&Token::LAngle) {
+            parse_generic_params(stream, &name)?
+        } else {
+            Vec::new()
+        };
+        if !generics.is_empty() && (is_exported || is_test) {
+            return Err(Error::at(
+                "a generic sentence takes no `export` or `test` marker",
+                item_span,
+            )
+            .with_help(
+                "it is not a sentence until it is given arguments, and each instance is \
+                 named by the arguments it was given",
+            ));
+        }
         if stream.peek() == Some(&Token::Colon) {
             stream.next();
         }
@@ -1012,10 +1273,12 @@ fn parse_items(
 
         items.push(sugar::Item::Sentence(SentenceDecl {
             name,
+            generics,
             body,
             annotations,
             is_exported,
             is_test,
+            span: Some(name_span),
         }));
     }
 
@@ -1062,6 +1325,7 @@ fn parse_mod_item(
     if let Some(Token::Identifier(ident)) = stream.peek()
         && let Some(composer) = Composer::from_name(ident)
     {
+        let span = stream.span();
         stream.next(); // consume composer name
         let args = parse_composer_args(stream)?;
         stream.expect(Token::Semicolon)?;
@@ -1070,6 +1334,24 @@ fn parse_mod_item(
             composer,
             args,
             is_test,
+            span,
+        }));
+    }
+
+    // Any other name here is a template the file declares. Which one is for
+    // lowering to say, since it may be declared further down.
+    if let Some(Token::Identifier(ident)) = stream.peek().cloned() {
+        let span = stream.span();
+        stream.next();
+        let template = parse_path(stream, ident)?;
+        let args = parse_composer_args(stream)?;
+        stream.expect(Token::Semicolon)?;
+        return Ok(sugar::Item::Instance(sugar::InstanceDecl {
+            name,
+            template,
+            args,
+            is_test,
+            span,
         }));
     }
 
@@ -1109,6 +1391,126 @@ fn parse_mod_item(
     }))
 }
 
+/// Reads the file named by the string literal after a `from`, relative to the
+/// directory a `mod name;` in the same place would load from. The file is
+/// added to `map`, so an error in what it holds can point into it.
+fn read_data_file(
+    stream: &mut TokenStream,
+    base_dir: Option<&std::path::Path>,
+    map: &mut SourceMap,
+) -> Result<(FileId, String), Error> {
+    let span = stream.span();
+    let relative = match stream.peek() {
+        Some(Token::StringLiteral(path)) => path.clone(),
+        _ => return Err(stream.expected("a string literal, the path of the file to read")),
+    };
+    stream.next();
+    let base = base_dir.ok_or_else(|| {
+        Error::at(format!("cannot read `{}`", relative), span)
+            .with_help("no base directory was given, so there is nowhere to look for the file")
+    })?;
+    let file_path = base.join(&relative);
+    let text = std::fs::read_to_string(&file_path).map_err(|e| {
+        Error::at(
+            format!("cannot read `{}`: {}", file_path.display(), e),
+            span,
+        )
+    })?;
+    let file = map.add_path(&file_path, text.clone());
+    Ok((file, text))
+}
+
+/// Parses a generic sentence's `<f, g>`: the names its body calls the
+/// sentences it is given by.
+fn parse_generic_params(stream: &mut TokenStream, sentence: &str) -> Result<Vec<String>, Error> {
+    stream.expect(Token::LAngle)?;
+    let mut params: Vec<String> = Vec::new();
+    loop {
+        let param_span = stream.span();
+        let param = expect_name(stream, "parameter name")?;
+        if param == "crate" || param == "super" {
+            return Err(Error::at(
+                format!("`{}` cannot name a parameter", param),
+                param_span,
+            ));
+        }
+        if params.contains(&param) {
+            return Err(Error::at(
+                format!(
+                    "duplicate parameter `{}` in generic sentence `{}`",
+                    param, sentence
+                ),
+                param_span,
+            )
+            .with_help(
+                "every use of a parameter names the same argument, so each needs its own name",
+            ));
+        }
+        params.push(param);
+        match stream.peek() {
+            Some(&Token::Comma) => {
+                stream.next();
+            }
+            Some(&Token::RAngle) => break,
+            _ => return Err(stream.expected("`,` or `>`")),
+        }
+    }
+    stream.expect(Token::RAngle)?;
+    Ok(params)
+}
+
+/// Parses what follows `template mod`: a name, a parameter list, and a body.
+fn parse_template_decl(
+    stream: &mut TokenStream,
+    base_dir: Option<&std::path::Path>,
+    map: &mut SourceMap,
+) -> Result<sugar::TemplateDecl, Error> {
+    let span = stream.span();
+    let name = expect_name(stream, "template name")?;
+
+    stream.expect(Token::LParen)?;
+    let mut params: Vec<String> = Vec::new();
+    while stream.peek() != Some(&Token::RParen) {
+        let param_span = stream.span();
+        let param = expect_name(stream, "parameter name")?;
+        // A parameter replaces the first segment of a path, so it cannot be
+        // one of the two segments that already mean something.
+        if param == "crate" || param == "super" {
+            return Err(Error::at(
+                format!("`{}` cannot name a parameter", param),
+                param_span,
+            ));
+        }
+        if params.contains(&param) {
+            return Err(Error::at(
+                format!("duplicate parameter `{}` in template `{}`", param, name),
+                param_span,
+            )
+            .with_help(
+                "every use of a parameter names the same argument, so each needs its own name",
+            ));
+        }
+        params.push(param);
+        if stream.peek() == Some(&Token::Comma) {
+            stream.next();
+        } else {
+            break;
+        }
+    }
+    stream.expect(Token::RParen)?;
+
+    stream.expect(Token::LBrace)?;
+    let new_base = base_dir.map(|b| b.join(&name));
+    let items = parse_items(stream, Some(Token::RBrace), new_base.as_deref(), map)?;
+    stream.expect(Token::RBrace)?;
+    Ok(sugar::TemplateDecl {
+        name,
+        params,
+        items,
+        span,
+    })
+}
+
 fn parse_enum_decl(
     stream: &mut TokenStream,
     annotations: Vec<SourceAnnotation>,
@@ -1160,6 +1562,51 @@ fn parse_enum_decl(
     })
 }
 
+/// `record Name { field: spec, ... }`. Fields are separated by commas, and a
+/// trailing one is allowed, as after enum variants.
+fn parse_record_decl(
+    stream: &mut TokenStream,
+    annotations: Vec<SourceAnnotation>,
+) -> Result<sugar::RecordDecl, Error> {
+    stream.expect(Token::RecordKeyword)?;
+    let name = expect_name(stream, "record name")?;
+    stream.expect(Token::LBrace)?;
+
+    let mut fields: Vec<sugar::RecordField> = Vec::new();
+    while stream.peek() != Some(&Token::RBrace) {
+        let field_span = stream.span();
+        let field_name = expect_name(stream, "field name")?;
+        if fields.iter().any(|f| f.name == field_name) {
+            return Err(Error::at(
+                format!("duplicate field `{}` in record `{}`", field_name, name),
+                field_span,
+            )
+            .with_help("each field names one position in the tuple, so the name is its address"));
+        }
+        stream.expect(Token::Colon)?;
+        let spec = parse_type_spec(stream)?;
+        fields.push(sugar::RecordField {
+            name: field_name,
+            spec,
+        });
+
+        match stream.peek() {
+            Some(&Token::Comma) => {
+                stream.next();
+            }
+            Some(&Token::RBrace) => {}
+            _ => return Err(stream.expected("`,` or `}`")),
+        }
+    }
+    stream.expect(Token::RBrace)?;
+
+    Ok(sugar::RecordDecl {
+        name,
+        fields,
+        annotations,
+    })
+}
+
 /// Everything the tree-building pass accumulates alongside the module tree
 /// itself: the flat sentence list to compile, and the library's lookup maps.
 /// Phase 3: declare. Walks the core tree assigning module ids and sentence
@@ -1171,11 +1618,27 @@ struct TreeBuilder {
     /// Each sentence paired with the module its paths resolve against, which
     /// is always simply the module it is declared in.
     flat_sentences: Vec<(ModuleId, SentenceDecl)>,
+    /// Generic sentences, likewise paired with their module. None of them is
+    /// compiled as written; phase 4 compiles an instance of one wherever a
+    /// call site gives it arguments.
+    generics: typed_index_collections::TiVec<GenericIndex, (ModuleId, SentenceDecl)>,
+    /// Every `const`, paired with its module, by the index its name is bound
+    /// to. Phase 4 evaluates each one before it compiles anything else.
+    consts: typed_index_collections::TiVec<ConstIndex, (ModuleId, ConstDecl)>,
+    /// Every `static_assert`, paired with its module, in declaration order.
+    static_asserts: Vec<(ModuleId, StaticAssertDecl)>,
     exports: HashMap<String, SentenceIndex>,
     tests: HashMap<String, SentenceIndex>,
     test_machines: HashSet<String>,
     /// In declaration order, which is the order `IdentityIndex` counts in.
     identities: Vec<Identity>,
+    /// The variants of every module lowered from an `enum`.
+    enums: HashMap<ModuleId, Vec<core::Variant>>,
+    /// Where every module instantiated from a declared template came from.
+    instances: HashMap<ModuleId, core::Instance>,
+    /// Every enum variant's `get`, by the variant's module, until
+    /// [`TreeBuilder::declare_accessors`] knows whether to declare them.
+    accessors: Vec<(ModuleId, SentenceDecl)>,
 }
 
 /// Sentences a test machine module exposes to the runtime.
@@ -1196,10 +1659,16 @@ impl TreeBuilder {
             symbol_counter: 0,
             sentence_counter: 0,
             flat_sentences: Vec::new(),
+            generics: typed_index_collections::TiVec::new(),
+            consts: typed_index_collections::TiVec::new(),
+            static_asserts: Vec::new(),
             exports: HashMap::new(),
             tests: HashMap::new(),
             test_machines: HashSet::new(),
             identities: Vec::new(),
+            enums: HashMap::new(),
+            instances: HashMap::new(),
+            accessors: Vec::new(),
         }
     }
 
@@ -1226,6 +1695,16 @@ impl TreeBuilder {
                         ModuleItem::Const(Value::ConstString(decl.text)),
                     )?;
                 }
+                core::Item::Data(decl) => {
+                    self.tree
+                        .declare(scope, decl.name, ModuleItem::Const(decl.value))?;
+                }
+                core::Item::Sentence(decl) if !decl.generics.is_empty() => {
+                    let g_idx = self.generics.next_key();
+                    self.tree
+                        .declare(scope, decl.name.clone(), ModuleItem::Generic(g_idx))?;
+                    self.generics.push((scope, decl));
+                }
                 core::Item::Sentence(decl) => {
                     let s_idx = SentenceIndex::from(self.sentence_counter);
                     self.sentence_counter += 1;
@@ -1278,17 +1757,44 @@ impl TreeBuilder {
                             scope,
                             SentenceDecl {
                                 name: format!("{}::{}", decl.name, suffix),
+                                generics: Vec::new(),
                                 body,
                                 annotations: decl.annotations.clone(),
                                 is_exported: false,
                                 is_test: false,
+                                span: Some(decl.span),
                             },
                         ));
                     }
                 }
+                core::Item::Const(decl) => {
+                    let c_idx = self.consts.next_key();
+                    self.tree
+                        .declare(scope, decl.name.clone(), ModuleItem::Computed(c_idx))?;
+                    self.consts.push((scope, decl));
+                }
+                core::Item::StaticAssert(decl) => {
+                    self.static_asserts.push((scope, decl));
+                }
                 core::Item::Mod(decl) => {
                     let sub_id = self.tree.declare_module(scope, decl.name)?;
+                    if let Some(instance) = decl.instance {
+                        self.instances.insert(sub_id, instance);
+                    }
                     self.build(decl.items, sub_id)?;
+                    if let Some(variants) = decl.variants {
+                        for variant in &variants {
+                            let path = Path {
+                                segments: vec![PathSegment::Identifier(variant.name.clone())],
+                            };
+                            if let Ok(&ModuleItem::Mod(module)) =
+                                self.tree.resolve_entry(sub_id, &path)
+                            {
+                                self.accessors.push((module, variant.get.clone()));
+                            }
+                        }
+                        self.enums.insert(sub_id, variants);
+                    }
                     if decl.is_test {
                         self.register_test_machine(sub_id, decl.exports_machine_sentences)?;
                     }
@@ -1298,6 +1804,29 @@ impl TreeBuilder {
         Ok(())
     }
 
+    /// Declares every enum variant's `get` in its variant's module, if the
+    /// program declares the result tags it answers with.
+    ///
+    /// Asked of the finished tree, the way `?` and `map_get` find the tags, so
+    /// it makes no difference where or how the program declares them. A
+    /// program without them has no `get`s, and hears why where it asks for one.
+    fn declare_accessors(&mut self) -> Result<(), String> {
+        let accessors = std::mem::take(&mut self.accessors);
+        let tagged = ["ok", "err"].into_iter().all(|tag| {
+            matches!(
+                self.tree.resolve(crate::resolve::ROOT, &prelude_path(tag)),
+                Ok(ResolvedItem::Const(_))
+            )
+        });
+        if !tagged {
+            return Ok(());
+        }
+        for (module, get) in accessors {
+            self.build(vec![core::Item::Sentence(get)], module)?;
+        }
+        Ok(())
+    }
+
     /// Registers a `test mod` as a machine the runtime can drive. Composed
     /// machines additionally export their machine sentences, since the generated
     /// bodies have no `export` markers of their own.
@@ -1328,6 +1857,8 @@ impl TreeBuilder {
 
 struct Compiler<'a> {
     tree: &'a ModuleTree,
+    /// What phase 3 recorded about each enum, for checking a `match` on one.
+    enums: &'a HashMap<ModuleId, Vec<core::Variant>>,
     sentences: Vec<Vec<Instruction>>,
     names: Vec<String>,
     annotations: Vec<Vec<SentenceAnnotation>>,
@@ -1347,6 +1878,125 @@ struct Compiler<'a> {
     /// The same, for the frames a `dip N { ... }` nests through. Keyed by the
     /// target too, since these wrap a particular callee rather than a shape.
     frames: HashMap<(usize, SentenceIndex), SentenceIndex>,
+    /// How the block being compiled is left. See [`Exit`].
+    exit: Exit,
+    /// Every generic sentence, by the index its name is bound to.
+    generics: &'a typed_index_collections::TiVec<GenericIndex, (ModuleId, SentenceDecl)>,
+    /// One instance per generic and list of arguments, however many call
+    /// sites spell it.
+    monomorphs: HashMap<(GenericIndex, Vec<SentenceIndex>), SentenceIndex>,
+    /// What the parameters of the generic being instantiated stand for. Empty
+    /// outside one; only ever the innermost, since a generic's body sees its
+    /// own parameters and no one else's.
+    arguments: HashMap<String, SentenceIndex>,
+    /// The generics whose instances are being compiled, outermost first.
+    instantiating: Vec<GenericIndex>,
+    /// Named sentences not compiled yet, by index. The main loop compiles
+    /// them in order, but working out a `const` compiles what it calls first.
+    pending: Vec<Option<(ModuleId, SentenceDecl)>>,
+    /// Sentences that have an index but are still being compiled, so have no
+    /// body to run yet.
+    unfinished: HashSet<SentenceIndex>,
+    /// Where every module instantiated from a template came from.
+    instances: &'a HashMap<ModuleId, core::Instance>,
+    /// Every `const`, by the index its name is bound to.
+    consts: &'a typed_index_collections::TiVec<ConstIndex, (ModuleId, ConstDecl)>,
+    /// What each `const` worked out to.
+    values: HashMap<ConstIndex, Value>,
+    /// The consts being worked out, outermost first.
+    evaluating: Vec<ConstIndex>,
+    /// What runs a body at compile time. `None` refuses every `const` and
+    /// `static_assert`.
+    evaluator: Option<Evaluator<'a>>,
+}
+
+/// Runs the sentence at an index on an empty stack, and answers the stack it
+/// leaves: how phase 4 works out a `const` or checks a `static_assert`.
+///
+/// The VM is what runs programs, so it is what runs these too, rather than a
+/// second interpreter kept in step with it here. It depends on this crate, so
+/// it is passed in — see `vm::assemble`.
+pub type Evaluator<'e> = &'e dyn Fn(Library, SentenceIndex) -> Result<Vec<Value>, String>;
+
+/// What the compiler holds about the sentence it is in the middle of, set
+/// aside while it compiles another first.
+struct Context {
+    current_sentence: String,
+    exit: Exit,
+    arguments: HashMap<String, SentenceIndex>,
+    instantiating: Vec<GenericIndex>,
+}
+
+/// Whether a block reports how it was left.
+///
+/// A `?!` leaves the sentence, but the code it is written in can only leave
+/// the block around it. So a block that a `?!` may be carried out of — one
+/// with a `?!` in it, or in a block inside it — pushes a marker on every way
+/// out: `true` when it finished, however it finished, and `false` on top of
+/// the error when it is carrying one further. The level around it branches on
+/// the marker straight away, and leaves early in turn on `false`, with the
+/// same balanced failure arm a `?` gets. At the top of a sentence there is
+/// nowhere further to carry it, which is why a `?!` there is a `?`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exit {
+    /// A sentence body, or a block with no `?!` to carry out.
+    Plain,
+    /// A block that reports how it was left.
+    Marked,
+}
+
+impl Exit {
+    /// What a way out of the block pushes when the block is done.
+    fn finished(self) -> Option<bool> {
+        (self == Exit::Marked).then_some(true)
+    }
+
+    /// What it pushes when it is carrying a `?!`'s error further.
+    fn carrying(self) -> Option<bool> {
+        (self == Exit::Marked).then_some(false)
+    }
+}
+
+/// An instruction compiled by [`Compiler::compile_carrying`]: what it became,
+/// and what the failure arm after it starts with.
+struct Carried {
+    instructions: Vec<Instruction>,
+    fail_prefix: Vec<Instruction>,
+}
+
+/// A stack as an error shows it: the values bottom first, or that there were
+/// none.
+fn describe_stack(stack: &[Value]) -> String {
+    if stack.is_empty() {
+        return "nothing".to_string();
+    }
+    let values: Vec<String> = stack.iter().map(|v| format!("`{}`", v)).collect();
+    values.join(", ")
+}
+
+/// Whether a `?!` in these instructions would have to be carried out of the
+/// block they are in: written there, or in an inline block inside them. A
+/// label is a sentence of its own, where a `?!` ends that sentence and no
+/// more.
+fn can_be_left_by_return(instructions: &[ParsedInstruction]) -> bool {
+    let inline = |target: &Target| match target {
+        Target::Inline(body) => can_be_left_by_return(&body.instructions),
+        Target::Label(_) | Target::Instance(..) => false,
+    };
+    instructions.iter().any(|inst| match inst {
+        ParsedInstruction::Return => true,
+        ParsedInstruction::Jump(t)
+        | ParsedInstruction::Dip(_, t)
+        | ParsedInstruction::TupleMap(t)
+        | ParsedInstruction::TupleFold(t) => inline(t),
+        ParsedInstruction::Branch(a, b) => inline(a) || inline(b),
+        ParsedInstruction::Match(m) => m
+            .arms
+            .iter()
+            .any(|arm| can_be_left_by_return(&arm.body.instructions)),
+        ParsedInstruction::Repeat(r) => inline(&r.body),
+        _ => false,
+    })
 }
 
 /// What a depth-carrying movement instruction does with the value it reaches.
@@ -1410,7 +2060,16 @@ impl<'a> Compiler<'a> {
         }
     }
 
-    fn compile_value(&self, scope: ModuleId, parsed: ParsedValue) -> Result<Value, String> {
+    /// Resolves `path` as [`ModuleTree::resolve`] does, except that a `const`
+    /// is worked out and resolves to its value.
+    fn resolve(&mut self, scope: ModuleId, path: &Path) -> Result<ResolvedItem, String> {
+        if let Ok(&ModuleItem::Computed(c)) = self.tree.resolve_entry(scope, path) {
+            return self.evaluate(c).map(ResolvedItem::Const);
+        }
+        self.tree.resolve(scope, path)
+    }
+
+    fn compile_value(&mut self, scope: ModuleId, parsed: ParsedValue) -> Result<Value, String> {
         match parsed {
             ParsedValue::Bool(b) => Ok(Value::Bool(b)),
             ParsedValue::Int(i) => Ok(Value::Int(i)),
@@ -1422,7 +2081,7 @@ impl<'a> Compiler<'a> {
                 }
                 Ok(Value::Tuple(compiled_elements))
             }
-            ParsedValue::Ref(path) => match self.tree.resolve(scope, &path)? {
+            ParsedValue::Ref(path) => match self.resolve(scope, &path)? {
                 ResolvedItem::Const(val) => Ok(val),
                 ResolvedItem::Sentence(_) => Err(format!(
                     "Expected a value, found sentence at path {:?}",
@@ -1440,6 +2099,15 @@ impl<'a> Compiler<'a> {
         let mut compiled = Vec::new();
         let mut rest = instructions.into_iter();
         while let Some(inst) = rest.next() {
+            if can_be_left_by_return(std::slice::from_ref(&inst))
+                && let Some(carried) = self.compile_carrying(scope, &inst)?
+            {
+                // What the instruction became, and then the rest of the block
+                // behind a branch on the marker it left.
+                compiled.extend(carried.instructions);
+                compiled.extend(self.carry_out(scope, carried.fail_prefix, rest.collect())?);
+                return Ok(compiled);
+            }
             let c_inst = match inst {
                 ParsedInstruction::Push(v) => {
                     let compiled_val = self.compile_value(scope, v)?;
@@ -1470,17 +2138,41 @@ impl<'a> Compiler<'a> {
                 ParsedInstruction::Modulo => Instruction::Modulo,
                 ParsedInstruction::Not => Instruction::Not,
                 ParsedInstruction::Negate => Instruction::Negate,
+                ParsedInstruction::BitAnd => Instruction::BitAnd,
+                ParsedInstruction::BitOr => Instruction::BitOr,
+                ParsedInstruction::BitXor => Instruction::BitXor,
+                ParsedInstruction::ShiftLeft => Instruction::ShiftLeft,
+                ParsedInstruction::ShiftRight => Instruction::ShiftRight,
+                ParsedInstruction::Popcount => Instruction::Popcount,
                 ParsedInstruction::Tuple(n) => Instruction::Tuple(n),
                 ParsedInstruction::Untuple(n) => Instruction::Untuple(n),
+                ParsedInstruction::TupleGet(i) => Instruction::TupleGet(i),
+                ParsedInstruction::TupleSet(i) => Instruction::TupleSet(i),
+                ParsedInstruction::TupleConcat => Instruction::TupleConcat,
+                ParsedInstruction::TupleSlice(start, end) => Instruction::TupleSlice(start, end),
+                ParsedInstruction::MapEmpty => Instruction::MapEmpty,
+                ParsedInstruction::MapInsert => Instruction::MapInsert,
+                ParsedInstruction::MapGet => Instruction::MapGet(
+                    self.prelude_symbol(scope, "ok", "`map_get`")?,
+                    self.prelude_symbol(scope, "err", "`map_get`")?,
+                ),
+                ParsedInstruction::MapRemove => Instruction::MapRemove,
+                ParsedInstruction::MapKeys => Instruction::MapKeys,
                 ParsedInstruction::And => Instruction::And,
                 ParsedInstruction::Or => Instruction::Or,
                 ParsedInstruction::ConstStringLen => Instruction::ConstStringLen,
                 ParsedInstruction::ConstStringCharAt => Instruction::ConstStringCharAt,
+                ParsedInstruction::ConstStringConcat => Instruction::ConstStringConcat,
+                ParsedInstruction::ConstStringSlice => Instruction::ConstStringSlice,
+                ParsedInstruction::ConstStringFromChar => Instruction::ConstStringFromChar,
+                ParsedInstruction::IntToConstString => Instruction::IntToConstString,
+                ParsedInstruction::ConstStringLess => Instruction::ConstStringLess,
                 ParsedInstruction::IsInt => Instruction::IsInt,
                 ParsedInstruction::IsBool => Instruction::IsBool,
                 ParsedInstruction::IsConstString => Instruction::IsConstString,
                 ParsedInstruction::IsSymbol => Instruction::IsSymbol,
                 ParsedInstruction::IsTuple => Instruction::IsTuple,
+                ParsedInstruction::IsMap => Instruction::IsMap,
                 ParsedInstruction::TupleLength => Instruction::TupleLength,
                 ParsedInstruction::AsBool => Instruction::AsBool,
                 ParsedInstruction::AsInt => Instruction::AsInt,
@@ -1493,27 +2185,59 @@ impl<'a> Compiler<'a> {
                     let target_idx = self.resolve_target(scope, target)?;
                     self.frame(depth, target_idx)
                 }
+                ParsedInstruction::TupleMap(target) => {
+                    Instruction::TupleMap(self.resolve_target(scope, target)?)
+                }
+                ParsedInstruction::TupleFold(target) => {
+                    Instruction::TupleFold(self.resolve_target(scope, target)?)
+                }
                 ParsedInstruction::Branch(t1, t2) => {
                     let idx1 = self.resolve_target(scope, t1)?;
                     let idx2 = self.resolve_target(scope, t2)?;
                     Instruction::Branch(idx1, idx2)
                 }
+                // Spliced in front of the rest of the block rather than
+                // compiled on its own, so that anything which takes the rest
+                // of the block with it — a `?!` carried out of an arm — takes
+                // what follows the `match` too.
+                ParsedInstruction::Match(m) => {
+                    let expansion = self.expand_match(scope, m)?;
+                    rest = expansion
+                        .into_iter()
+                        .chain(rest)
+                        .collect::<Vec<_>>()
+                        .into_iter();
+                    continue;
+                }
+                ParsedInstruction::Repeat(r) => {
+                    compiled.extend(self.compile_repeat(scope, r)?);
+                    continue;
+                }
                 // `?` takes the rest of the block with it, so it is the end
                 // of this body rather than one more instruction in it.
                 ParsedInstruction::Try => {
                     let tail: Vec<ParsedInstruction> = rest.by_ref().collect();
-                    compiled.extend(self.compile_try(scope, tail)?);
+                    let marker = self.exit.finished();
+                    compiled.extend(self.compile_try(scope, tail, "`?`", marker)?);
+                    return Ok(compiled);
+                }
+                // The same, except that the block it leaves is not done: the
+                // level around it is to leave as well.
+                ParsedInstruction::Return => {
+                    let tail: Vec<ParsedInstruction> = rest.by_ref().collect();
+                    let marker = self.exit.carrying();
+                    compiled.extend(self.compile_try(scope, tail, "`?!`", marker)?);
                     return Ok(compiled);
                 }
                 ParsedInstruction::TypeCheckPath(path) => {
-                    let resolved = match self.tree.resolve(scope, &path) {
+                    let resolved = match self.resolve(scope, &path) {
                         Ok(res) => res,
                         Err(e) => {
                             let mut check_path = path.clone();
                             check_path
                                 .segments
                                 .push(PathSegment::Identifier("check".to_string()));
-                            self.tree.resolve(scope, &check_path).map_err(|_| {
+                            self.resolve(scope, &check_path).map_err(|_| {
                                 format!("Could not resolve type path '{}': {}", path, e)
                             })?
                         }
@@ -1531,49 +2255,164 @@ impl<'a> Compiler<'a> {
             };
             compiled.push(c_inst);
         }
+        if let Some(marker) = self.exit.finished() {
+            compiled.push(Instruction::Push(Value::Bool(marker)));
+        }
         Ok(compiled)
     }
 
-    /// `?`, as the branches a user could have written in its place.
-    ///
-    /// A result is `(value, tag)` with `tag` being `crate::prelude::ok` or
-    /// `crate::prelude::err`, so the expansion asks whether it was handed a
-    /// pair at all, takes that pair apart, asks which tag it carries, and puts
-    /// the rest of the block in the arm that runs when the answer is `ok`:
-    ///
-    /// ```text
-    /// pick 0 pick 0 as_tuple 2 equal
-    /// branch { untuple 2 push ok equal } { push false }
-    /// branch { ...the rest of the block... } { push err tuple 2 }
-    /// ```
-    ///
-    /// The first branch asks whether there is a result to take apart at all: a
-    /// value that is not a 2-tuple is not one, and rather than failing on it,
-    /// `?` calls it an error carrying that value — which is why the else arm
-    /// leaves the value alone and only answers `false`. That is what keeps `?`
-    /// total (see `docs/totality.md`).
-    ///
-    /// Asking before unpacking is what `untuple` used to answer for itself. It
-    /// is the same question, written where the one caller that wants it is.
+    /// An instruction with a block inside it that a `?!` may be carried out of,
+    /// with those blocks compiled to report how they were left. Answers `None`
+    /// for one that is compiled as usual, having no such block after all.
     ///
-    /// The failure arm is left one step short: it rebuilds the error but does
-    /// not yet drop whatever the block was holding underneath it, because how
-    /// much that is depends on the arity of the rest of the block, which is not
-    /// known until every sentence has been emitted.
-    /// [`crate::arity::balance_early_returns`] finishes it.
-    fn compile_try(
+    /// What it becomes leaves the blocks' marker on top, and the failure arm
+    /// of [`Compiler::carry_out`] starts with whatever brings the error there.
+    /// For `dip N` that is the `N` values the frame restored on top of both:
+    /// `roll N` brings the marker up past them, and the same again brings the
+    /// error up.
+    fn compile_carrying(
         &mut self,
         scope: ModuleId,
-        tail: Vec<ParsedInstruction>,
-    ) -> Result<Vec<Instruction>, String> {
-        let ok = self.prelude_symbol(scope, "ok")?;
-        let err = self.prelude_symbol(scope, "err")?;
+        inst: &ParsedInstruction,
+    ) -> Result<Option<Carried>, String> {
+        Ok(Some(match inst.clone() {
+            ParsedInstruction::Jump(target) => Carried {
+                instructions: vec![Instruction::Jump(self.marked_block(scope, target)?)],
+                fail_prefix: Vec::new(),
+            },
+            ParsedInstruction::Dip(depth, target) => {
+                let body = self.marked_block(scope, target)?;
+                let mut instructions = vec![self.frame(depth, body)];
+                let up = self.reach(Reach::Move, depth);
+                instructions.extend(up.iter().cloned());
+                Carried {
+                    instructions,
+                    fail_prefix: up,
+                }
+            }
+            // Both arms report, so the one without a `?!` in it says it
+            // finished.
+            ParsedInstruction::Branch(a, b) => {
+                let a = self.marked_block(scope, a)?;
+                let b = self.marked_block(scope, b)?;
+                Carried {
+                    instructions: vec![Instruction::Branch(a, b)],
+                    fail_prefix: Vec::new(),
+                }
+            }
+            ParsedInstruction::Repeat(_) => {
+                return Err(format!(
+                    "In '{}': a `?!` cannot be carried out of a `repeat` body, which runs \
+                     once per iteration with nothing after it to leave from; \
+                     `repeat N while ok` stops at the first error",
+                    self.current_sentence
+                ));
+            }
+            ParsedInstruction::TupleMap(_) | ParsedInstruction::TupleFold(_) => {
+                return Err(format!(
+                    "In '{}': a `?!` cannot be carried out of a `tuple_map` or `tuple_fold` \
+                     body, which runs once per element and has to leave exactly one value \
+                     each time; `?` inside the body leaves that value early instead",
+                    self.current_sentence
+                ));
+            }
+            _ => return Ok(None),
+        }))
+    }
+
+    /// The rest of a block, behind a branch on the marker a block inside it
+    /// left: on `true` it runs, and on `false` the level leaves in turn,
+    /// carrying the error the marker was on top of.
+    ///
+    /// The failure arm is balanced against the rest exactly as a `?`'s is,
+    /// by [`crate::arity::balance_early_returns`], and in a block that reports
+    /// how it was left it reports that it is carrying the error further.
+    fn carry_out(
+        &mut self,
+        scope: ModuleId,
+        fail_prefix: Vec<Instruction>,
+        tail: Vec<ParsedInstruction>,
+    ) -> Result<Vec<Instruction>, String> {
+        let rest = self.compile_sentence_body(scope, tail)?;
+        let rest = self.push_block(rest);
+        let fail = self.push_block(fail_prefix);
+        self.early_returns.push(EarlyReturn {
+            rest,
+            fail,
+            in_sentence: self.current_sentence.clone(),
+            written: "`?!`",
+            marker: self.exit.carrying(),
+        });
+        Ok(vec![Instruction::Branch(rest, fail)])
+    }
+
+    /// A block that reports how it was left. A label is wrapped in one, so
+    /// that a `branch` with a `?!` in one arm can have a label for the other.
+    fn marked_block(&mut self, scope: ModuleId, target: Target) -> Result<SentenceIndex, String> {
+        let instructions = match target {
+            Target::Inline(body) => body.instructions,
+            label @ (Target::Label(_) | Target::Instance(..)) => {
+                vec![ParsedInstruction::Jump(label)]
+            }
+        };
+        self.inline_block(scope, instructions, Exit::Marked)
+    }
 
+    /// `?`, as the branches a user could have written in its place.
+    ///
+    /// A result is `(value, tag)` with `tag` being `crate::prelude::ok` or
+    /// `crate::prelude::err`, so the expansion asks whether it was handed a
+    /// pair at all, takes that pair apart, asks which tag it carries, and puts
+    /// the rest of the block in the arm that runs when the answer is `ok`:
+    ///
+    /// ```text
+    /// pick 0 pick 0 as_tuple 2 equal
+    /// branch { untuple 2 push ok equal } { push false }
+    /// branch { ...the rest of the block... } { push err tuple 2 }
+    /// ```
+    ///
+    /// The first branch asks whether there is a result to take apart at all: a
+    /// value that is not a 2-tuple is not one, and rather than failing on it,
+    /// `?` calls it an error carrying that value — which is why the else arm
+    /// leaves the value alone and only answers `false`. That is what keeps `?`
+    /// total (see `docs/totality.md`).
+    ///
+    /// Asking before unpacking is what `untuple` used to answer for itself. It
+    /// is the same question, written where the one caller that wants it is.
+    ///
+    /// The failure arm is left one step short: it rebuilds the error but does
+    /// not yet drop whatever the block was holding underneath it, because how
+    /// much that is depends on the arity of the rest of the block, which is not
+    /// known until every sentence has been emitted.
+    /// [`crate::arity::balance_early_returns`] finishes it.
+    fn compile_try(
+        &mut self,
+        scope: ModuleId,
+        tail: Vec<ParsedInstruction>,
+        written: &'static str,
+        marker: Option<bool>,
+    ) -> Result<Vec<Instruction>, String> {
         // The rest of the block first, so that a `?` nested inside it records
         // itself before this one does: balancing reads the arity of a rest arm,
         // and an arm with an unbalanced `?` in it does not have one yet.
         let rest = self.compile_sentence_body(scope, tail)?;
         let rest = self.push_block(rest);
+        self.early_return(scope, rest, written, marker)
+    }
+
+    /// The instructions `?` stands for, given the block that runs on `ok`,
+    /// already emitted. Records the site for balancing, so `rest` must not
+    /// hold an unrecorded `?` of its own. `marker` is what the failure arm
+    /// pushes last, in a block that reports how it was left (see [`Exit`]).
+    fn early_return(
+        &mut self,
+        scope: ModuleId,
+        rest: SentenceIndex,
+        written: &'static str,
+        marker: Option<bool>,
+    ) -> Result<Vec<Instruction>, String> {
+        let ok = self.prelude_symbol(scope, "ok", written)?;
+        let err = self.prelude_symbol(scope, "err", written)?;
 
         let is_ok = self.push_block(vec![
             Instruction::Untuple(2),
@@ -1587,6 +2426,8 @@ impl<'a> Compiler<'a> {
             rest,
             fail,
             in_sentence: self.current_sentence.clone(),
+            written,
+            marker,
         });
         Ok(vec![
             // Two copies: one for `as_tuple` to coerce and one for `equal` to
@@ -1601,32 +2442,242 @@ impl<'a> Compiler<'a> {
         ])
     }
 
-    /// The tag symbols `?` compares against.
+    /// `repeat`, as the jumps a user could have written in its place.
+    ///
+    /// The body is resolved once — an inline block becomes one sentence, as
+    /// it would after `jump` — and each iteration is a jump to it, preceded by
+    /// `push i` when the loop is `with index`. So `repeat 3 { … }` is
+    /// `jump b ; jump b ; jump b`, and the program stays free of recursion
+    /// because the count was a literal.
+    ///
+    /// `while ok` puts a `?` after every iteration but the last, each with the
+    /// iterations after it as its rest arm, which is the shape `?` would have
+    /// taken had the user written `jump b ? jump b ? jump b` in a block of its
+    /// own:
+    ///
+    /// ```text
+    /// jump b
+    /// …?… { jump b
+    ///       …?… { jump b } { push err tuple 2 } }
+    ///     { push err tuple 2 }
+    /// ```
+    ///
+    /// The last iteration's result is the loop's, and an `err` from any other
+    /// stops the ones after it and is the loop's instead. The rest arms are
+    /// built last iteration first, so the `?`s arrive for balancing innermost
+    /// first. `repeat 0 while ok` runs nothing and answers `ok`.
+    fn compile_repeat(&mut self, scope: ModuleId, r: Repeat) -> Result<Vec<Instruction>, String> {
+        let body = self.resolve_target(scope, r.body)?;
+        let step = |i: usize| {
+            let mut insts = Vec::new();
+            if r.with_index {
+                insts.push(Instruction::Push(Value::Int(i as i64)));
+            }
+            insts.push(Instruction::Jump(body));
+            insts
+        };
+        if !r.while_ok {
+            return Ok((0..r.count).flat_map(step).collect());
+        }
+        if r.count == 0 {
+            let ok = self.prelude_symbol(scope, "ok", "`while ok`")?;
+            return Ok(vec![Instruction::Push(ok), Instruction::Tuple(2)]);
+        }
+
+        let mut rest = None;
+        for i in (1..r.count).rev() {
+            let mut insts = step(i);
+            if let Some(rest) = rest {
+                insts.extend(self.early_return(scope, rest, "`while ok`", None)?);
+            }
+            rest = Some(self.push_block(insts));
+        }
+        let mut insts = step(0);
+        if let Some(rest) = rest {
+            insts.extend(self.early_return(scope, rest, "`while ok`", None)?);
+        }
+        Ok(insts)
+    }
+
+    /// `match`, as the chain of tag comparisons a user could have written in
+    /// its place.
+    ///
+    /// A value of an enum is `(payload, tag)`, so the chain takes it apart and
+    /// compares the tag against each named variant's in the order the arms
+    /// were written, with the arm's block in the branch that answers `true`:
+    ///
+    /// ```text
+    /// untuple 2
+    /// pick 0 push E::A::tag equal
+    /// branch { drop 0 untuple <A's width> ...A's arm... } {
+    ///     ...the same for the next arm...
+    /// }
+    /// ```
+    ///
+    /// The end of the chain asks nothing. A `_` arm is what is left, and gets
+    /// the value put back together; without one, the arms cover every variant,
+    /// so the last of them is the only one left to be. A `junk` arm puts one
+    /// question in front of all of it — `pick 0` and the enum's own `check` —
+    /// and runs on the untouched value when the answer is no. Without one,
+    /// nothing asks: a value that is not of the enum is taken apart the way
+    /// `untuple` takes anything apart, and lands in whichever arm its tag
+    /// selects, or at the end of the chain.
+    fn expand_match(
+        &mut self,
+        scope: ModuleId,
+        m: Match,
+    ) -> Result<Vec<ParsedInstruction>, String> {
+        let what = format!("In '{}': `match {}`", self.current_sentence, m.enum_path);
+        let variants = match self.tree.resolve_entry(scope, &m.enum_path) {
+            Ok(ModuleItem::Mod(id)) => self.enums.get(id),
+            Ok(_) => None,
+            Err(e) => return Err(format!("{}: {}", what, e)),
+        }
+        .ok_or_else(|| format!("{} names something that is not an enum", what))?;
+
+        let mut arms = Vec::new();
+        let mut wildcard = None;
+        let mut junk = None;
+        for arm in m.arms {
+            match arm.pattern {
+                Pattern::Variant(name) => {
+                    let Some(variant) = variants.iter().find(|v| v.name == name) else {
+                        let names: Vec<&str> = variants.iter().map(|v| v.name.as_str()).collect();
+                        return Err(format!(
+                            "{}: `{}` has no variant `{}` (its variants are: {})",
+                            what,
+                            m.enum_path,
+                            name,
+                            names.join(", ")
+                        ));
+                    };
+                    arms.push((variant, arm.body.instructions));
+                }
+                Pattern::Wildcard => wildcard = Some(arm.body.instructions),
+                Pattern::Junk => junk = Some(arm.body.instructions),
+            }
+        }
+
+        let missing: Vec<&str> = variants
+            .iter()
+            .filter(|v| !arms.iter().any(|(covered, _)| covered.name == v.name))
+            .map(|v| v.name.as_str())
+            .collect();
+        match (&wildcard, missing.is_empty()) {
+            (None, false) => {
+                return Err(format!(
+                    "{} does not cover {}; give each an arm, or add a `_` arm",
+                    what,
+                    missing
+                        .iter()
+                        .map(|name| format!("`{}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+            (Some(_), true) => {
+                return Err(format!(
+                    "{}: the `_` arm can never run, since every variant already has an arm",
+                    what
+                ));
+            }
+            _ => {}
+        }
+        if arms.is_empty() && wildcard.is_none() {
+            return Err(format!(
+                "{}: `{}` has no variants, so there is nothing for an arm to match",
+                what, m.enum_path
+            ));
+        }
+
+        let tag = |name: &str| {
+            let mut path = m.enum_path.clone();
+            path.segments
+                .push(PathSegment::Identifier(name.to_string()));
+            path.segments
+                .push(PathSegment::Identifier("tag".to_string()));
+            path
+        };
+        let unpacked = |width: usize, body: Vec<ParsedInstruction>| {
+            let mut insts = vec![
+                ParsedInstruction::Drop(0),
+                ParsedInstruction::Untuple(width),
+            ];
+            insts.extend(body);
+            insts
+        };
+
+        // Built from the end, so each test's else arm is the chain already
+        // built for the arms after it.
+        let mut chain = match wildcard {
+            Some(body) => {
+                let mut insts = vec![ParsedInstruction::Tuple(2)];
+                insts.extend(body);
+                insts
+            }
+            None => {
+                let (last, body) = arms.pop().expect("checked above: an arm is left");
+                unpacked(last.width, body)
+            }
+        };
+        for (variant, body) in arms.into_iter().rev() {
+            chain = vec![
+                ParsedInstruction::Pick(0),
+                ParsedInstruction::Push(ParsedValue::Ref(tag(&variant.name))),
+                ParsedInstruction::Equal,
+                ParsedInstruction::Branch(
+                    Target::Inline(ParsedSentence {
+                        instructions: unpacked(variant.width, body),
+                    }),
+                    Target::Inline(ParsedSentence {
+                        instructions: chain,
+                    }),
+                ),
+            ];
+        }
+
+        let mut dispatch = vec![ParsedInstruction::Untuple(2)];
+        dispatch.extend(chain);
+        let expansion = match junk {
+            None => dispatch,
+            Some(body) => vec![
+                ParsedInstruction::Pick(0),
+                ParsedInstruction::TypeCheckPath(m.enum_path.clone()),
+                ParsedInstruction::Branch(
+                    Target::Inline(ParsedSentence {
+                        instructions: dispatch,
+                    }),
+                    Target::Inline(ParsedSentence { instructions: body }),
+                ),
+            ],
+        };
+        Ok(expansion)
+    }
+
+    /// The tag symbols `?` compares against, and `map_get` answers with.
+    /// `reader` is how the source spelled whichever wanted them, for the error.
     ///
     /// Absolute, not resolved against the sentence's own module: which symbol
     /// marks an error is a property of the program rather than of the place the
     /// `?` was written. Nothing declares them for you — a program that uses `?`
     /// says what its tags are.
-    fn prelude_symbol(&self, scope: ModuleId, name: &str) -> Result<Value, String> {
-        let path = Path {
-            segments: vec![
-                PathSegment::Crate,
-                PathSegment::Identifier("prelude".to_string()),
-                PathSegment::Identifier(name.to_string()),
-            ],
-        };
+    fn prelude_symbol(&self, scope: ModuleId, name: &str, reader: &str) -> Result<Value, String> {
         let missing = || {
             format!(
-                "`?` reads the tag `crate::prelude::{}`, which this program does not \
-                 declare; a program that uses `?` needs `mod prelude {{ symbol ok symbol err }}`",
-                name
+                "{} reads the tag `crate::prelude::{}`, which this program does not \
+                 declare; a program that uses {} needs `mod prelude {{ symbol ok symbol err }}`",
+                reader, name, reader
             )
         };
-        match self.tree.resolve(scope, &path).map_err(|_| missing())? {
+        match self
+            .tree
+            .resolve(scope, &prelude_path(name))
+            .map_err(|_| missing())?
+        {
             ResolvedItem::Const(val) => Ok(val),
             ResolvedItem::Sentence(_) => Err(format!(
-                "`crate::prelude::{}` names a sentence, but `?` compares against it as a value",
-                name
+                "`crate::prelude::{}` names a sentence, but {} reads it as a value",
+                name, reader
             )),
         }
     }
@@ -1715,11 +2766,16 @@ impl<'a> Compiler<'a> {
     fn resolve_target(&mut self, scope: ModuleId, target: Target) -> Result<SentenceIndex, String> {
         match target {
             Target::Label(path) => {
-                match self
-                    .tree
-                    .resolve(scope, &path)
-                    .map_err(|e| format!("Unresolved label target: {}", e))?
+                if let [PathSegment::Identifier(name)] = path.segments.as_slice()
+                    && let Some(&idx) = self.arguments.get(name)
                 {
+                    return Ok(idx);
+                }
+                let resolved = self.resolve(scope, &path).map_err(|e| {
+                    self.missing_get(scope, &path)
+                        .unwrap_or_else(|| format!("Unresolved label target: {}", e))
+                })?;
+                match resolved {
                     ResolvedItem::Sentence(idx) => Ok(idx),
                     ResolvedItem::Const(_) => Err(format!(
                         "Expected sentence, found a value at path {:?}",
@@ -1727,26 +2783,405 @@ impl<'a> Compiler<'a> {
                     )),
                 }
             }
+            Target::Instance(path, args) => self.instantiate(scope, path, args),
             Target::Inline(parsed_sentence) => {
-                let new_idx = SentenceIndex::from(self.sentences.len());
-                self.sentences.push(Vec::new());
-                self.names.push("<inline>".to_string());
+                self.inline_block(scope, parsed_sentence.instructions, Exit::Plain)
+            }
+        }
+    }
 
-                // A branch arm or dip body carries no annotations of its own:
-                // it is part of the sentence that wrote it, and every
-                // annotation left is a claim about a sentence being called.
-                self.annotations.push(Vec::new());
+    /// Why `path` names no sentence, when it is an enum variant's `get` that
+    /// went undeclared because the program has no result tags. Those are only
+    /// needed once something asks for the accessor, so this is where the
+    /// program hears about them.
+    fn missing_get(&self, scope: ModuleId, path: &Path) -> Option<String> {
+        let (last, parent) = path.segments.split_last()?;
+        if *last != PathSegment::Identifier("get".to_string()) || parent.is_empty() {
+            return None;
+        }
+        let parent = Path {
+            segments: parent.to_vec(),
+        };
+        let ModuleItem::Mod(variant) = self.tree.resolve_entry(scope, &parent).ok()? else {
+            return None;
+        };
+        let name = self.tree.path_of(*variant).pop()?;
+        let variants = self.enums.get(&self.tree.parent(*variant)?)?;
+        if !variants.iter().any(|v| v.name == name) {
+            return None;
+        }
+        let reader = format!("`{}`", path);
+        let missing = ["ok", "err"]
+            .into_iter()
+            .find_map(|tag| self.prelude_symbol(scope, tag, &reader).err())?;
+        Some(format!("In '{}': {}", self.current_sentence, missing))
+    }
 
-                let compiled_body =
-                    self.compile_sentence_body(scope, parsed_sentence.instructions)?;
-                let idx_usize: usize = new_idx.into();
-                self.sentences[idx_usize] = compiled_body;
-                Ok(new_idx)
+    /// The instance of the generic at `path` given `args`, compiled the first
+    /// time a call site asks for it.
+    ///
+    /// The arguments are resolved where they were written; the body is
+    /// compiled in the generic's own module, with its parameters standing for
+    /// them. An instance is an ordinary sentence from then on, named after
+    /// the generic and its arguments — `util::twice<util::inc>` — so two
+    /// compilations of one program name it alike.
+    fn instantiate(
+        &mut self,
+        scope: ModuleId,
+        path: Path,
+        args: Vec<Target>,
+    ) -> Result<SentenceIndex, String> {
+        let generic = match self
+            .tree
+            .resolve_entry(scope, &path)
+            .map_err(|e| format!("Unresolved label target: {}", e))?
+        {
+            ModuleItem::Generic(g) => *g,
+            other => {
+                return Err(format!(
+                    "'{}' names a {}, which takes no `<...>` arguments",
+                    path,
+                    other.describe()
+                ));
             }
+        };
+        let generics = self.generics;
+        let (decl_scope, decl) = &generics[generic];
+        let fq_name = self.tree.fq_name(*decl_scope, &decl.name);
+        if args.len() != decl.generics.len() {
+            return Err(format!(
+                "`{}` takes {} sentence argument(s) ({}), but {} were given",
+                fq_name,
+                decl.generics.len(),
+                decl.generics.join(", "),
+                args.len()
+            ));
         }
+        let args = args
+            .into_iter()
+            .map(|arg| self.resolve_target(scope, arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Checked before the cache: an instance that reaches its own generic
+        // either asks for itself, which is recursion, or for an instance with
+        // other arguments, which may ask for another, without end.
+        if self.instantiating.contains(&generic) {
+            return Err(format!(
+                "generic sentence `{}` instantiates itself",
+                fq_name
+            ));
+        }
+        let key = (generic, args);
+        if let Some(&idx) = self.monomorphs.get(&key) {
+            return Ok(idx);
+        }
+
+        let arg_names: Vec<&str> = key
+            .1
+            .iter()
+            .map(|&idx| self.names[usize::from(idx)].as_str())
+            .collect();
+        let name = format!("{}<{}>", fq_name, arg_names.join(", "));
+        let new_idx = SentenceIndex::from(self.sentences.len());
+        self.sentences.push(Vec::new());
+        self.names.push(name.clone());
+        self.annotations.push(Vec::new());
+
+        let annotations = self
+            .resolve_annotations(*decl_scope, &decl.annotations)
+            .map_err(|e| format!("In '{}': {}", name, e))?;
+        self.annotations[usize::from(new_idx)] = annotations;
+
+        let bound = decl.generics.iter().cloned().zip(key.1.iter().copied());
+        let mut instantiating = self.instantiating.clone();
+        instantiating.push(generic);
+        let outer = self.enter(name.clone(), bound.collect(), instantiating);
+        self.monomorphs.insert(key, new_idx);
+        self.unfinished.insert(new_idx);
+
+        let compiled = self.compile_sentence_body(*decl_scope, decl.body.instructions.clone());
+
+        self.unfinished.remove(&new_idx);
+        self.leave(outer);
+        self.sentences[usize::from(new_idx)] =
+            compiled.map_err(|e| format!("In '{}': {}", name, e))?;
+        Ok(new_idx)
+    }
+
+    /// Sets the compiler up for a sentence of its own, answering what it was
+    /// set up for before.
+    fn enter(
+        &mut self,
+        name: String,
+        arguments: HashMap<String, SentenceIndex>,
+        instantiating: Vec<GenericIndex>,
+    ) -> Context {
+        Context {
+            current_sentence: std::mem::replace(&mut self.current_sentence, name),
+            exit: std::mem::replace(&mut self.exit, Exit::Plain),
+            arguments: std::mem::replace(&mut self.arguments, arguments),
+            instantiating: std::mem::replace(&mut self.instantiating, instantiating),
+        }
+    }
+
+    /// Goes back to the sentence [`Compiler::enter`] set aside.
+    fn leave(&mut self, outer: Context) {
+        self.current_sentence = outer.current_sentence;
+        self.exit = outer.exit;
+        self.arguments = outer.arguments;
+        self.instantiating = outer.instantiating;
+    }
+
+    /// Compiles the named sentence at `idx`, unless something that needed it
+    /// first already has.
+    fn compile_named(&mut self, idx: SentenceIndex) -> Result<(), Error> {
+        let i = usize::from(idx);
+        let Some((scope, sentence)) = self.pending.get_mut(i).and_then(Option::take) else {
+            return Ok(());
+        };
+        let name = self.names[i].clone();
+        let outer = self.enter(name.clone(), HashMap::new(), Vec::new());
+        self.unfinished.insert(idx);
+
+        let span = sentence.span;
+        let compiled = match self.resolve_annotations(scope, &sentence.annotations) {
+            Ok(annotations) => {
+                self.annotations[i] = annotations;
+                self.compile_sentence_body(scope, sentence.body.instructions)
+                    .map_err(Error::from)
+            }
+            Err(e) => Err(format!("In '{}': {}", name, e).into()),
+        };
+
+        self.unfinished.remove(&idx);
+        self.leave(outer);
+        self.sentences[i] =
+            compiled.map_err(|e| place_in_instance(self.tree, self.instances, scope, e, span))?;
+        Ok(())
+    }
+
+    /// The value of a `const`, worked out the first time it is asked for.
+    fn evaluate(&mut self, c: ConstIndex) -> Result<Value, String> {
+        if let Some(val) = self.values.get(&c) {
+            return Ok(val.clone());
+        }
+        let consts = self.consts;
+        let (scope, decl) = &consts[c];
+        let name = self.tree.fq_name(*scope, &decl.name);
+        if self.evaluating.contains(&c) {
+            return Err(format!("const `{}` depends on its own value", name));
+        }
+
+        self.evaluating.push(c);
+        let body_name = self.tree.fq_name(*scope, &format!("<const {}>", decl.name));
+        let left = self.run(*scope, decl.body.clone(), body_name);
+        self.evaluating.pop();
+
+        match left
+            .map_err(|e| format!("In const `{}`: {}", name, e))?
+            .as_slice()
+        {
+            [val] => {
+                self.values.insert(c, val.clone());
+                Ok(val.clone())
+            }
+            other => Err(format!(
+                "const `{}` has to leave one value, but left {}",
+                name,
+                describe_stack(other)
+            )),
+        }
+    }
+
+    /// Compiles `body` into a sentence of its own, called `name`, and runs it
+    /// on an empty stack, answering what it leaves.
+    fn run(
+        &mut self,
+        scope: ModuleId,
+        body: ParsedSentence,
+        name: String,
+    ) -> Result<Vec<Value>, String> {
+        let Some(evaluator) = self.evaluator else {
+            return Err(
+                "it has to be run to be compiled, and this assembly has nothing to run it: \
+                 assemble through `vm::assemble`"
+                    .to_string(),
+            );
+        };
+
+        let idx = SentenceIndex::from(self.sentences.len());
+        self.sentences.push(Vec::new());
+        self.names.push(name.clone());
+        // Nothing goes in and one value comes out, which `check_arities`
+        // holds it to afterwards like any other claim.
+        self.annotations.push(vec![Annotation::Arity(0, 1)]);
+        let outer = self.enter(name, HashMap::new(), Vec::new());
+        self.unfinished.insert(idx);
+        let compiled = self.compile_sentence_body(scope, body.instructions);
+        self.unfinished.remove(&idx);
+        self.leave(outer);
+        self.sentences[usize::from(idx)] = compiled?;
+
+        let reached = self.close(idx)?;
+
+        // The early returns it can reach are balanced on a copy, the same way
+        // phase 5 balances them all: the VM runs what the program will run.
+        let mut library = Library::new();
+        library.sentences = self.sentences.clone().into();
+        library.names = self.names.clone().into();
+        library.annotations = self.annotations.clone().into();
+        let sites: Vec<EarlyReturn> = self
+            .early_returns
+            .iter()
+            .filter(|site| reached.contains(&site.rest))
+            .cloned()
+            .collect();
+        crate::arity::balance_early_returns(&mut library, &sites)?;
+        evaluator(library, idx)
+    }
+
+    /// Compiles everything `root` calls that has not been compiled yet, all
+    /// the way down, and answers every sentence it can reach.
+    ///
+    /// The VM is about to run `root`, and it is sure to stop only because
+    /// recursion is forbidden. `check_arities` refuses recursion, but after
+    /// the fact; so a cycle among these is refused here, before the run.
+    fn close(&mut self, root: SentenceIndex) -> Result<HashSet<SentenceIndex>, String> {
+        let mut reached = HashSet::new();
+        self.reach_from(root, &mut Vec::new(), &mut reached)?;
+        Ok(reached)
+    }
+
+    fn reach_from(
+        &mut self,
+        idx: SentenceIndex,
+        path: &mut Vec<SentenceIndex>,
+        reached: &mut HashSet<SentenceIndex>,
+    ) -> Result<(), String> {
+        if reached.contains(&idx) {
+            return Ok(());
+        }
+        let i = usize::from(idx);
+        if path.contains(&idx) {
+            return Err(format!(
+                "'{}' calls itself, so running it might never stop",
+                self.names[i]
+            ));
+        }
+        self.compile_named(idx).map_err(|e| e.to_string())?;
+        if self.unfinished.contains(&idx) {
+            return Err(format!(
+                "it calls '{}', which is still being compiled because it needs this value",
+                self.names[i]
+            ));
+        }
+
+        path.push(idx);
+        let callees: Vec<SentenceIndex> = self.sentences[i]
+            .iter()
+            .flat_map(|inst| match inst {
+                Instruction::Branch(then, otherwise) => vec![*then, *otherwise],
+                other => other.callee().into_iter().collect(),
+            })
+            .collect();
+        for callee in callees {
+            self.reach_from(callee, path, reached)?;
+        }
+        path.pop();
+        reached.insert(idx);
+        Ok(())
+    }
+
+    /// Flattens a block into a freshly allocated sentence, leaving it the way
+    /// `exit` says. The block's index is taken before its body is compiled,
+    /// so it comes before any block inside it.
+    fn inline_block(
+        &mut self,
+        scope: ModuleId,
+        instructions: Vec<ParsedInstruction>,
+        exit: Exit,
+    ) -> Result<SentenceIndex, String> {
+        let new_idx = SentenceIndex::from(self.sentences.len());
+        self.sentences.push(Vec::new());
+        self.names.push("<inline>".to_string());
+
+        // A branch arm or dip body carries no annotations of its own: it is
+        // part of the sentence that wrote it, and every annotation left is a
+        // claim about a sentence being called.
+        self.annotations.push(Vec::new());
+
+        let outer = std::mem::replace(&mut self.exit, exit);
+        let compiled_body = self.compile_sentence_body(scope, instructions);
+        self.exit = outer;
+        let idx_usize: usize = new_idx.into();
+        self.sentences[idx_usize] = compiled_body?;
+        Ok(new_idx)
+    }
+}
+
+/// `crate::prelude::<name>`, where a program declares its result tags.
+fn prelude_path(name: &str) -> Path {
+    Path {
+        segments: vec![
+            PathSegment::Crate,
+            PathSegment::Identifier("prelude".to_string()),
+            PathSegment::Identifier(name.to_string()),
+        ],
     }
 }
 
+/// Points an error raised in `scope` at the instantiation that generated it,
+/// if one did, with a note at `item`, the part of the template's body the
+/// error came from, when the template is one the user declared.
+///
+/// The innermost instance is the one reported: its site is the nearest place
+/// in the source that wrote the code in question, even when that site is
+/// itself inside another template. An error that already points somewhere
+/// other than `item` has been placed, and is left where it is.
+fn place_in_instance(
+    tree: &ModuleTree,
+    instances: &HashMap<ModuleId, core::Instance>,
+    scope: ModuleId,
+    err: Error,
+    item: Option<Span>,
+) -> Error {
+    if err.span.is_some() && err.span != item {
+        return err;
+    }
+    let mut module = Some(scope);
+    while let Some(id) = module {
+        if let Some(instance) = instances.get(&id) {
+            let placed = Error {
+                span: Some(instance.site),
+                ..err
+            };
+            let module = tree.path_of(id).join("::");
+            return match (item, instance.declared) {
+                (Some(item), Some(_)) => placed.with_note(
+                    format!(
+                        "`{}` is an instance of template `{}`, which wrote this",
+                        module, instance.template
+                    ),
+                    item,
+                ),
+                (None, Some(declared)) => placed.with_note(
+                    format!(
+                        "`{}` is an instance of template `{}`, declared here",
+                        module, instance.template
+                    ),
+                    declared,
+                ),
+                (_, None) => placed.with_help(format!(
+                    "`{}` is generated by `{}`",
+                    module, instance.template
+                )),
+            };
+        }
+        module = tree.parent(id);
+    }
+    err
+}
+
 /// Assembles the input text into a `Library`, rendering any error as text.
 ///
 /// A convenience for callers with a single source string and no file on disk;
@@ -1775,26 +3210,45 @@ pub fn assemble_source(
     map: &mut SourceMap,
     file: FileId,
     base_dir: Option<&std::path::Path>,
+) -> Result<Library, Error> {
+    assemble_source_evaluating(map, file, base_dir, None)
+}
+
+/// Assembles a source file as [`assemble_source`] does, running every `const`
+/// and `static_assert` body with `evaluator`. Without one, a program that has
+/// either does not assemble.
+pub fn assemble_source_evaluating(
+    map: &mut SourceMap,
+    file: FileId,
+    base_dir: Option<&std::path::Path>,
+    evaluator: Option<Evaluator>,
 ) -> Result<Library, Error> {
     let parsed = parse_source(map, file, base_dir)?;
     let items = crate::lower::lower_items(parsed)?;
 
     let mut builder = TreeBuilder::new();
     builder.build(items, crate::resolve::ROOT)?;
+    builder.declare_accessors()?;
 
     let TreeBuilder {
         tree,
         sentence_counter,
         flat_sentences,
+        generics,
+        consts,
+        static_asserts,
         exports,
         tests,
         test_machines,
         identities,
+        enums,
+        instances,
         ..
     } = builder;
 
     let mut compiler = Compiler {
         tree: &tree,
+        enums: &enums,
         sentences: Vec::new(),
         names: Vec::new(),
         annotations: Vec::new(),
@@ -1802,24 +3256,61 @@ pub fn assemble_source(
         current_sentence: String::new(),
         reaches: HashMap::new(),
         frames: HashMap::new(),
+        exit: Exit::Plain,
+        generics: &generics,
+        monomorphs: HashMap::new(),
+        arguments: HashMap::new(),
+        instantiating: Vec::new(),
+        pending: Vec::new(),
+        unfinished: HashSet::new(),
+        instances: &instances,
+        consts: &consts,
+        values: HashMap::new(),
+        evaluating: Vec::new(),
+        evaluator,
     };
 
-    // Pre-allocate space for all named sentences
+    // Pre-allocate space for all named sentences. Their names are filled in
+    // up front, since an instance is named after its arguments and may be
+    // compiled before they are.
     compiler.sentences.resize(sentence_counter, Vec::new());
-    compiler.names.resize(sentence_counter, String::new());
+    compiler.names = flat_sentences
+        .iter()
+        .map(|(scope, sentence)| tree.fq_name(*scope, &sentence.name))
+        .collect();
     compiler.annotations.resize(sentence_counter, Vec::new());
+    compiler.pending = flat_sentences.into_iter().map(Some).collect();
+
+    // Every const first, so that a sentence using one finds it worked out.
+    // Each compiles whatever it calls as it goes.
+    for (c, (scope, decl)) in consts.iter_enumerated() {
+        compiler.evaluate(c).map_err(|e| {
+            let e = Error::at(e, decl.span);
+            place_in_instance(&tree, &instances, *scope, e, Some(decl.span))
+        })?;
+    }
 
-    // Compile instructions recursively
-    for (idx, (scope, sentence)) in flat_sentences.into_iter().enumerate() {
-        let name = tree.fq_name(scope, &sentence.name);
-        compiler.current_sentence = name.clone();
-        compiler.annotations[idx] = compiler
-            .resolve_annotations(scope, &sentence.annotations)
-            .map_err(|e| format!("In '{}': {}", name, e))?;
-        let compiled_instructions =
-            compiler.compile_sentence_body(scope, sentence.body.instructions)?;
-        compiler.sentences[idx] = compiled_instructions;
-        compiler.names[idx] = name;
+    for idx in 0..sentence_counter {
+        compiler.compile_named(SentenceIndex::from(idx))?;
+    }
+
+    for (scope, decl) in static_asserts {
+        let place = |e: Error| place_in_instance(&tree, &instances, scope, e, Some(decl.span));
+        let name = tree.fq_name(scope, "<static_assert>");
+        let left = compiler.run(scope, decl.body, name).map_err(|e| {
+            place(Error::at(
+                format!("In a static assertion: {}", e),
+                decl.span,
+            ))
+        })?;
+        if left != [Value::Bool(true)] {
+            return Err(place(
+                Error::at("static assertion failed", decl.span).with_help(format!(
+                    "it has to leave `true`, and left {}",
+                    describe_stack(&left)
+                )),
+            ));
+        }
     }
 
     let early_returns = compiler.early_returns;
@@ -1955,6 +3446,84 @@ mod tests {
         assert!(rendered.contains("--> main.hana:2:12"), "{}", rendered);
     }
 
+    #[test]
+    fn a_duplicate_match_arm_points_at_the_second_one() {
+        let rendered = error_for("sentence a {\n    match E { A { } _ { } A { } }\n}\n");
+        assert!(
+            rendered.contains("duplicate `match` arm `A`"),
+            "{}",
+            rendered
+        );
+        assert!(rendered.contains("--> main.hana:2:27"), "{}", rendered);
+    }
+
+    #[test]
+    fn a_duplicate_record_field_points_at_the_second_one() {
+        let rendered = error_for("record R {\n    x: int,\n    x: bool,\n}\n");
+        assert!(
+            rendered.contains("duplicate field `x` in record `R`"),
+            "{}",
+            rendered
+        );
+        assert!(rendered.contains("--> main.hana:3:5"), "{}", rendered);
+    }
+
+    #[test]
+    fn a_repeat_clause_must_be_spelled_out() {
+        let rendered = error_for("sentence a {\n    repeat 3 with i { }\n}\n");
+        assert!(
+            rendered.contains("expected `index` after `with`, found `i`"),
+            "{}",
+            rendered
+        );
+        assert!(rendered.contains("--> main.hana:2:19"), "{}", rendered);
+    }
+
+    #[test]
+    fn a_duplicate_template_parameter_points_at_the_second_one() {
+        let rendered = error_for("template mod t(a, b, a) { }\n");
+        assert!(
+            rendered.contains("duplicate parameter `a` in template `t`"),
+            "{}",
+            rendered
+        );
+        assert!(rendered.contains("--> main.hana:1:22"), "{}", rendered);
+    }
+
+    #[test]
+    fn a_duplicate_generic_parameter_points_at_the_second_one() {
+        let rendered = error_for("sentence s<f, f> { }\n");
+        assert!(
+            rendered.contains("duplicate parameter `f` in generic sentence `s`"),
+            "{}",
+            rendered
+        );
+        assert!(rendered.contains("--> main.hana:1:15"), "{}", rendered);
+    }
+
+    #[test]
+    fn a_generic_argument_is_never_a_block() {
+        let rendered = error_for("sentence s { jump t<{ add }> }\n");
+        assert!(
+            rendered.contains("expected a sentence name, found `{`"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn a_return_is_one_token() {
+        let tokens = spans("? ?! ?");
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::Question, "?"),
+                (Token::QuestionBang, "?!"),
+                (Token::Question, "?"),
+            ]
+        );
+    }
+
     #[test]
     fn errors_name_tokens_as_written_not_as_variants() {
         let rendered = error_for("sentence a { push 1 } }");
@@ -2002,6 +3571,135 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
+    // -----------------------------------------------------------------------
+    // `from "path"`
+    // -----------------------------------------------------------------------
+
+    /// Writes `files` into a fresh directory and parses `main` from it.
+    fn parse_with_files(
+        dir: &str,
+        main: &str,
+        files: &[(&str, &str)],
+    ) -> Result<Vec<sugar::Item>, String> {
+        let dir = std::env::temp_dir().join(dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, text) in files {
+            std::fs::write(dir.join(name), text).unwrap();
+        }
+        let path = dir.join("main.hana");
+        std::fs::write(&path, main).unwrap();
+
+        let mut map = SourceMap::new();
+        let root = map.add_path(&path, main.to_string());
+        let parsed = parse_source(&mut map, root, Some(&dir)).map_err(|e| map.render(&e));
+        let _ = std::fs::remove_dir_all(&dir);
+        parsed
+    }
+
+    fn data_value(items: &[sugar::Item]) -> &Value {
+        match items {
+            [sugar::Item::Data(decl)] => &decl.value,
+            other => panic!("expected one data item, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_const_string_reads_a_file_without_its_last_line_ending() {
+        let items = parse_with_files(
+            "hanoi_data_const_string_test",
+            "const_string input from \"input.txt\"\n",
+            &[("input.txt", "39472\n")],
+        )
+        .unwrap();
+        match items.as_slice() {
+            [sugar::Item::ConstString(decl)] => assert_eq!(decl.text, "39472"),
+            other => panic!("expected one const string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_data_file_reads_as_lines_unless_it_is_json() {
+        let items = parse_with_files(
+            "hanoi_data_lines_test",
+            "const rows from \"rows.txt\";\n",
+            &[("rows.txt", "a b\r\n\nc\n")],
+        )
+        .unwrap();
+        assert_eq!(
+            *data_value(&items),
+            Value::Tuple(vec![
+                Value::ConstString("a b".into()),
+                Value::ConstString("".into()),
+                Value::ConstString("c".into()),
+            ])
+        );
+
+        let items = parse_with_files(
+            "hanoi_data_json_test",
+            "const grid from \"grid.json\";\n",
+            &[(
+                "grid.json",
+                r#"{"size": [2, -3], "name": "caf\u00e9\n", "ok": true}"#,
+            )],
+        )
+        .unwrap();
+        assert_eq!(
+            *data_value(&items),
+            Value::Tuple(vec![
+                Value::Tuple(vec![
+                    Value::ConstString("size".into()),
+                    Value::Tuple(vec![Value::Int(2), Value::Int(-3)]),
+                ]),
+                Value::Tuple(vec![
+                    Value::ConstString("name".into()),
+                    Value::ConstString("café\n".into()),
+                ]),
+                Value::Tuple(vec![Value::ConstString("ok".into()), Value::Bool(true)]),
+            ])
+        );
+    }
+
+    #[test]
+    fn a_missing_data_file_points_at_its_path() {
+        let rendered = parse_with_files(
+            "hanoi_data_missing_test",
+            "const input from \"nowhere.json\";\n",
+            &[],
+        )
+        .expect_err("expected an error");
+        assert!(rendered.contains("cannot read"), "{}", rendered);
+        assert!(rendered.contains("main.hana:1:18"), "{}", rendered);
+    }
+
+    #[test]
+    fn malformed_json_points_into_the_file_and_back_at_the_const() {
+        let rendered = parse_with_files(
+            "hanoi_data_malformed_test",
+            "const input from \"input.json\";\n",
+            &[("input.json", "[1,\n 2.5]\n")],
+        )
+        .expect_err("expected an error");
+        assert!(rendered.contains("fraction or an exponent"), "{}", rendered);
+        assert!(rendered.contains("input.json:2:2"), "{}", rendered);
+        assert!(rendered.contains("read for `const input`"), "{}", rendered);
+
+        let rendered = parse_with_files(
+            "hanoi_data_null_test",
+            "const input from \"input.json\";\n",
+            &[("input.json", "[1, null]")],
+        )
+        .expect_err("expected an error");
+        assert!(rendered.contains("`null` has no value"), "{}", rendered);
+        assert!(rendered.contains("input.json:1:5"), "{}", rendered);
+    }
+
+    #[test]
+    fn a_data_file_needs_a_base_directory() {
+        let err = assemble("const_string input from \"input.txt\"").expect_err("expected an error");
+        assert!(err.contains("nowhere to look"), "{}", err);
+    }
+
     // -----------------------------------------------------------------------
     // `identity`
     // -----------------------------------------------------------------------
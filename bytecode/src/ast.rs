@@ -12,6 +12,7 @@
 use crate::library::Annotation;
 use crate::resolve::Path;
 use crate::source::Span;
+use crate::value::Value;
 
 /// An annotation as written in source: it names its target by path.
 pub type SourceAnnotation = Annotation<Path>;
@@ -33,13 +34,17 @@ pub struct ParsedSentence {
     pub instructions: Vec<ParsedInstruction>,
 }
 
-/// Where a `jump`, `dip` or `branch` goes: a named sentence, or an anonymous block.
+/// Where a `jump`, `dip` or `branch` goes: a named sentence, an instance of a
+/// generic one, or an anonymous block.
 ///
-/// Inline blocks survive lowering and are flattened into their own sentences
-/// during resolution, where sentence indices are already being allocated.
+/// Inline blocks and instances survive lowering, and are turned into sentences
+/// of their own during resolution, where sentence indices are already being
+/// allocated.
 #[derive(Debug, Clone)]
 pub enum Target {
     Label(Path),
+    /// `name<f, g>`. The arguments are only ever labels or further instances.
+    Instance(Path, Vec<Target>),
     Inline(ParsedSentence),
 }
 
@@ -64,27 +69,64 @@ pub enum ParsedInstruction {
     Modulo,
     Not,
     Negate,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Popcount,
     Jump(Target),
     /// Run the target with the top `usize` values of the stack hidden from it.
     Dip(usize, Target),
     Branch(Target, Target),
     Tuple(usize),
     Untuple(usize),
+    TupleGet(usize),
+    TupleSet(usize),
+    TupleConcat,
+    TupleSlice(usize, usize),
+    /// `tuple_map S`: call `S` on each element of a tuple.
+    TupleMap(Target),
+    /// `tuple_fold S`: call `S` on an accumulator and each element of a tuple.
+    TupleFold(Target),
+    MapEmpty,
+    MapInsert,
+    /// `map_get`: look a key up, answering a result. The tags it answers with
+    /// are resolved at emit time, as `?`'s are.
+    MapGet,
+    MapRemove,
+    MapKeys,
     And,
     Or,
     ConstStringLen,
     ConstStringCharAt,
+    ConstStringConcat,
+    ConstStringSlice,
+    ConstStringFromChar,
+    IntToConstString,
+    ConstStringLess,
     IsInt,
     IsBool,
     IsConstString,
     IsSymbol,
     IsTuple,
+    IsMap,
     TupleLength,
     AsBool,
     AsInt,
     AsTuple(usize),
     /// Check the top of stack against the predicate or symbol `Path` names.
     TypeCheckPath(Path),
+    /// `match Enum { Variant { … } … }`: dispatch on an enum value's tag.
+    ///
+    /// Erased at emit time into the chain of tag comparisons a user would have
+    /// written, because checking the arms needs the enum's variants and the
+    /// width of each payload — which only the module tree knows. See
+    /// [`Match`].
+    Match(Match),
+    /// `repeat N { … }`: run a block `N` times, unrolled at emit time. See
+    /// [`Repeat`].
+    Repeat(Repeat),
     /// `?`: unwrap a result, or leave the block early carrying the error.
     ///
     /// Written as punctuation and erased at emit time, like [`Self::Drop`] with
@@ -93,6 +135,76 @@ pub enum ParsedInstruction {
     /// depends on the arity of the instructions that follow it. See
     /// `docs/hana.md` and [`crate::arity::balance_early_returns`].
     Try,
+    /// `?!`: unwrap a result, or leave the whole sentence carrying the error —
+    /// out of every branch arm, `dip` body and inline block it is written in.
+    ///
+    /// At the top level of a sentence body it is [`Self::Try`]. Nested, the
+    /// blocks around it report on the way out whether they finished, and each
+    /// enclosing level leaves early in turn when they did not. See
+    /// `docs/hana.md`.
+    Return,
+}
+
+/// A `repeat` as written: `repeat N [with index] [while ok] <target>`.
+///
+/// The count is a literal, so the unrolled program is finite and holds no
+/// recursion. The body is flattened once, like any other block, and each
+/// iteration is a jump to it: `N` is the number of jumps written out, not the
+/// number of copies of the body.
+#[derive(Debug, Clone)]
+pub struct Repeat {
+    pub count: usize,
+    /// `with index`: push the iteration's index, `0` to `N - 1`, before each
+    /// run of the body, for the body to consume.
+    pub with_index: bool,
+    /// `while ok`: the body answers a result. An `ok` is unwrapped for the
+    /// next iteration, and an `err` ends the loop where it stands, so the
+    /// whole loop answers a result — the last iteration's, or the first error.
+    pub while_ok: bool,
+    pub body: Target,
+}
+
+/// A `match` as written: the enum it dispatches on, and its arms in source
+/// order.
+///
+/// Duplicate arms are refused by the parser, which still has their spans.
+/// Whether the arms cover the enum — and whether every variant they name is
+/// one — is phase 4's to say, since only the module tree knows what the path
+/// names.
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub enum_path: Path,
+    pub arms: Vec<MatchArm>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: ParsedSentence,
+}
+
+/// What a `match` arm answers for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    /// One variant. The arm starts with that variant's payload untupled, so
+    /// its first element is deepest and its last is on top.
+    Variant(String),
+    /// `_`: every variant no other arm names. The arm starts with the whole
+    /// `(payload, tag)` value, since which variant it is was not settled.
+    Wildcard,
+    /// `junk`: a value that is not of the enum at all. The arm starts with
+    /// that value, untouched.
+    Junk,
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Variant(name) => write!(f, "{}", name),
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Junk => write!(f, "junk"),
+        }
+    }
 }
 
 /// A symbol declaration. Shared between sugar and core.
@@ -111,6 +223,17 @@ pub struct ConstStringDecl {
     pub text: String,
 }
 
+/// A `const name from "path";` declaration, once its file has been read.
+/// Shared between sugar and core.
+///
+/// The file is read while parsing, so what is left is a value with nothing in
+/// it to resolve: only ints, bools, const strings and tuples of them.
+#[derive(Debug, Clone)]
+pub struct DataDecl {
+    pub name: String,
+    pub value: Value,
+}
+
 /// A sentence declaration. Shared between sugar and core.
 ///
 /// The `function` keyword is not a separate item kind: it lowers to a sentence
@@ -118,10 +241,17 @@ pub struct ConstStringDecl {
 #[derive(Debug, Clone)]
 pub struct SentenceDecl {
     pub name: String,
+    /// The parameters of a generic sentence, `sentence name<f, g>`, which is
+    /// compiled once for each distinct list of sentences it is given. Empty
+    /// for an ordinary sentence.
+    pub generics: Vec<String>,
     pub body: ParsedSentence,
     pub annotations: Vec<SourceAnnotation>,
     pub is_exported: bool,
     pub is_test: bool,
+    /// Where the name was written, for an error in code an instance of a
+    /// template generated from it. A generated sentence has none.
+    pub span: Option<Span>,
 }
 
 /// A claim that two programs are interchangeable.
@@ -151,6 +281,30 @@ pub struct IdentityDecl {
     pub span: Span,
 }
 
+/// `const name = { ... };`: a value worked out at compile time. Shared
+/// between sugar and core.
+///
+/// The body is a sentence of no inputs, run by the VM once phase 4 has
+/// compiled it and everything it calls, and what it leaves is the value the
+/// name denotes wherever a literal could go.
+#[derive(Debug, Clone)]
+pub struct ConstDecl {
+    pub name: String,
+    pub body: ParsedSentence,
+    /// Where the name was written, for an error in working the value out.
+    pub span: Span,
+}
+
+/// `static_assert { ... }`: a sentence of no inputs that has to leave `true`
+/// for the program to compile. Shared between sugar and core.
+#[derive(Debug, Clone)]
+pub struct StaticAssertDecl {
+    pub body: ParsedSentence,
+    /// Where the `static_assert` was written, which is what a failed one
+    /// points at.
+    pub span: Span,
+}
+
 /// A type specification. Sugar only — it never survives lowering.
 #[derive(Debug, Clone)]
 pub enum TypeSpec {
@@ -168,6 +322,7 @@ pub enum PrimitiveType {
     ConstString,
     Symbol,
     Tuple,
+    Map,
 }
 
 impl std::fmt::Display for ParsedValue {
@@ -175,8 +330,8 @@ impl std::fmt::Display for ParsedValue {
         match self {
             ParsedValue::Bool(b) => write!(f, "{}", b),
             ParsedValue::Int(i) => write!(f, "{}", i),
-            // Quoted, because composer templates are rendered as text and
-            // re-parsed: what this prints has to lex back to the same literal.
+            // Quoted, so an error that prints one shows the literal as it
+            // would be written.
             ParsedValue::ConstString(s) => write!(f, "{:?}", s),
             ParsedValue::Tuple(elements) => {
                 write!(f, "(")?;
@@ -199,21 +354,28 @@ impl std::fmt::Display for ParsedValue {
 /// The surface language, as parsed. No desugaring has happened yet.
 pub mod sugar {
     use super::{
-        ConstStringDecl, IdentityDecl, ParsedValue, SentenceDecl, SourceAnnotation, SymbolDecl,
-        TypeSpec,
+        ConstDecl, ConstStringDecl, DataDecl, IdentityDecl, ParsedValue, SentenceDecl,
+        SourceAnnotation, StaticAssertDecl, SymbolDecl, TypeSpec,
     };
     use crate::resolve::Path;
+    use crate::source::Span;
 
     #[derive(Debug, Clone)]
     pub enum Item {
         Symbol(SymbolDecl),
         ConstString(ConstStringDecl),
+        Data(DataDecl),
         Sentence(SentenceDecl),
         Identity(IdentityDecl),
+        Const(ConstDecl),
+        StaticAssert(StaticAssertDecl),
         Mod(ModDecl),
         Type(TypeDecl),
         Enum(EnumDecl),
+        Record(RecordDecl),
         Compose(ComposeDecl),
+        Template(TemplateDecl),
+        Instance(InstanceDecl),
     }
 
     #[derive(Debug, Clone)]
@@ -244,12 +406,57 @@ pub mod sugar {
         pub elements: Vec<TypeSpec>,
     }
 
+    /// `record Name { field: spec, ... }`: a tuple whose positions have names.
+    #[derive(Debug, Clone)]
+    pub struct RecordDecl {
+        pub name: String,
+        /// In declaration order, which is tuple order: the first field is the
+        /// first element.
+        pub fields: Vec<RecordField>,
+        pub annotations: Vec<SourceAnnotation>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct RecordField {
+        pub name: String,
+        pub spec: TypeSpec,
+    }
+
     #[derive(Debug, Clone)]
     pub struct ComposeDecl {
         pub name: String,
         pub composer: Composer,
         pub args: Vec<ModuleExpr>,
         pub is_test: bool,
+        /// Where the composer's name was written, which is where an error in
+        /// the code it generates is reported.
+        pub span: Span,
+    }
+
+    /// `template mod name(params) { … }`: a module body with holes in it.
+    ///
+    /// It declares nothing where it is written. Each instantiation is lowered
+    /// as a copy of `items` with every parameter replaced by its argument.
+    #[derive(Debug, Clone)]
+    pub struct TemplateDecl {
+        pub name: String,
+        pub params: Vec<String>,
+        pub items: Vec<Item>,
+        /// Where the name was written, for errors in code an instance of it
+        /// generated.
+        pub span: Span,
+    }
+
+    /// `mod name template(args);` for a template declared in `.hana` rather
+    /// than built in.
+    #[derive(Debug, Clone)]
+    pub struct InstanceDecl {
+        pub name: String,
+        pub template: Path,
+        pub args: Vec<ModuleExpr>,
+        pub is_test: bool,
+        /// Where the template's path was written.
+        pub span: Span,
     }
 
     /// An argument to a composer: a module path, a nested composition, or a
@@ -260,6 +467,13 @@ pub mod sugar {
         Composed {
             composer: Composer,
             args: Vec<ModuleExpr>,
+            span: Span,
+        },
+        /// A nested instantiation of a declared template.
+        Instance {
+            template: Path,
+            args: Vec<ModuleExpr>,
+            span: Span,
         },
         Value(ParsedValue),
     }
@@ -279,20 +493,23 @@ pub mod sugar {
     }
 
     impl Composer {
+        /// Every composer, in declaration order, so `composer as usize`
+        /// indexes this.
+        pub const ALL: [Composer; 10] = [
+            Composer::Concurrent,
+            Composer::Hidden,
+            Composer::Prefix,
+            Composer::RenamePrefix,
+            Composer::StaticClosure,
+            Composer::Done,
+            Composer::Emit,
+            Composer::EmitStatic,
+            Composer::Accept,
+            Composer::AcceptStatic,
+        ];
+
         pub fn from_name(name: &str) -> Option<Self> {
-            match name {
-                "compose_concurrent" => Some(Composer::Concurrent),
-                "compose_hidden" => Some(Composer::Hidden),
-                "compose_prefix" => Some(Composer::Prefix),
-                "compose_rename_prefix" => Some(Composer::RenamePrefix),
-                "compose_static_closure" => Some(Composer::StaticClosure),
-                "compose_done" => Some(Composer::Done),
-                "compose_emit" => Some(Composer::Emit),
-                "compose_emit_static" => Some(Composer::EmitStatic),
-                "compose_accept" => Some(Composer::Accept),
-                "compose_accept_static" => Some(Composer::AcceptStatic),
-                _ => None,
-            }
+            Composer::ALL.into_iter().find(|c| c.name() == name)
         }
 
         pub fn name(&self) -> &'static str {
@@ -315,14 +532,21 @@ pub mod sugar {
 /// The irreducible subset of the language. Everything here corresponds to
 /// something a user could have written by hand.
 pub mod core {
-    use super::{ConstStringDecl, IdentityDecl, SentenceDecl, SymbolDecl};
+    use super::{
+        ConstDecl, ConstStringDecl, DataDecl, IdentityDecl, SentenceDecl, StaticAssertDecl,
+        SymbolDecl,
+    };
+    use crate::source::Span;
 
     #[derive(Debug, Clone)]
     pub enum Item {
         Symbol(SymbolDecl),
         ConstString(ConstStringDecl),
+        Data(DataDecl),
         Sentence(SentenceDecl),
         Identity(IdentityDecl),
+        Const(ConstDecl),
+        StaticAssert(StaticAssertDecl),
         Mod(ModDecl),
     }
 
@@ -331,8 +555,41 @@ pub mod core {
         pub name: String,
         pub items: Vec<Item>,
         pub is_test: bool,
-        /// Set for modules generated by a composer, whose bodies carry no
-        /// `export` markers of their own.
+        /// Set for test machines generated from a template, whose bodies need
+        /// carry no `export` markers of their own.
         pub exports_machine_sentences: bool,
+        /// Set for modules lowered from an `enum`: its variants in declaration
+        /// order, which is what a `match` on it is checked against.
+        pub variants: Option<Vec<Variant>>,
+        /// Set for modules generated from a template — a built-in composer or
+        /// a declared one — so an error in the code it generated can point at
+        /// the instantiation.
+        pub instance: Option<Instance>,
+    }
+
+    /// Where a module generated from a template came from.
+    #[derive(Debug, Clone)]
+    pub struct Instance {
+        /// The template's name, as seen from the instantiation: a built-in
+        /// composer's, or a declared template's path.
+        pub template: String,
+        /// Where the template was declared. A built-in composer has no
+        /// declaration the user can see.
+        pub declared: Option<Span>,
+        /// Where it was instantiated.
+        pub site: Span,
+    }
+
+    /// One variant of the `enum` a module was lowered from.
+    #[derive(Debug, Clone)]
+    pub struct Variant {
+        pub name: String,
+        /// How many elements the payload tuple holds, which is what a `match`
+        /// arm for this variant untuples.
+        pub width: usize,
+        /// The variant's `get`, which answers with the result tags. Phase 3
+        /// declares it in the variant's module once the tree shows the
+        /// program has them.
+        pub get: SentenceDecl,
     }
 }
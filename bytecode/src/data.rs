@@ -0,0 +1,77 @@
+//! Reading data files into values at compile time, for `const name from
+//! "path";`.
+//!
+//! A file ending in `.json` is read as JSON; any other file is read as lines.
+//! Either way the result is an ordinary [`Value`], built out of the values the
+//! language already has, so nothing downstream knows it came from a file.
+
+use crate::json::{self, Json};
+use crate::source::{Error, FileId, Span};
+use crate::value::Value;
+
+/// Reads `text`, the contents of `file`, the way its name says to. Errors
+/// point into the file itself.
+pub(crate) fn read(name: &str, text: &str, file: FileId) -> Result<Value, Error> {
+    if name.ends_with(".json") {
+        json(text, file)
+    } else {
+        Ok(lines(text))
+    }
+}
+
+/// Drops the line ending a file's last line almost always carries, so a file
+/// holding `39472` reads as `"39472"` and not as six characters.
+pub(crate) fn trim_last_line_ending(text: &str) -> &str {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    text.strip_suffix('\r').unwrap_or(text)
+}
+
+/// A tuple of const strings, one per line, without their line endings. The
+/// ending on the last line does not start another, empty one.
+fn lines(text: &str) -> Value {
+    let text = trim_last_line_ending(text);
+    if text.is_empty() {
+        return Value::Tuple(vec![]);
+    }
+    Value::Tuple(
+        text.split('\n')
+            .map(|line| Value::ConstString(line.strip_suffix('\r').unwrap_or(line).to_string()))
+            .collect(),
+    )
+}
+
+/// One JSON document.
+///
+/// - an integer is an `Int`, and has to fit in one;
+/// - a string is a `ConstString`, and `true` and `false` are `Bool`s;
+/// - an array is a tuple of its elements;
+/// - an object is a tuple of `(key, value)` pairs, in the order the file
+///   writes them.
+///
+/// A number with a fraction or an exponent, and `null`, are refused: there is
+/// no value for either to become.
+fn json(text: &str, file: FileId) -> Result<Value, Error> {
+    let document = json::parse(text).map_err(|e| {
+        let error = Error::at(e.message, Span::new(file, e.start, e.end));
+        match e.help {
+            Some(help) => error.with_help(help),
+            None => error,
+        }
+    })?;
+    Ok(value(document))
+}
+
+fn value(json: Json) -> Value {
+    match json {
+        Json::Bool(b) => Value::Bool(b),
+        Json::Int(n) => Value::Int(n),
+        Json::Str(s) => Value::ConstString(s),
+        Json::Array(elements) => Value::Tuple(elements.into_iter().map(value).collect()),
+        Json::Object(entries) => Value::Tuple(
+            entries
+                .into_iter()
+                .map(|(key, v)| Value::Tuple(vec![Value::ConstString(key), value(v)]))
+                .collect(),
+        ),
+    }
+}
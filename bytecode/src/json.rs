@@ -0,0 +1,276 @@
+//! The JSON that data files and recordings are written in.
+//!
+//! Only what some [`Value`](crate::Value) could become is read: there are no
+//! floats and no `null`, and an integer has to fit in an `Int`. What the
+//! document means is up to whoever reads it, so this answers the document as
+//! written, and a `const` read from a file and the runtime's recordings each
+//! make their own values out of it.
+
+use std::fmt;
+
+/// A JSON document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Array(Vec<Json>),
+    /// Its entries in the order the document writes them.
+    Object(Vec<(String, Json)>),
+}
+
+/// What is wrong with a document, and where: `start` and `end` are byte
+/// offsets into the text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonError {
+    pub message: String,
+    pub help: Option<String>,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl JsonError {
+    fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(help) = &self.help {
+            write!(f, " ({})", help)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads `text`, which has to hold one JSON value and nothing else.
+pub fn parse(text: &str) -> Result<Json, JsonError> {
+    let mut reader = Reader { text, pos: 0 };
+    reader.whitespace();
+    let value = reader.value()?;
+    reader.whitespace();
+    if reader.pos < text.len() {
+        return Err(reader.error("expected end of file after the JSON value"));
+    }
+    Ok(value)
+}
+
+struct Reader<'t> {
+    text: &'t str,
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, message: impl Into<String>) -> JsonError {
+        let end = self.peek().map_or(self.pos, |c| self.pos + c.len_utf8());
+        self.error_from(self.pos, end, message)
+    }
+
+    fn error_from(&self, start: usize, end: usize, message: impl Into<String>) -> JsonError {
+        JsonError {
+            message: message.into(),
+            help: None,
+            start,
+            end,
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), JsonError> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", c)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        match self.peek() {
+            Some('[') => {
+                self.pos += 1;
+                Ok(Json::Array(self.sequence(']', |reader| reader.value())?))
+            }
+            Some('{') => {
+                self.pos += 1;
+                let entries = self.sequence('}', |reader| {
+                    if reader.peek() != Some('"') {
+                        return Err(reader.error("expected a string, the key of an object entry"));
+                    }
+                    let key = reader.string()?;
+                    reader.whitespace();
+                    reader.expect(':')?;
+                    reader.whitespace();
+                    Ok((key, reader.value()?))
+                })?;
+                Ok(Json::Object(entries))
+            }
+            Some('"') => Ok(Json::Str(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+                    self.pos += 1;
+                }
+                match &self.text[start..self.pos] {
+                    "true" => Ok(Json::Bool(true)),
+                    "false" => Ok(Json::Bool(false)),
+                    "null" => Err(self
+                        .error_from(start, self.pos, "`null` has no value to become")
+                        .with_help("leave the entry out")),
+                    word => Err(self.error_from(
+                        start,
+                        self.pos,
+                        format!("expected a JSON value, found `{}`", word),
+                    )),
+                }
+            }
+            Some(_) => Err(self.error("expected a JSON value")),
+            None => Err(self.error("expected a JSON value, found end of file")),
+        }
+    }
+
+    /// The elements of an array or the entries of an object, up to and
+    /// including `close`.
+    fn sequence<T>(
+        &mut self,
+        close: char,
+        mut element: impl FnMut(&mut Self) -> Result<T, JsonError>,
+    ) -> Result<Vec<T>, JsonError> {
+        let mut elements = Vec::new();
+        self.whitespace();
+        if self.peek() == Some(close) {
+            self.pos += 1;
+            return Ok(elements);
+        }
+        loop {
+            self.whitespace();
+            elements.push(element(self)?);
+            self.whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(c) if c == close => {
+                    self.pos += 1;
+                    return Ok(elements);
+                }
+                _ => return Err(self.error(format!("expected ',' or '{}'", close))),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            return Err(self.error("expected a digit"));
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if let Some('.' | 'e' | 'E') = self.peek() {
+            while self
+                .peek()
+                .is_some_and(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'))
+            {
+                self.pos += 1;
+            }
+            return Err(self
+                .error_from(start, self.pos, "a number with a fraction or an exponent")
+                .with_help("there are no floats: only integers can be read"));
+        }
+        let digits = &self.text[start..self.pos];
+        digits.parse::<i64>().map(Json::Int).map_err(|_| {
+            self.error_from(
+                start,
+                self.pos,
+                format!("`{}` does not fit in an int", digits),
+            )
+        })
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        let start = self.pos;
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(out),
+                Some('\\') => {
+                    let escape = self.pos - 1;
+                    match self.bump() {
+                        Some('"') => out.push('"'),
+                        Some('\\') => out.push('\\'),
+                        Some('/') => out.push('/'),
+                        Some('b') => out.push('\u{8}'),
+                        Some('f') => out.push('\u{c}'),
+                        Some('n') => out.push('\n'),
+                        Some('r') => out.push('\r'),
+                        Some('t') => out.push('\t'),
+                        Some('u') => out.push(self.unicode_escape(escape)?),
+                        _ => return Err(self.error_from(escape, self.pos, "unknown escape")),
+                    }
+                }
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error_from(
+                        self.pos - 1,
+                        self.pos,
+                        "a control character has to be escaped inside a string",
+                    ));
+                }
+                Some(c) => out.push(c),
+                None => return Err(self.error_from(start, self.pos, "unterminated string")),
+            }
+        }
+    }
+
+    /// The rest of a `\uXXXX` escape starting at `escape`, and of the low
+    /// half that has to follow a high surrogate.
+    fn unicode_escape(&mut self, escape: usize) -> Result<char, JsonError> {
+        let high = self.hex4(escape)?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if !self.text[self.pos..].starts_with("\\u") {
+                return Err(self.error_from(escape, self.pos, "unpaired surrogate"));
+            }
+            self.pos += 2;
+            let low = self.hex4(escape)?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error_from(escape, self.pos, "unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error_from(escape, self.pos, "unpaired surrogate"))
+    }
+
+    fn hex4(&mut self, escape: usize) -> Result<u32, JsonError> {
+        let digits = self.text.get(self.pos..self.pos + 4);
+        match digits.and_then(|d| u32::from_str_radix(d, 16).ok()) {
+            Some(code) if digits.is_some_and(|d| d.chars().all(|c| c.is_ascii_hexdigit())) => {
+                self.pos += 4;
+                Ok(code)
+            }
+            _ => Err(self.error_from(escape, self.pos, "expected four hex digits after \\u")),
+        }
+    }
+}
@@ -1,6 +1,8 @@
 pub mod arity;
 pub mod assembly;
 pub mod ast;
+mod data;
+pub mod json;
 pub mod library;
 pub mod lower;
 pub mod opcode;
@@ -9,7 +11,9 @@ pub mod source;
 pub mod value;
 
 pub use arity::check_arities;
-pub use assembly::{assemble, assemble_source, assemble_with_path};
+pub use assembly::{
+    Evaluator, assemble, assemble_source, assemble_source_evaluating, assemble_with_path,
+};
 pub use library::{Annotation, Arity, Identity, IdentityIndex, Library, Sentence, SentenceIndex};
 pub use opcode::Instruction;
 pub use source::{Error, FileId, SourceMap, Span};
@@ -276,6 +280,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn a_map_get_carries_the_tags_it_answers_with() {
+        let res = assemble(&with_prelude("sentence s { map_empty push 1 map_get }")).unwrap();
+        assert_eq!(
+            res.sentences[SentenceIndex::from(0)].last(),
+            Some(&Instruction::MapGet(
+                res.symbols["prelude::ok"].clone(),
+                res.symbols["prelude::err"].clone()
+            ))
+        );
+        let err = assemble("sentence s { map_empty push 1 map_get }").unwrap_err();
+        assert!(
+            err.contains("`map_get` reads the tag `crate::prelude::ok`"),
+            "{}",
+            err
+        );
+    }
+
     #[test]
     fn an_early_return_cannot_invent_what_the_rest_of_the_block_would_push() {
         let err = assemble(&with_prelude("sentence s { ? push 1 push 2 }")).unwrap_err();
@@ -283,6 +305,659 @@ mod tests {
         assert!(err.contains("'s'"), "should name the sentence: {}", err);
     }
 
+    #[test]
+    fn a_return_at_the_top_of_a_sentence_is_a_question_mark() {
+        let with = |op: &str| {
+            let res = assemble(&with_prelude(&format!(
+                "#[arity(1, 1)] sentence s {{ {} push 1 add }}",
+                op
+            )))
+            .unwrap();
+            res.sentences
+        };
+        assert_eq!(with("?"), with("?!"));
+    }
+
+    #[test]
+    fn an_arm_with_a_return_in_it_reports_how_it_was_left() {
+        let res = assemble(&with_prelude(
+            "#[arity(2, 1)] sentence s { branch { ?! } { } push 1 add }",
+        ))
+        .unwrap();
+        let [
+            Instruction::Branch(arm, other),
+            Instruction::Branch(rest, fail),
+        ] = res.sentences[sentence_named(&res, "s")][..]
+        else {
+            panic!(
+                "expected the branch and then one on its marker, got {:?}",
+                res.sentences[sentence_named(&res, "s")]
+            );
+        };
+        // The arm without the `?!` finished, and says so.
+        assert_eq!(
+            res.sentences[other],
+            vec![Instruction::Push(Value::Bool(true))]
+        );
+        // The arm with it says so on the way out of its rest arm, and says it
+        // is carrying the error on the way out of its failure arm.
+        let [.., Instruction::Branch(arm_rest, arm_fail)] = res.sentences[arm][..] else {
+            panic!("expected a `?` shape, got {:?}", res.sentences[arm]);
+        };
+        assert_eq!(
+            res.sentences[arm_rest],
+            vec![Instruction::Push(Value::Bool(true))]
+        );
+        assert_eq!(
+            res.sentences[arm_fail].last(),
+            Some(&Instruction::Push(Value::Bool(false)))
+        );
+        // The level around it carries the error out. The rest of the
+        // sentence takes as many values as it leaves, so there is nothing for
+        // it to drop.
+        assert_eq!(
+            res.sentences[rest],
+            vec![Instruction::Push(Value::Int(1)), Instruction::Add]
+        );
+        assert_eq!(res.sentences[fail], vec![]);
+    }
+
+    #[test]
+    fn a_return_cannot_be_carried_out_of_a_repeat_body() {
+        let err = assemble(&with_prelude(
+            "#[arity(1, 1)] sentence s { repeat 2 { ?! } }",
+        ))
+        .unwrap_err();
+        assert!(err.contains("`repeat` body"), "{}", err);
+        assert!(err.contains("'s'"), "should name the sentence: {}", err);
+    }
+
+    #[test]
+    fn a_return_cannot_invent_what_the_rest_of_the_sentence_would_push() {
+        let err = assemble(&with_prelude(
+            "sentence s { branch { ?! } { } push 1 push 2 }",
+        ))
+        .unwrap_err();
+        assert!(err.contains("after a `?!`"), "{}", err);
+        assert!(err.contains("leaves 2 more values"), "{}", err);
+    }
+
+    // -----------------------------------------------------------------------
+    // `match`
+    // -----------------------------------------------------------------------
+
+    const SHAPE: &str = "enum Shape { Circle(int), Rect(int, int), Empty() }\n";
+
+    #[test]
+    fn a_match_is_a_chain_of_tag_comparisons() {
+        let res = assemble(&format!(
+            "{}#[arity(1, 1)] sentence s {{ match Shape {{ Circle {{ }} Rect {{ add }} Empty {{ push 0 }} }} }}",
+            SHAPE
+        ))
+        .unwrap();
+        let s = sentence_named(&res, "s");
+        let [
+            Instruction::Untuple(2),
+            Instruction::Copy,
+            Instruction::Push(ref circle),
+            Instruction::Equal,
+            Instruction::Branch(is_circle, not_circle),
+        ] = res.sentences[s][..]
+        else {
+            panic!("expected the first test, got {:?}", res.sentences[s]);
+        };
+        assert_eq!(*circle, res.symbols["Shape::Circle::tag"]);
+        assert_eq!(
+            res.sentences[is_circle],
+            vec![Instruction::Drop, Instruction::Untuple(1)]
+        );
+
+        // The next test is in the else arm, and the last arm is what is left
+        // once it says no: nothing asks whether the tag was `Empty`'s.
+        let [.., Instruction::Branch(is_rect, last)] = res.sentences[not_circle][..] else {
+            panic!(
+                "expected the second test, got {:?}",
+                res.sentences[not_circle]
+            );
+        };
+        assert_eq!(
+            res.sentences[is_rect],
+            vec![Instruction::Drop, Instruction::Untuple(2), Instruction::Add]
+        );
+        assert_eq!(
+            res.sentences[last],
+            vec![
+                Instruction::Drop,
+                Instruction::Untuple(0),
+                Instruction::Push(Value::Int(0))
+            ]
+        );
+    }
+
+    #[test]
+    fn a_match_must_cover_every_variant() {
+        let err = assemble(&format!(
+            "{}#[arity(1, 1)] sentence s {{ match Shape {{ Circle {{ }} }} }}",
+            SHAPE
+        ))
+        .unwrap_err();
+        assert!(err.contains("does not cover `Rect`, `Empty`"), "{}", err);
+        assert!(err.contains("'s'"), "should name the sentence: {}", err);
+    }
+
+    #[test]
+    fn a_wildcard_covers_what_the_arms_do_not() {
+        assemble(&format!(
+            "{}#[arity(1, 1)] sentence s {{ match Shape {{ Circle {{ }} _ {{ drop 0 push 1 }} }} }}",
+            SHAPE
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn a_wildcard_that_can_never_run_is_refused() {
+        let err =
+            assemble("enum E { A() } #[arity(1, 1)] sentence s { match E { A { push 1 } _ { } } }")
+                .unwrap_err();
+        assert!(err.contains("can never run"), "{}", err);
+    }
+
+    #[test]
+    fn a_match_arm_must_name_a_variant() {
+        let err = assemble(&format!(
+            "{}#[arity(1, 1)] sentence s {{ match Shape {{ Square {{ }} _ {{ }} }} }}",
+            SHAPE
+        ))
+        .unwrap_err();
+        assert!(err.contains("has no variant `Square`"), "{}", err);
+        assert!(err.contains("Circle, Rect, Empty"), "{}", err);
+    }
+
+    #[test]
+    fn a_match_needs_an_enum() {
+        let err = assemble("type T int; sentence s { match T { _ { } } }").unwrap_err();
+        assert!(err.contains("not an enum"), "{}", err);
+    }
+
+    #[test]
+    fn a_junk_arm_asks_the_enums_check_first() {
+        let res = assemble(
+            "enum E { A(int) } #[arity(1, 1)] sentence s { match E { A { } junk { drop 0 push 0 } } }",
+        )
+        .unwrap();
+        let [
+            Instruction::Copy,
+            Instruction::Jump(check),
+            Instruction::Branch(_, junk),
+        ] = res.sentences[sentence_named(&res, "s")][..]
+        else {
+            panic!("expected the check in front of the chain");
+        };
+        assert_eq!(check, sentence_named(&res, "E::check"));
+        assert_eq!(
+            res.sentences[junk],
+            vec![Instruction::Drop, Instruction::Push(Value::Int(0))]
+        );
+    }
+
+    // -----------------------------------------------------------------------
+    // `repeat`
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn a_repeat_jumps_to_one_body_n_times() {
+        let res = assemble("#[arity(1, 1)] sentence s { repeat 3 { push 1 add } }").unwrap();
+        let s = sentence_named(&res, "s");
+        let [
+            Instruction::Jump(a),
+            Instruction::Jump(b),
+            Instruction::Jump(c),
+        ] = res.sentences[s][..]
+        else {
+            panic!("expected three jumps, got {:?}", res.sentences[s]);
+        };
+        assert!(a == b && b == c, "the body should be flattened once");
+        assert_eq!(
+            res.sentences[a],
+            vec![Instruction::Push(Value::Int(1)), Instruction::Add]
+        );
+    }
+
+    #[test]
+    fn a_repeat_with_index_pushes_each_index() {
+        let res = assemble(
+            "sentence step { add } #[arity(1, 1)] sentence s { repeat 2 with index step }",
+        )
+        .unwrap();
+        let step = sentence_named(&res, "step");
+        assert_eq!(
+            res.sentences[sentence_named(&res, "s")],
+            vec![
+                Instruction::Push(Value::Int(0)),
+                Instruction::Jump(step),
+                Instruction::Push(Value::Int(1)),
+                Instruction::Jump(step),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_repeat_while_ok_nests_the_rest_of_the_loop_under_a_question_mark() {
+        let res = assemble(&with_prelude(
+            "#[arity(1, 1)] sentence step { push crate::prelude::ok tuple 2 }\n\
+             #[arity(1, 1)] sentence s { repeat 3 while ok step }",
+        ))
+        .unwrap();
+        let step = sentence_named(&res, "step");
+        let [Instruction::Jump(first), .., Instruction::Branch(rest, _)] =
+            res.sentences[sentence_named(&res, "s")][..]
+        else {
+            panic!("expected an iteration and then a `?`");
+        };
+        assert_eq!(first, step);
+        let [Instruction::Jump(second), .., Instruction::Branch(last, _)] = res.sentences[rest][..]
+        else {
+            panic!("expected the second iteration and then a `?`");
+        };
+        assert_eq!(second, step);
+        // The last iteration answers for the whole loop, so nothing asks
+        // about its result.
+        assert_eq!(res.sentences[last], vec![Instruction::Jump(step)]);
+    }
+
+    #[test]
+    fn a_repeat_of_nothing_while_ok_answers_ok() {
+        let res = assemble(&with_prelude(
+            "#[arity(1, 1)] sentence s { repeat 0 while ok { } }",
+        ))
+        .unwrap();
+        assert_eq!(
+            res.sentences[sentence_named(&res, "s")],
+            vec![
+                Instruction::Push(res.symbols["prelude::ok"].clone()),
+                Instruction::Tuple(2)
+            ]
+        );
+    }
+
+    // -----------------------------------------------------------------------
+    // Generated constructors and accessors
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn a_variants_new_builds_the_tagged_pair() {
+        let res = assemble(SHAPE).unwrap();
+        assert_eq!(
+            res.sentences[sentence_named(&res, "Shape::Rect::new")],
+            vec![
+                Instruction::Tuple(2),
+                Instruction::Push(res.symbols["Shape::Rect::tag"].clone()),
+                Instruction::Tuple(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_variants_get_needs_the_result_tags() {
+        let res = assemble(SHAPE).unwrap();
+        assert!(res.names.iter().any(|n| n == "Shape::Circle::is"));
+        assert!(!res.names.iter().any(|n| n == "Shape::Circle::get"));
+
+        let res = assemble(&with_prelude(SHAPE)).unwrap();
+        let [
+            Instruction::Copy,
+            Instruction::Jump(check),
+            Instruction::Branch(_, _),
+        ] = res.sentences[sentence_named(&res, "Shape::Circle::get")][..]
+        else {
+            panic!("expected the variant's check in front of a branch");
+        };
+        assert_eq!(check, sentence_named(&res, "Shape::Circle::check"));
+    }
+
+    #[test]
+    fn a_variants_get_finds_the_result_tags_however_they_are_declared() {
+        // From a template, which no syntactic look at `mod prelude` would see.
+        let res = assemble(&format!(
+            "template mod tags() {{ symbol ok symbol err }}\nmod prelude tags();\n{}",
+            SHAPE
+        ))
+        .unwrap();
+        assert!(res.names.iter().any(|n| n == "Shape::Circle::get"));
+
+        // From inside an instance, as a variant of an enum it declares.
+        let res = assemble(&with_prelude(&format!(
+            "template mod shapes() {{ {} }}\nmod s shapes();\n\
+             sentence unwrap {{ jump s::Shape::Circle::get }}",
+            SHAPE
+        )))
+        .unwrap();
+        assert!(res.names.iter().any(|n| n == "s::Shape::Circle::get"));
+    }
+
+    #[test]
+    fn asking_for_get_without_the_result_tags_says_why_it_is_missing() {
+        let err = assemble(&format!(
+            "{}sentence s {{ jump Shape::Circle::get }}",
+            SHAPE
+        ))
+        .unwrap_err();
+        assert!(err.contains("In 's'"), "{}", err);
+        assert!(err.contains("`Shape::Circle::get`"), "{}", err);
+        assert!(
+            err.contains("mod prelude { symbol ok symbol err }"),
+            "{}",
+            err
+        );
+
+        // The tag the program does lack is the one it hears about.
+        let err = assemble(&format!(
+            "mod prelude {{ symbol ok }}\n{}sentence s {{ jump Shape::Circle::get }}",
+            SHAPE
+        ))
+        .unwrap_err();
+        assert!(err.contains("`crate::prelude::err`"), "{}", err);
+
+        // Anything else that is missing is missing as before.
+        let err = assemble(&format!(
+            "{}sentence s {{ jump Shape::Circle::got }}",
+            SHAPE
+        ))
+        .unwrap_err();
+        assert!(err.contains("Unresolved label target"), "{}", err);
+    }
+
+    #[test]
+    fn a_tuple_type_gets_positional_accessors() {
+        let res = assemble("type Triple (int, bool, symbol);").unwrap();
+        assert_eq!(
+            res.sentences[sentence_named(&res, "Triple::new")],
+            vec![Instruction::Tuple(3)]
+        );
+        assert_eq!(
+            res.sentences[sentence_named(&res, "Triple::get_1")],
+            vec![Instruction::AsTuple(3), Instruction::TupleGet(1)]
+        );
+        assert!(!res.names.iter().any(|n| n == "Triple::get_3"));
+
+        let res = assemble("type MyInt int;").unwrap();
+        assert!(!res.names.iter().any(|n| n == "MyInt::new"));
+    }
+
+    // -----------------------------------------------------------------------
+    // `record`
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn a_record_names_the_positions_of_a_tuple() {
+        let res = assemble("record P { x: int, y: bool, z: symbol }").unwrap();
+        assert_eq!(
+            res.sentences[sentence_named(&res, "P::new")],
+            vec![Instruction::Tuple(3)]
+        );
+        assert_eq!(
+            res.sentences[sentence_named(&res, "P::get_x")],
+            vec![Instruction::AsTuple(3), Instruction::TupleGet(0)]
+        );
+
+        // The record is coerced under the new contents, then written in place.
+        let [Instruction::Dip(coerce), Instruction::TupleSet(2)] =
+            res.sentences[sentence_named(&res, "P::set_z")][..]
+        else {
+            panic!(
+                "unexpected set_z: {:?}",
+                res.sentences[sentence_named(&res, "P::set_z")]
+            );
+        };
+        assert_eq!(res.sentences[coerce], vec![Instruction::AsTuple(3)]);
+    }
+
+    #[test]
+    fn a_spec_names_a_record_by_its_path() {
+        let res = assemble(
+            "record Point { x: int, y: int }\n\
+             type MaybePoint Point | bool;\n\
+             enum Shape { At(Point) }",
+        )
+        .unwrap();
+        let point = sentence_named(&res, "Point::check");
+        let calls = |name: &str| {
+            let s = sentence_named(&res, name);
+            let mut seen = vec![s];
+            let mut i = 0;
+            while i < seen.len() {
+                for inst in &res.sentences[seen[i]] {
+                    match *inst {
+                        Instruction::Jump(t) | Instruction::Dip(t) => seen.push(t),
+                        Instruction::Branch(a, b) => seen.extend([a, b]),
+                        _ => {}
+                    }
+                }
+                i += 1;
+            }
+            seen.contains(&point)
+        };
+        assert!(calls("MaybePoint::check"));
+        assert!(calls("Shape::At::check"));
+    }
+
+    const TWICE: &str = r#"
+        sentence inc {
+            push 1
+            add
+        }
+
+        template mod twice(step) {
+            sentence run {
+                jump step
+                jump step
+            }
+        }
+    "#;
+
+    #[test]
+    fn a_template_declares_nothing_until_it_is_instantiated() {
+        let res = assemble(TWICE).unwrap();
+        assert!(!res.names.iter().any(|n| n.starts_with("twice")));
+
+        let res = assemble(&format!("{}\nmod by_two twice(inc);", TWICE)).unwrap();
+        let inc = sentence_named(&res, "inc");
+        assert_eq!(
+            res.sentences[sentence_named(&res, "by_two::run")],
+            vec![Instruction::Jump(inc), Instruction::Jump(inc)]
+        );
+    }
+
+    #[test]
+    fn an_instance_passes_one_argument_per_parameter() {
+        let err = assemble(&format!("{}\nmod by_two twice(inc, inc);", TWICE)).unwrap_err();
+        assert!(
+            err.contains("`twice` takes 1 argument(s) (step), but 2 were given"),
+            "{}",
+            err
+        );
+        assert!(err.contains("note: `twice` is declared here"), "{}", err);
+    }
+
+    #[test]
+    fn an_error_in_an_instance_points_at_the_site_and_the_template() {
+        let err = assemble(&format!("{}\nmod by_two twice(dec);", TWICE)).unwrap_err();
+        assert!(
+            err.contains("Item 'dec' not found in module 'crate'"),
+            "{}",
+            err
+        );
+        assert!(err.contains("--> <input>:14:12"), "{}", err);
+        assert!(
+            err.contains("note: `by_two` is an instance of template `twice`, which wrote this"),
+            "{}",
+            err
+        );
+        // `run`, the sentence in the template's body that named the parameter.
+        assert!(err.contains("--> <input>:8:22"), "{}", err);
+    }
+
+    #[test]
+    fn a_failing_const_in_an_instance_points_at_the_site_and_the_const() {
+        let err =
+            assemble("template mod t(step) {\n  const c = { jump step };\n}\nmod x t(nowhere);")
+                .unwrap_err();
+        assert!(err.contains("--> <input>:4:7"), "{}", err);
+        assert!(
+            err.contains("note: `x` is an instance of template `t`, which wrote this"),
+            "{}",
+            err
+        );
+        assert!(err.contains("--> <input>:2:9"), "{}", err);
+    }
+
+    #[test]
+    fn a_value_parameter_names_nothing() {
+        let err = assemble(&format!("{}\nmod by_two twice(3);", TWICE)).unwrap_err();
+        assert!(
+            err.contains(
+                "in this instance of `twice`: parameter `step` is bound to the value `3`, but \
+                 is used where a path is needed"
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn a_template_cannot_instantiate_itself() {
+        let err =
+            assemble("template mod forever() { mod again super::forever(); }\nmod x forever();")
+                .unwrap_err();
+        assert!(
+            err.contains("template `crate::forever` instantiates itself"),
+            "{}",
+            err
+        );
+    }
+
+    const APPLY: &str = r#"
+        sentence inc {
+            push 1
+            add
+        }
+
+        sentence discard {
+            drop 0
+        }
+
+        sentence twice<f> {
+            jump f
+            jump f
+        }
+    "#;
+
+    #[test]
+    fn a_generic_compiles_once_per_list_of_arguments() {
+        // Two spellings of one argument are one instance, and the generic
+        // itself is no sentence at all.
+        let code = format!(
+            "{}\nmod m {{ sentence a {{ jump super::twice<super::inc> }} }}\nsentence b {{ jump twice<crate::inc> }}",
+            APPLY
+        );
+        let res = assemble(&code).unwrap();
+        let inc = sentence_named(&res, "inc");
+        let instance = sentence_named(&res, "twice<inc>");
+        assert_eq!(
+            res.sentences[instance],
+            vec![Instruction::Jump(inc), Instruction::Jump(inc)]
+        );
+        assert_eq!(
+            res.sentences[sentence_named(&res, "m::a")],
+            vec![Instruction::Jump(instance)]
+        );
+        assert_eq!(
+            res.sentences[sentence_named(&res, "b")],
+            vec![Instruction::Jump(instance)]
+        );
+        assert_eq!(
+            res.names.iter().filter(|n| n.starts_with("twice")).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn an_instance_is_named_after_its_arguments_all_the_way_down() {
+        let code = format!("{}\nsentence c {{ jump twice<twice<inc>> }}", APPLY);
+        let res = assemble(&code).unwrap();
+        let inner = sentence_named(&res, "twice<inc>");
+        assert_eq!(
+            res.sentences[sentence_named(&res, "twice<twice<inc>>")],
+            vec![Instruction::Jump(inner), Instruction::Jump(inner)]
+        );
+    }
+
+    #[test]
+    fn a_generic_is_not_compiled_until_it_is_instantiated() {
+        assert!(assemble("sentence broken<f> { jump nowhere }").is_ok());
+        let err = assemble("sentence broken<f> { jump nowhere }\nsentence a { jump broken<a> }")
+            .unwrap_err();
+        assert!(err.contains("In 'broken<a>'"), "{}", err);
+    }
+
+    #[test]
+    fn each_instance_answers_for_its_own_arity() {
+        let code = format!(
+            "{}\n#[arity(1, 1)]\nsentence once<f> {{ jump f }}\nsentence a {{ jump once<inc> }}",
+            APPLY
+        );
+        assert!(assemble(&code).is_ok());
+
+        let code = format!("{}\nsentence b {{ jump once<discard> }}", code);
+        let err = assemble(&code).unwrap_err();
+        assert!(err.contains("Sentence 'once<discard>'"), "{}", err);
+    }
+
+    #[test]
+    fn a_generic_is_only_named_with_its_arguments() {
+        let err = assemble(&format!("{}\nsentence a {{ jump twice }}", APPLY)).unwrap_err();
+        assert!(err.contains("names a generic sentence"), "{}", err);
+
+        let err = assemble(&format!("{}\nsentence a {{ jump inc<inc> }}", APPLY)).unwrap_err();
+        assert!(err.contains("takes no `<...>` arguments"), "{}", err);
+
+        let err =
+            assemble(&format!("{}\nsentence a {{ jump twice<inc, inc> }}", APPLY)).unwrap_err();
+        assert!(
+            err.contains("`twice` takes 1 sentence argument(s) (f), but 2 were given"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn a_generic_cannot_instantiate_itself() {
+        // Even with other arguments, which would ask for instances without end.
+        let err = assemble(&format!(
+            "{}\nsentence grow<f> {{ jump grow<twice<f>> }}\nsentence a {{ jump grow<inc> }}",
+            APPLY
+        ))
+        .unwrap_err();
+        assert!(
+            err.contains("generic sentence `grow` instantiates itself"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn a_generic_parameter_hides_a_template_parameter() {
+        let code = format!(
+            "{}\ntemplate mod t(f) {{ sentence own<f> {{ jump f }} sentence run {{ jump own<super::discard> }} }}\nmod x t(inc);",
+            APPLY
+        );
+        let res = assemble(&code).unwrap();
+        assert_eq!(
+            res.sentences[sentence_named(&res, "x::own<discard>")],
+            vec![Instruction::Jump(sentence_named(&res, "discard"))]
+        );
+    }
+
     #[test]
     fn test_drop_zero_does_not_expand() {
         let code = r#"
@@ -590,33 +1265,116 @@ mod tests {
         assert!(err.contains(r#"const_string greeting "hello""#), "{}", err);
     }
 
+    const MACHINE: &str = r#"
+        mod m {
+            export function init { drop 0 push "unused" }
+            export function accept { drop 0 push false }
+            export function tau_reduce { push false tuple 2 }
+            export function emit { drop 0 tuple 0 push false tuple 2 }
+            export function process { untuple 2 drop 0 }
+            export function is_done { drop 0 push false }
+            export function is_ready_to_finish { drop 0 push false }
+        }
+    "#;
+
     #[test]
     fn a_const_string_survives_a_composer_template() {
-        // Composer templates are rendered as text and re-parsed, so a value
-        // passed to one has to print as something that lexes back to it. A
-        // string literal is the only value where that takes quoting.
-        let code = r#"
-            mod m {
-                export function init { drop 0 push "unused" }
-                export function accept { drop 0 push false }
-                export function tau_reduce { push false tuple 2 }
-                export function emit { drop 0 tuple 0 push false tuple 2 }
-                export function process { untuple 2 drop 0 }
-                export function is_done { drop 0 push false }
-                export function is_ready_to_finish { drop 0 push false }
-            }
-            mod closed compose_static_closure(m, "xyz");
-        "#;
-        let res = assemble(code).unwrap();
-        let idx = res.names.iter().position(|n| n == "closed::init").unwrap();
+        // A value passed to a composer is substituted into the parsed
+        // template, so it arrives as the literal it was, quotes and all.
+        let code = format!(r#"{}mod closed compose_static_closure(m, "xyz");"#, MACHINE);
+        let res = assemble(&code).unwrap();
         assert!(
-            res.sentences[SentenceIndex::from(idx)]
+            res.sentences[sentence_named(&res, "closed::init")]
                 .contains(&Instruction::Push(Value::ConstString("xyz".to_string()))),
             "{:?}",
-            res.sentences[SentenceIndex::from(idx)]
+            res.sentences[sentence_named(&res, "closed::init")]
+        );
+    }
+
+    #[test]
+    fn a_path_in_a_composers_literal_is_read_from_the_composition() {
+        // The same rule a declared template's arguments follow: `s` is the
+        // symbol beside `closed`, not something inside it.
+        let code = format!(
+            "{}symbol s\nmod closed compose_static_closure(m, (s, 1));",
+            MACHINE
+        );
+        let res = assemble(&code).unwrap();
+        let pair = Value::Tuple(vec![res.symbols["s"].clone(), Value::Int(1)]);
+        assert!(
+            res.sentences[sentence_named(&res, "closed::init")].contains(&Instruction::Push(pair)),
+            "{:?}",
+            res.sentences[sentence_named(&res, "closed::init")]
+        );
+    }
+
+    #[test]
+    fn every_composer_assembles() {
+        let code = format!(
+            r#"{}
+            symbol s
+            function keep {{ drop 0 push true }}
+            mod concurrent compose_concurrent(m, m, keep);
+            mod hidden compose_hidden(concurrent, keep);
+            mod prefix compose_prefix(m, s);
+            mod rename_prefix compose_rename_prefix(s, s, m);
+            mod static_closure compose_static_closure(m, 1);
+            mod done compose_done();
+            mod emit compose_emit(m);
+            mod emit_static compose_emit_static(1, m);
+            mod accept compose_accept(keep, m);
+            mod accept_static compose_accept_static(1, m);
+            "#,
+            MACHINE
+        );
+        let res = assemble(&format!(
+            "mod prelude {{ symbol start symbol pass symbol fail }}\n{}",
+            code
+        ))
+        .unwrap();
+        for composer in ast::sugar::Composer::ALL {
+            let module = composer.name().trim_start_matches("compose_");
+            assert!(
+                res.names.iter().any(|n| *n == format!("{}::init", module)),
+                "{}",
+                module
+            );
+        }
+    }
+
+    #[test]
+    fn a_composer_argument_must_be_what_its_template_uses_it_as() {
+        let err = assemble("mod e compose_emit(3);").unwrap_err();
+        assert!(
+            err.contains(
+                "argument `machine` of `compose_emit` must be a machine module path, found the \
+                 value `3`"
+            ),
+            "{}",
+            err
+        );
+        assert!(err.contains("--> <input>:1:7"), "{}", err);
+    }
+
+    #[test]
+    fn an_error_in_a_composition_points_at_the_composer() {
+        let err = assemble("mod e compose_emit(nowhere);").unwrap_err();
+        assert!(err.contains("--> <input>:1:7"), "{}", err);
+        assert!(
+            err.contains("= help: `e` is generated by `compose_emit`"),
+            "{}",
+            err
         );
     }
 
+    #[test]
+    fn a_nested_composition_lands_in_a_module_no_path_can_name() {
+        let code = format!("{}mod e compose_emit(compose_emit(m));", MACHINE);
+        let res = assemble(&code).unwrap();
+        // Not an identifier, so nothing written in a source file names it.
+        assert!(res.names.iter().any(|n| n == "{anon#0}::init"));
+    }
+
     #[test]
     fn test_assemble_test_annotation() {
         let code = r#"
@@ -746,6 +1504,7 @@ mod tests {
             "sentence ping { jump pong } sentence pong { jump ping }",
             "sentence viabranch { pick 0 branch { drop 0 } { jump viabranch } }",
             "sentence entry { jump loops } sentence loops { jump loops }",
+            "sentence viamap { tuple_map viamap }",
         ] {
             let res = assemble(code);
             assert!(
@@ -758,6 +1517,50 @@ mod tests {
         }
     }
 
+    /// A tuple walk's body runs once per element and hands back the one value
+    /// the next step reads, so anything else is refused where it is written.
+    #[test]
+    fn test_a_walk_body_has_to_fit_the_walk() {
+        for good in [
+            "sentence s { tuple_map { push 1 add } }",
+            "sentence s { tuple_map { } }",
+            "sentence s { tuple_fold { add } }",
+            "sentence s { tuple_fold { drop 0 } }",
+        ] {
+            assert!(assemble(good).is_ok(), "{}", good);
+        }
+        for (bad, shape) in [
+            ("sentence s { tuple_map { push 1 } }", "0 -> 1"),
+            ("sentence s { tuple_map { add } }", "2 -> 1"),
+            ("sentence s { tuple_fold { add add } }", "3 -> 1"),
+            ("sentence s { tuple_fold { } }", "0 -> 0"),
+        ] {
+            let err = assemble(bad).unwrap_err();
+            assert!(
+                err.contains(&format!("but it is {}", shape)),
+                "{}: {}",
+                bad,
+                err
+            );
+        }
+    }
+
+    /// A `?!` has nowhere to go from inside a walk: the walk is not done, and
+    /// there is nothing after the body for it to skip.
+    #[test]
+    fn test_a_return_is_not_carried_out_of_a_walk() {
+        let code = r#"
+            mod prelude { symbol ok symbol err }
+            sentence s { tuple_map { ?! } }
+        "#;
+        let err = assemble(code).unwrap_err();
+        assert!(
+            err.contains("cannot be carried out of a `tuple_map`"),
+            "{}",
+            err
+        );
+    }
+
     /// The annotation that used to license recursion says so itself, rather
     /// than coming back as a name that might have been misspelled.
     #[test]
@@ -1195,9 +1998,14 @@ mod tests {
             type MyBool bool;
             type MySymbol symbol;
             type MyTuple tuple;
+            type MyMap map;
         "#;
         let res = assemble(code).unwrap();
-        assert_eq!(res.sentences.len(), 4);
+        assert_eq!(res.sentences.len(), 5);
+        assert_eq!(
+            res.sentences[res.exports["MyMap::check"]],
+            vec![Instruction::IsMap]
+        );
 
         // uppercase 'Int' should fail because it is case-sensitive
         let bad_code = r#"
@@ -1220,8 +2028,9 @@ mod tests {
         // nested. `Empty` contributes none at all: `untuple 0` leaves exactly
         // the answer. Each tuple spec past its first element still contributes
         // one dip block, which is flattened into a sentence of its own: one for
-        // `Pair`, one for `Nested`.
-        assert_eq!(res.sentences.len(), 14);
+        // `Pair`, one for `Nested`. The three tuple types then get a `new`
+        // each, and `Pair` and `Nested` a `get_0` and a `get_1` each.
+        assert_eq!(res.sentences.len(), 21);
     }
 
     #[test]
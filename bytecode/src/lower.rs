@@ -1,7 +1,10 @@
 //! Phase 2: sugar to core.
 //!
 //! This pass is purely syntactic and position independent. It needs no module
-//! tree and no scope — only a counter for naming anonymous composer modules.
+//! tree and no scope — only a counter for naming anonymous composer modules,
+//! and where each `template mod` is declared, read off the parsed items before
+//! it starts, so an instantiation can find its template by path (see
+//! [`Substitution`]).
 //!
 //! # The depth rule
 //!
@@ -19,17 +22,82 @@
 //! every sentence resolve in the module it is declared in, with no special case
 //! for type checks.
 
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::OnceLock;
+
 use crate::ast::core;
 use crate::ast::sugar::{self, Composer};
 use crate::ast::{
-    ParsedInstruction, ParsedSentence, ParsedValue, PrimitiveType, SentenceDecl, SourceAnnotation,
-    SymbolDecl, Target, TypeSpec,
+    Match, MatchArm, ParsedInstruction, ParsedSentence, ParsedValue, PrimitiveType, Repeat,
+    SentenceDecl, SourceAnnotation, SymbolDecl, Target, TypeSpec,
 };
+use crate::library::Annotation;
 use crate::resolve::{Path, PathSegment};
+use crate::source::{Error, Span};
 
 /// Lowers a parsed module body into core items.
-pub fn lower_items(items: Vec<sugar::Item>) -> Result<Vec<core::Item>, String> {
-    Lowerer { anon_counter: 0 }.items(items)
+pub fn lower_items(items: Vec<sugar::Item>) -> Result<Vec<core::Item>, Error> {
+    let mut templates = HashMap::new();
+    collect_templates(&items, &mut Vec::new(), &mut templates)?;
+    Lowerer {
+        anon_counter: 0,
+        templates,
+        module: Vec::new(),
+        instantiating: Vec::new(),
+    }
+    .items(items)
+}
+
+/// Finds every `template mod` outside a template body, keyed by the absolute
+/// path of the module it would be instantiated as if it were one.
+fn collect_templates(
+    items: &[sugar::Item],
+    module: &mut Vec<String>,
+    templates: &mut HashMap<Vec<String>, Rc<sugar::TemplateDecl>>,
+) -> Result<(), Error> {
+    for item in items {
+        match item {
+            sugar::Item::Mod(m) => {
+                module.push(m.name.clone());
+                collect_templates(&m.items, module, templates)?;
+                module.pop();
+            }
+            sugar::Item::Template(t) => {
+                // A template inside a template would be declared once per
+                // instance, at a path that only exists once it is instantiated.
+                if let Some(inner) = first_template(&t.items) {
+                    return Err(Error::at(
+                        format!(
+                            "template `{}` is declared inside template `{}`",
+                            inner.name, t.name
+                        ),
+                        inner.span,
+                    )
+                    .with_help("declare it beside the outer template instead"));
+                }
+                let mut path = module.clone();
+                path.push(t.name.clone());
+                if let Some(earlier) = templates.insert(path, Rc::new(t.clone())) {
+                    return Err(Error::at(
+                        format!("template `{}` is declared twice", t.name),
+                        t.span,
+                    )
+                    .with_note("the first declaration is here", earlier.span));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn first_template(items: &[sugar::Item]) -> Option<&sugar::TemplateDecl> {
+    items.iter().find_map(|item| match item {
+        sugar::Item::Template(t) => Some(t),
+        sugar::Item::Mod(m) => first_template(&m.items),
+        _ => None,
+    })
 }
 
 /// Rewrites a path written `levels` modules shallower than where it will be
@@ -58,6 +126,17 @@ fn shift_spec(spec: &TypeSpec, levels: usize) -> TypeSpec {
     }
 }
 
+/// Applies [`shift_path`] to every symbol or const string a value names.
+fn shift_value(val: &ParsedValue, levels: usize) -> ParsedValue {
+    match val {
+        ParsedValue::Ref(path) => ParsedValue::Ref(shift_path(path, levels)),
+        ParsedValue::Tuple(elements) => {
+            ParsedValue::Tuple(elements.iter().map(|e| shift_value(e, levels)).collect())
+        }
+        _ => val.clone(),
+    }
+}
+
 fn ident_path(segments: &[&str]) -> Path {
     Path {
         segments: segments
@@ -73,6 +152,8 @@ fn plain_mod(name: String, items: Vec<core::Item>) -> core::Item {
         items,
         is_test: false,
         exports_machine_sentences: false,
+        variants: None,
+        instance: None,
     })
 }
 
@@ -83,21 +164,20 @@ enum ComposerArg {
     Value(ParsedValue),
 }
 
-impl std::fmt::Display for ComposerArg {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ComposerArg::Path(p) => write!(f, "{}", p),
-            ComposerArg::Value(v) => write!(f, "{}", v),
-        }
-    }
-}
-
 struct Lowerer {
     anon_counter: usize,
+    /// See [`collect_templates`].
+    templates: HashMap<Vec<String>, Rc<sugar::TemplateDecl>>,
+    /// The absolute path of the module being lowered, which an instantiation
+    /// looks its template up from.
+    module: Vec<String>,
+    /// The templates being instantiated, outermost first. A template that
+    /// instantiates itself would never stop, so it is refused.
+    instantiating: Vec<Vec<String>>,
 }
 
 impl Lowerer {
-    fn items(&mut self, items: Vec<sugar::Item>) -> Result<Vec<core::Item>, String> {
+    fn items(&mut self, items: Vec<sugar::Item>) -> Result<Vec<core::Item>, Error> {
         let mut lowered = Vec::new();
         for item in items {
             lowered.extend(self.item(item)?);
@@ -105,46 +185,239 @@ impl Lowerer {
         Ok(lowered)
     }
 
-    fn item(&mut self, item: sugar::Item) -> Result<Vec<core::Item>, String> {
+    fn item(&mut self, item: sugar::Item) -> Result<Vec<core::Item>, Error> {
         match item {
             sugar::Item::Symbol(decl) => Ok(vec![core::Item::Symbol(decl)]),
             sugar::Item::ConstString(decl) => Ok(vec![core::Item::ConstString(decl)]),
+            sugar::Item::Data(decl) => Ok(vec![core::Item::Data(decl)]),
             sugar::Item::Sentence(decl) => Ok(vec![core::Item::Sentence(decl)]),
             // Core, so there is nothing to lower. The depth rule does not
             // apply either: an identity emits nothing deeper than where it was
             // written, so no path inside it needs shifting.
             sugar::Item::Identity(decl) => Ok(vec![core::Item::Identity(decl)]),
-            sugar::Item::Mod(decl) => Ok(vec![core::Item::Mod(core::ModDecl {
-                name: decl.name,
-                items: self.items(decl.items)?,
-                is_test: decl.is_test,
-                exports_machine_sentences: false,
-            })]),
+            // Core as well, and for the same reason need no shifting.
+            sugar::Item::Const(decl) => Ok(vec![core::Item::Const(decl)]),
+            sugar::Item::StaticAssert(decl) => Ok(vec![core::Item::StaticAssert(decl)]),
+            sugar::Item::Mod(decl) => {
+                self.module.push(decl.name.clone());
+                let items = self.items(decl.items);
+                self.module.pop();
+                Ok(vec![core::Item::Mod(core::ModDecl {
+                    name: decl.name,
+                    items: items?,
+                    is_test: decl.is_test,
+                    exports_machine_sentences: false,
+                    variants: None,
+                    instance: None,
+                })])
+            }
             sugar::Item::Type(decl) => Ok(vec![lower_type(decl)?]),
             sugar::Item::Enum(decl) => Ok(vec![lower_enum(decl)?]),
-            sugar::Item::Compose(decl) => self.compose(decl),
+            sugar::Item::Record(decl) => Ok(vec![lower_record(decl)?]),
+            sugar::Item::Compose(decl) => {
+                let mut siblings = Vec::new();
+                let args = self.module_args(&decl.args, &mut siblings)?;
+                siblings.push(self.compose(
+                    decl.composer,
+                    &args,
+                    decl.span,
+                    decl.name,
+                    decl.is_test,
+                )?);
+                Ok(siblings)
+            }
+            // Declares nothing where it is written; see [`Self::instance`].
+            sugar::Item::Template(_) => Ok(Vec::new()),
+            sugar::Item::Instance(decl) => {
+                let mut siblings = Vec::new();
+                let args = self.module_args(&decl.args, &mut siblings)?;
+                siblings.push(self.instance(
+                    &decl.template,
+                    &args,
+                    decl.span,
+                    decl.name,
+                    decl.is_test,
+                )?);
+                Ok(siblings)
+            }
+        }
+    }
+
+    /// `mod m compose_X(args);` becomes `mod m { …template items… }`.
+    ///
+    /// A built-in composer is a template like any other, parsed once from the
+    /// `.hana` file that defines it (see [`builtin_items`]). What it adds is a
+    /// signature, so an argument of the wrong kind is refused here, at the
+    /// composition, rather than as whatever a path where a value should be
+    /// does to the generated code.
+    fn compose(
+        &mut self,
+        composer: Composer,
+        args: &[ComposerArg],
+        site: Span,
+        name: String,
+        is_test: bool,
+    ) -> Result<core::Item, Error> {
+        let signature = signature(composer);
+        if args.len() == signature.len() {
+            for ((param, hole), arg) in signature.iter().zip(args) {
+                if let (ComposerArg::Value(val), Some(wanted)) = (arg, hole.path()) {
+                    return Err(Error::at(
+                        format!(
+                            "argument `{}` of `{}` must be {}, found the value `{}`",
+                            param,
+                            composer.name(),
+                            wanted,
+                            val
+                        ),
+                        site,
+                    ));
+                }
+            }
+        }
+        let blueprint = Blueprint {
+            name: composer.name().to_string(),
+            params: signature.iter().map(|(param, _)| *param).collect(),
+            items: builtin_items(composer),
+            declared: None,
+            declared_in: &[],
+        };
+        self.expand(blueprint, args, site, name, is_test)
+    }
+
+    /// `mod m t(args);` becomes `mod m { …t's items… }`, for a template
+    /// declared with `template mod`.
+    fn instance(
+        &mut self,
+        template: &Path,
+        args: &[ComposerArg],
+        site: Span,
+        name: String,
+        is_test: bool,
+    ) -> Result<core::Item, Error> {
+        let Some(absolute) = self.locate_template(template) else {
+            return Err(
+                Error::at(format!("cannot find template `{}`", template), site).with_help(
+                    "a template is found by path from the module instantiating it, like a sentence",
+                ),
+            );
+        };
+        let decl = self.templates[&absolute].clone();
+        if self.instantiating.contains(&absolute) {
+            return Err(
+                Error::at(format!("template `{}` instantiates itself", template), site)
+                    .with_note(format!("`{}` is declared here", decl.name), decl.span),
+            );
+        }
+
+        let blueprint = Blueprint {
+            name: template.to_string(),
+            params: decl.params.iter().map(String::as_str).collect(),
+            items: &decl.items,
+            declared: Some(decl.span),
+            declared_in: &absolute[..absolute.len() - 1],
+        };
+        self.instantiating.push(absolute.clone());
+        let expanded = self.expand(blueprint, args, site, name, is_test);
+        self.instantiating.pop();
+        expanded
+    }
+
+    /// Substitutes `args` into a template and lowers the result as the body of
+    /// a module named `name`.
+    ///
+    /// The arguments are paths relative to the instantiation site, and the
+    /// items land one module deeper, so [`COMPOSER_DEPTH`] applies. The
+    /// substitution is structural, over the parsed template, so an argument
+    /// cannot change what the rest of the template means, and every error in
+    /// what it generates can be reported at `site`.
+    fn expand(
+        &mut self,
+        blueprint: Blueprint,
+        args: &[ComposerArg],
+        site: Span,
+        name: String,
+        is_test: bool,
+    ) -> Result<core::Item, Error> {
+        let noted = |err: Error| match blueprint.declared {
+            Some(span) => err.with_note(format!("`{}` is declared here", blueprint.name), span),
+            None => err,
+        };
+
+        if args.len() != blueprint.params.len() {
+            return Err(noted(Error::at(
+                format!(
+                    "`{}` takes {} argument(s) ({}), but {} were given",
+                    blueprint.name,
+                    blueprint.params.len(),
+                    blueprint.params.join(", "),
+                    args.len()
+                ),
+                site,
+            )));
         }
+
+        let substitution = Substitution {
+            params: blueprint.params.iter().copied().zip(args).collect(),
+            declared_in: blueprint.declared_in,
+        };
+        let items = substitution.items(blueprint.items, 0).map_err(|e| {
+            noted(Error::at(
+                format!("in this instance of `{}`: {}", blueprint.name, e),
+                site,
+            ))
+        })?;
+
+        self.module.push(name.clone());
+        let items = self.items(items);
+        self.module.pop();
+
+        Ok(core::Item::Mod(core::ModDecl {
+            name,
+            items: items?,
+            is_test,
+            // A built-in template's sentences carry no `export` markers, so a
+            // machine made from one has to export them wholesale. A declared
+            // template gets the same, so it can be written the same way.
+            exports_machine_sentences: is_test,
+            variants: None,
+            instance: Some(core::Instance {
+                template: blueprint.name,
+                declared: blueprint.declared,
+                site,
+            }),
+        }))
     }
 
-    /// `mod m compose_X(args);` becomes `mod m { …template items… }`, preceded
-    /// by a sibling module for each nested composition.
-    fn compose(&mut self, decl: sugar::ComposeDecl) -> Result<Vec<core::Item>, String> {
-        let mut siblings = Vec::new();
-        let mut args = Vec::new();
-        for arg in &decl.args {
-            args.push(self.module_expr(arg, &mut siblings)?);
+    /// The absolute path of the template `path` names from the current
+    /// module, if one is declared there.
+    ///
+    /// Resolved the way phase 4 resolves any path — `crate`, `super`, then
+    /// names, with no fallback to enclosing modules — but syntactically, since
+    /// the module tree does not exist yet and templates are not in it anyway.
+    fn locate_template(&self, path: &Path) -> Option<Vec<String>> {
+        let mut absolute = self.module.clone();
+        for segment in &path.segments {
+            match segment {
+                PathSegment::Crate => absolute.clear(),
+                PathSegment::Super => {
+                    absolute.pop()?;
+                }
+                PathSegment::Identifier(name) => absolute.push(name.clone()),
+            }
         }
+        self.templates.contains_key(&absolute).then_some(absolute)
+    }
 
-        let generated = self.generate(decl.composer, &args)?;
-        siblings.push(core::Item::Mod(core::ModDecl {
-            name: decl.name,
-            items: generated,
-            is_test: decl.is_test,
-            // A composed machine's sentences come from a template and carry no
-            // `export` markers, so the module has to export them wholesale.
-            exports_machine_sentences: decl.is_test,
-        }));
-        Ok(siblings)
+    fn module_args(
+        &mut self,
+        exprs: &[sugar::ModuleExpr],
+        siblings: &mut Vec<core::Item>,
+    ) -> Result<Vec<ComposerArg>, Error> {
+        exprs
+            .iter()
+            .map(|expr| self.module_expr(expr, siblings))
+            .collect()
     }
 
     /// Reduces a composer argument to a path or value. A nested composition is
@@ -153,214 +426,505 @@ impl Lowerer {
         &mut self,
         expr: &sugar::ModuleExpr,
         siblings: &mut Vec<core::Item>,
-    ) -> Result<ComposerArg, String> {
+    ) -> Result<ComposerArg, Error> {
         match expr {
             sugar::ModuleExpr::Named(path) => Ok(ComposerArg::Path(path.clone())),
             sugar::ModuleExpr::Value(val) => Ok(ComposerArg::Value(val.clone())),
-            sugar::ModuleExpr::Composed { composer, args } => {
-                let mut lowered = Vec::new();
-                for arg in args {
-                    lowered.push(self.module_expr(arg, siblings)?);
-                }
-                let generated = self.generate(*composer, &lowered)?;
-
-                // The name must be a legal identifier: composer templates are
-                // rendered as text and re-tokenized, so it round-trips through
-                // the lexer.
-                let name = format!("__anon_mod_{}", self.anon_counter);
-                self.anon_counter += 1;
-                siblings.push(plain_mod(name.clone(), generated));
-
+            sugar::ModuleExpr::Composed {
+                composer,
+                args,
+                span,
+            } => {
+                let lowered = self.module_args(args, siblings)?;
+                let name = self.anonymous_name();
+                siblings.push(self.compose(*composer, &lowered, *span, name.clone(), false)?);
+                Ok(ComposerArg::Path(ident_path(&[&name])))
+            }
+            sugar::ModuleExpr::Instance {
+                template,
+                args,
+                span,
+            } => {
+                let lowered = self.module_args(args, siblings)?;
+                let name = self.anonymous_name();
+                siblings.push(self.instance(template, &lowered, *span, name.clone(), false)?);
                 Ok(ComposerArg::Path(ident_path(&[&name])))
             }
         }
     }
 
-    /// Expands a composer into the items that go inside the composed module.
+    /// A name for the module a nested composition lands in.
     ///
-    /// Every argument is user-written at the declaration site while the items
-    /// land one level deeper, so paths shift by one throughout.
-    fn generate(
-        &mut self,
-        composer: Composer,
-        args: &[ComposerArg],
-    ) -> Result<Vec<core::Item>, String> {
-        let name = composer.name();
-        match composer {
-            Composer::Concurrent => {
-                let paths = expect_paths(args, name, 3)?;
-                self.template(
-                    TEMPLATE_CONCURRENT,
-                    &[("p1", &paths[0]), ("p2", &paths[1]), ("sync_fn", &paths[2])],
-                )
+    /// Nothing re-lexes it, so it need not be an identifier, and is not one:
+    /// no path a user writes can name it or collide with it.
+    fn anonymous_name(&mut self) -> String {
+        let name = format!("{{anon#{}}}", self.anon_counter);
+        self.anon_counter += 1;
+        name
+    }
+}
+
+/// A template about to be instantiated: declared with `template mod`, or
+/// built in.
+struct Blueprint<'a> {
+    /// The template's name as the instantiation wrote it, for messages.
+    name: String,
+    params: Vec<&'a str>,
+    items: &'a [sugar::Item],
+    /// Where it was declared; a built-in template was declared nowhere the
+    /// user can see.
+    declared: Option<Span>,
+    /// See [`Substitution::declared_in`].
+    declared_in: &'a [String],
+}
+
+/// Every composer argument is shifted by one: the user wrote it at the
+/// declaration site, and the generated items land one module deeper.
+const COMPOSER_DEPTH: usize = 1;
+
+/// Replaces a template's parameters with an instantiation's arguments, over
+/// the parsed template body.
+///
+/// A parameter is a name that stands for the first segment of a path. Bound to
+/// a path, it is replaced by that path shifted to where the use lands; bound to
+/// a value, it can only stand alone where a value goes, and naming anything
+/// through it is an error. Every other path in the body was written relative to
+/// the template and means the same in the instance, except one that climbs out
+/// of it with `super`: that one is rewritten from the crate root, so it still
+/// names what it named beside the template's declaration.
+///
+/// `depth` counts the `mod`s between a use and the template body's top level.
+struct Substitution<'a> {
+    params: HashMap<&'a str, &'a ComposerArg>,
+    /// The absolute path of the module the template is declared in.
+    declared_in: &'a [String],
+}
+
+impl<'a> Substitution<'a> {
+    /// The same substitution, with `names` standing for themselves.
+    fn hiding(&self, names: &[String]) -> Substitution<'a> {
+        Substitution {
+            params: self
+                .params
+                .iter()
+                .filter(|(name, _)| !names.iter().any(|n| n == *name))
+                .map(|(name, arg)| (*name, *arg))
+                .collect(),
+            declared_in: self.declared_in,
+        }
+    }
+
+    fn items(&self, items: &[sugar::Item], depth: usize) -> Result<Vec<sugar::Item>, String> {
+        items.iter().map(|item| self.item(item, depth)).collect()
+    }
+
+    fn item(&self, item: &sugar::Item, depth: usize) -> Result<sugar::Item, String> {
+        Ok(match item {
+            sugar::Item::Symbol(_) | sugar::Item::ConstString(_) | sugar::Item::Data(_) => {
+                item.clone()
             }
-            Composer::Hidden => {
-                let paths = expect_paths(args, name, 2)?;
-                self.template(
-                    TEMPLATE_HIDDEN,
-                    &[("concurrent", &paths[0]), ("hidden_fn", &paths[1])],
-                )
+            // Refused by `collect_templates`, so never reached.
+            sugar::Item::Template(_) => item.clone(),
+            // A generic sentence's own parameters hide the template's.
+            sugar::Item::Sentence(decl) if !decl.generics.is_empty() => {
+                let inner = self.hiding(&decl.generics);
+                sugar::Item::Sentence(SentenceDecl {
+                    body: inner.sentence(&decl.body, depth)?,
+                    annotations: self.annotations(&decl.annotations, depth)?,
+                    ..decl.clone()
+                })
             }
-            Composer::Prefix => {
-                let paths = expect_paths(args, name, 2)?;
-                self.template(
-                    TEMPLATE_PREFIX,
-                    &[("target", &paths[0]), ("prefix", &paths[1])],
-                )
+            sugar::Item::Sentence(decl) => sugar::Item::Sentence(SentenceDecl {
+                body: self.sentence(&decl.body, depth)?,
+                annotations: self.annotations(&decl.annotations, depth)?,
+                ..decl.clone()
+            }),
+            sugar::Item::Identity(decl) => sugar::Item::Identity(crate::ast::IdentityDecl {
+                lhs: self.sentence(&decl.lhs, depth)?,
+                rhs: self.sentence(&decl.rhs, depth)?,
+                annotations: self.annotations(&decl.annotations, depth)?,
+                ..decl.clone()
+            }),
+            sugar::Item::Const(decl) => sugar::Item::Const(crate::ast::ConstDecl {
+                body: self.sentence(&decl.body, depth)?,
+                ..decl.clone()
+            }),
+            sugar::Item::StaticAssert(decl) => {
+                sugar::Item::StaticAssert(crate::ast::StaticAssertDecl {
+                    body: self.sentence(&decl.body, depth)?,
+                    ..decl.clone()
+                })
             }
-            Composer::RenamePrefix => {
-                let paths = expect_paths(args, name, 3)?;
-                self.template(
-                    TEMPLATE_RENAME_PREFIX,
-                    &[
-                        ("from_symbol", &paths[0]),
-                        ("to_symbol", &paths[1]),
-                        ("target", &paths[2]),
-                    ],
-                )
+            sugar::Item::Mod(decl) => sugar::Item::Mod(sugar::ModDecl {
+                items: self.items(&decl.items, depth + 1)?,
+                ..decl.clone()
+            }),
+            sugar::Item::Type(decl) => sugar::Item::Type(sugar::TypeDecl {
+                spec: self.spec(&decl.spec, depth)?,
+                annotations: self.annotations(&decl.annotations, depth)?,
+                ..decl.clone()
+            }),
+            sugar::Item::Enum(decl) => sugar::Item::Enum(sugar::EnumDecl {
+                variants: decl
+                    .variants
+                    .iter()
+                    .map(|v| {
+                        Ok(sugar::EnumVariant {
+                            name: v.name.clone(),
+                            elements: self.specs(&v.elements, depth)?,
+                        })
+                    })
+                    .collect::<Result<_, String>>()?,
+                annotations: self.annotations(&decl.annotations, depth)?,
+                ..decl.clone()
+            }),
+            sugar::Item::Record(decl) => sugar::Item::Record(sugar::RecordDecl {
+                fields: decl
+                    .fields
+                    .iter()
+                    .map(|f| {
+                        Ok(sugar::RecordField {
+                            name: f.name.clone(),
+                            spec: self.spec(&f.spec, depth)?,
+                        })
+                    })
+                    .collect::<Result<_, String>>()?,
+                annotations: self.annotations(&decl.annotations, depth)?,
+                ..decl.clone()
+            }),
+            sugar::Item::Compose(decl) => sugar::Item::Compose(sugar::ComposeDecl {
+                args: self.module_exprs(&decl.args, depth)?,
+                ..decl.clone()
+            }),
+            sugar::Item::Instance(decl) => sugar::Item::Instance(sugar::InstanceDecl {
+                template: self.path(&decl.template, depth)?,
+                args: self.module_exprs(&decl.args, depth)?,
+                ..decl.clone()
+            }),
+        })
+    }
+
+    /// What a path written in the template names in the instance: a path, or
+    /// the value a parameter standing alone is bound to.
+    fn binding(&self, path: &Path, depth: usize) -> Result<ComposerArg, String> {
+        let (first, rest) = match path.segments.split_first() {
+            Some((PathSegment::Identifier(first), rest)) => (first, rest),
+            _ => return self.escape(path, depth).map(ComposerArg::Path),
+        };
+        match self.params.get(first.as_str()) {
+            None => self.escape(path, depth).map(ComposerArg::Path),
+            Some(ComposerArg::Path(arg)) => {
+                let mut shifted = shift_path(arg, COMPOSER_DEPTH + depth);
+                shifted.segments.extend(rest.iter().cloned());
+                Ok(ComposerArg::Path(shifted))
             }
-            Composer::StaticClosure => {
-                expect_arity(args, name, 2, "target_machine and a value")?;
-                let machine = expect_path(
-                    &args[0],
-                    name,
-                    "first argument must be a machine module path",
-                )?;
-                let val = as_value(&args[1]);
-                self.template(
-                    TEMPLATE_STATIC_CLOSURE,
-                    &[("machine", &machine), ("val", &val)],
-                )
+            Some(ComposerArg::Value(val)) if rest.is_empty() => {
+                Ok(ComposerArg::Value(shift_value(val, COMPOSER_DEPTH + depth)))
             }
-            Composer::Done => {
-                if !args.is_empty() {
-                    return Err(format!("{} expects 0 arguments", name));
-                }
-                self.template(TEMPLATE_DONE, &[])
+            Some(ComposerArg::Value(val)) => Err(format!(
+                "parameter `{}` is bound to the value `{}`, which has nothing to name in `{}`",
+                first, val, path
+            )),
+        }
+    }
+
+    /// A path in a position that needs one, where a value will not do.
+    fn path(&self, path: &Path, depth: usize) -> Result<Path, String> {
+        match self.binding(path, depth)? {
+            ComposerArg::Path(p) => Ok(p),
+            ComposerArg::Value(val) => Err(format!(
+                "parameter `{}` is bound to the value `{}`, but is used where a path is needed",
+                path, val
+            )),
+        }
+    }
+
+    /// Rewrites a path that climbs out of the template body from the crate
+    /// root, and leaves every other path alone.
+    fn escape(&self, path: &Path, depth: usize) -> Result<Path, String> {
+        let supers = path
+            .segments
+            .iter()
+            .take_while(|s| **s == PathSegment::Super)
+            .count();
+        if supers <= depth {
+            return Ok(path.clone());
+        }
+        // The first `super` past the body's top level is the declaring module.
+        let climb = supers - depth - 1;
+        let Some(kept) = self.declared_in.len().checked_sub(climb) else {
+            return Err(format!("`{}` climbs out of the crate", path));
+        };
+        let mut segments = vec![PathSegment::Crate];
+        segments.extend(
+            self.declared_in[..kept]
+                .iter()
+                .map(|name| PathSegment::Identifier(name.clone())),
+        );
+        segments.extend(path.segments[supers..].iter().cloned());
+        Ok(Path { segments })
+    }
+
+    fn value(&self, val: &ParsedValue, depth: usize) -> Result<ParsedValue, String> {
+        match val {
+            ParsedValue::Ref(path) => Ok(match self.binding(path, depth)? {
+                ComposerArg::Path(p) => ParsedValue::Ref(p),
+                ComposerArg::Value(val) => val,
+            }),
+            ParsedValue::Tuple(elements) => Ok(ParsedValue::Tuple(
+                elements
+                    .iter()
+                    .map(|e| self.value(e, depth))
+                    .collect::<Result<_, _>>()?,
+            )),
+            _ => Ok(val.clone()),
+        }
+    }
+
+    fn sentence(&self, body: &ParsedSentence, depth: usize) -> Result<ParsedSentence, String> {
+        Ok(ParsedSentence {
+            instructions: body
+                .instructions
+                .iter()
+                .map(|i| self.instruction(i, depth))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn instruction(
+        &self,
+        instruction: &ParsedInstruction,
+        depth: usize,
+    ) -> Result<ParsedInstruction, String> {
+        Ok(match instruction {
+            ParsedInstruction::Push(val) => ParsedInstruction::Push(self.value(val, depth)?),
+            ParsedInstruction::Jump(target) => ParsedInstruction::Jump(self.target(target, depth)?),
+            ParsedInstruction::Dip(n, target) => {
+                ParsedInstruction::Dip(*n, self.target(target, depth)?)
             }
-            Composer::Emit => {
-                let paths = expect_paths(args, name, 1)?;
-                self.template(TEMPLATE_EMIT, &[("machine", &paths[0])])
+            ParsedInstruction::Branch(then, otherwise) => {
+                ParsedInstruction::Branch(self.target(then, depth)?, self.target(otherwise, depth)?)
             }
-            Composer::EmitStatic => {
-                expect_arity(args, name, 2, "event and target_machine")?;
-                let val = as_value(&args[0]);
-                let machine = expect_path(
-                    &args[1],
-                    name,
-                    "second argument must be a machine module path",
-                )?;
-                self.template(
-                    TEMPLATE_EMIT_STATIC,
-                    &[("machine", &machine), ("val", &val)],
-                )
+            ParsedInstruction::TupleMap(target) => {
+                ParsedInstruction::TupleMap(self.target(target, depth)?)
             }
-            Composer::Accept => {
-                expect_arity(args, name, 2, "value_set and target_machine")?;
-                let val_set =
-                    expect_path(&args[0], name, "first argument must be a sentence path")?;
-                let machine = expect_path(
-                    &args[1],
-                    name,
-                    "second argument must be a machine module path",
-                )?;
-                self.template(
-                    TEMPLATE_ACCEPT,
-                    &[("machine", &machine), ("val_set_path", &val_set)],
-                )
+            ParsedInstruction::TupleFold(target) => {
+                ParsedInstruction::TupleFold(self.target(target, depth)?)
             }
-            Composer::AcceptStatic => {
-                expect_arity(args, name, 2, "event and target_machine")?;
-                let val = as_value(&args[0]);
-                let machine = expect_path(
-                    &args[1],
-                    name,
-                    "second argument must be a machine module path",
-                )?;
-                self.template(
-                    TEMPLATE_ACCEPT_STATIC,
-                    &[("machine", &machine), ("val", &val)],
-                )
+            ParsedInstruction::TypeCheckPath(path) => {
+                ParsedInstruction::TypeCheckPath(self.path(path, depth)?)
             }
+            ParsedInstruction::Match(m) => ParsedInstruction::Match(Match {
+                enum_path: self.path(&m.enum_path, depth)?,
+                arms: m
+                    .arms
+                    .iter()
+                    .map(|arm| {
+                        Ok(MatchArm {
+                            pattern: arm.pattern.clone(),
+                            body: self.sentence(&arm.body, depth)?,
+                        })
+                    })
+                    .collect::<Result<_, String>>()?,
+            }),
+            ParsedInstruction::Repeat(r) => ParsedInstruction::Repeat(Repeat {
+                body: self.target(&r.body, depth)?,
+                ..r.clone()
+            }),
+            _ => instruction.clone(),
+        })
+    }
+
+    fn target(&self, target: &Target, depth: usize) -> Result<Target, String> {
+        match target {
+            Target::Label(path) => Ok(Target::Label(self.path(path, depth)?)),
+            Target::Instance(path, args) => Ok(Target::Instance(
+                self.path(path, depth)?,
+                args.iter()
+                    .map(|arg| self.target(arg, depth))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Target::Inline(body) => Ok(Target::Inline(self.sentence(body, depth)?)),
         }
     }
 
-    /// Renders a template and lowers the result.
-    ///
-    /// Templates are still substituted textually and re-parsed; making them
-    /// structured is a separate change. They contain `function` declarations,
-    /// which are sugar, so the parsed result has to go back through lowering.
-    fn template(
-        &mut self,
-        template_str: &str,
-        vars: &[(&str, &dyn std::fmt::Display)],
-    ) -> Result<Vec<core::Item>, String> {
-        let mut rendered = template_str.to_string();
-        for (name, val) in vars {
-            rendered = rendered.replace(&format!("{{{{{}}}}}", name), &val.to_string());
+    fn spec(&self, spec: &TypeSpec, depth: usize) -> Result<TypeSpec, String> {
+        match spec {
+            TypeSpec::Path(path) => Ok(TypeSpec::Path(self.path(path, depth)?)),
+            TypeSpec::Tuple(elements) => Ok(TypeSpec::Tuple(self.specs(elements, depth)?)),
+            TypeSpec::Union(variants) => Ok(TypeSpec::Union(self.specs(variants, depth)?)),
+            TypeSpec::Primitive(_) | TypeSpec::Literal(_) => Ok(spec.clone()),
         }
+    }
 
-        // The template is generated, so it gets its own map under a name that
-        // says so. A failure here is a bug in the template rather than in the
-        // user's file, and the rendered error shows the substituted text — the
-        // only place the mistake is visible.
-        let mut map = crate::source::SourceMap::new();
-        let file = map.add("<composer template>", rendered);
-        let parsed =
-            crate::assembly::parse_source(&mut map, file, None).map_err(|e| map.render(&e))?;
-        self.items(parsed)
+    fn specs(&self, specs: &[TypeSpec], depth: usize) -> Result<Vec<TypeSpec>, String> {
+        specs.iter().map(|s| self.spec(s, depth)).collect()
     }
-}
 
-/// Every composer argument is shifted by one: the user wrote it at the
-/// declaration site, and the generated items land one module deeper.
-const COMPOSER_DEPTH: usize = 1;
+    fn annotations(
+        &self,
+        annotations: &[SourceAnnotation],
+        depth: usize,
+    ) -> Result<Vec<SourceAnnotation>, String> {
+        annotations
+            .iter()
+            .map(|ann| {
+                Ok(match ann {
+                    Annotation::Arity(i, o) => Annotation::Arity(*i, *o),
+                    Annotation::Precondition(p) => Annotation::Precondition(self.path(p, depth)?),
+                    Annotation::Postcondition(p) => Annotation::Postcondition(self.path(p, depth)?),
+                })
+            })
+            .collect()
+    }
 
-fn as_value(arg: &ComposerArg) -> ParsedValue {
-    match arg {
-        ComposerArg::Value(val) => val.clone(),
-        ComposerArg::Path(path) => ParsedValue::Ref(shift_path(path, COMPOSER_DEPTH)),
+    fn module_exprs(
+        &self,
+        exprs: &[sugar::ModuleExpr],
+        depth: usize,
+    ) -> Result<Vec<sugar::ModuleExpr>, String> {
+        exprs
+            .iter()
+            .map(|expr| {
+                Ok(match expr {
+                    // A parameter forwarded to a nested composition keeps
+                    // whatever it is bound to, value or path.
+                    sugar::ModuleExpr::Named(path) => match self.binding(path, depth)? {
+                        ComposerArg::Path(p) => sugar::ModuleExpr::Named(p),
+                        ComposerArg::Value(val) => sugar::ModuleExpr::Value(val),
+                    },
+                    sugar::ModuleExpr::Composed {
+                        composer,
+                        args,
+                        span,
+                    } => sugar::ModuleExpr::Composed {
+                        composer: *composer,
+                        args: self.module_exprs(args, depth)?,
+                        span: *span,
+                    },
+                    sugar::ModuleExpr::Instance {
+                        template,
+                        args,
+                        span,
+                    } => sugar::ModuleExpr::Instance {
+                        template: self.path(template, depth)?,
+                        args: self.module_exprs(args, depth)?,
+                        span: *span,
+                    },
+                    sugar::ModuleExpr::Value(val) => {
+                        sugar::ModuleExpr::Value(self.value(val, depth)?)
+                    }
+                })
+            })
+            .collect()
     }
 }
 
-fn expect_path(arg: &ComposerArg, composer: &str, what: &str) -> Result<Path, String> {
-    match arg {
-        ComposerArg::Path(path) => Ok(shift_path(path, COMPOSER_DEPTH)),
-        ComposerArg::Value(_) => Err(format!("{}: {}", composer, what)),
+/// `type Name spec;` becomes `mod Name { export sentence check { …spec… } }`.
+/// The check lands one level below the declaration, so the spec shifts by one.
+///
+/// A tuple-shaped spec also gets a constructor and positional accessors:
+/// `new` packs its `n` elements (`n -> 1`) and `get_<i>` answers element `i`
+/// of a value (`1 -> 1`). An accessor asks no question of its own, so on a
+/// value of the wrong shape it answers whatever `as_tuple n` made of it;
+/// `check` is the question.
+fn lower_type(decl: sugar::TypeDecl) -> Result<core::Item, String> {
+    let spec = shift_spec(&decl.spec, 1);
+    let check = check_sentence(&spec, decl.annotations)?;
+    let mut items = vec![core::Item::Sentence(check)];
+    if let TypeSpec::Tuple(elements) = &spec {
+        let n = elements.len();
+        items.push(generated("new", vec![ParsedInstruction::Tuple(n)]));
+        for i in 0..n {
+            items.push(generated(&format!("get_{}", i), element_of(n, i)));
+        }
     }
+    Ok(plain_mod(decl.name, items))
 }
 
-fn expect_arity(args: &[ComposerArg], composer: &str, n: usize, what: &str) -> Result<(), String> {
-    if args.len() != n {
-        return Err(format!(
-            "{} expects exactly {} arguments: {}",
-            composer, n, what
+/// `record Name { f: spec, ... }` is a tuple type whose positions have names:
+/// `mod Name { check; new; get_<f>; set_<f>; ... }`, with `check` the tuple
+/// spec of its fields in order and `new` taking them in that order too.
+/// `get_<f>` is the positional accessor a tuple `type` would call `get_<i>`.
+/// `set_<f>` takes a value and the field's new contents (`2 -> 1`).
+///
+/// Since the module has a `check`, a spec names a record the way it names a
+/// `type`, by its path.
+fn lower_record(decl: sugar::RecordDecl) -> Result<core::Item, String> {
+    let n = decl.fields.len();
+    let spec = TypeSpec::Tuple(decl.fields.iter().map(|f| shift_spec(&f.spec, 1)).collect());
+    let check = check_sentence(&spec, decl.annotations)?;
+    let mut items = vec![
+        core::Item::Sentence(check),
+        generated("new", vec![ParsedInstruction::Tuple(n)]),
+    ];
+    for (i, field) in decl.fields.iter().enumerate() {
+        items.push(generated(&format!("get_{}", field.name), element_of(n, i)));
+        items.push(generated(
+            &format!("set_{}", field.name),
+            replace_element(n, i),
         ));
     }
-    Ok(())
+    Ok(plain_mod(decl.name, items))
+}
+
+/// `[value, x] -> [value with element i replaced by x]`, for `n`-tuples.
+///
+/// The value is coerced to an `n`-tuple first, so a value of the wrong shape
+/// comes out as `n` `()`s with `x` in slot `i` — what taking it apart and
+/// putting it back together made of it — rather than the `()` `tuple_set`
+/// leaves on its own.
+fn replace_element(n: usize, i: usize) -> Vec<ParsedInstruction> {
+    vec![
+        ParsedInstruction::Dip(
+            1,
+            Target::Inline(ParsedSentence {
+                instructions: vec![ParsedInstruction::AsTuple(n)],
+            }),
+        ),
+        ParsedInstruction::TupleSet(i),
+    ]
+}
+
+/// Element `i` of a value read as an `n`-tuple. The coercion is what keeps
+/// this the accessor it was when it took the value apart: a tuple of another
+/// width answers `()`, even where it has an element `i` to give.
+fn element_of(n: usize, i: usize) -> Vec<ParsedInstruction> {
+    vec![
+        ParsedInstruction::AsTuple(n),
+        ParsedInstruction::TupleGet(i),
+    ]
+}
+
+/// An exported sentence the lowering wrote, with no annotations: its arity is
+/// whatever its body infers to.
+fn generated(name: &str, instructions: Vec<ParsedInstruction>) -> core::Item {
+    core::Item::Sentence(generated_sentence(name, instructions))
 }
 
-fn expect_paths(args: &[ComposerArg], composer: &str, n: usize) -> Result<Vec<Path>, String> {
-    if args.len() != n {
-        return Err(format!("{} requires exactly {} arguments", composer, n));
+/// What [`generated`] declares, for a sentence that is not an item yet.
+fn generated_sentence(name: &str, instructions: Vec<ParsedInstruction>) -> SentenceDecl {
+    SentenceDecl {
+        name: name.to_string(),
+        generics: Vec::new(),
+        body: ParsedSentence { instructions },
+        annotations: Vec::new(),
+        is_exported: true,
+        is_test: false,
+        span: None,
     }
-    args.iter()
-        .map(|arg| match arg {
-            ComposerArg::Path(path) => Ok(shift_path(path, COMPOSER_DEPTH)),
-            ComposerArg::Value(_) => Err(format!(
-                "{} expects path arguments, found a literal value",
-                composer
-            )),
-        })
-        .collect()
 }
 
-/// `type Name spec;` becomes `mod Name { export sentence check { …spec… } }`.
-/// The check lands one level below the declaration, so the spec shifts by one.
-fn lower_type(decl: sugar::TypeDecl) -> Result<core::Item, String> {
-    let spec = shift_spec(&decl.spec, 1);
-    let check = check_sentence(&spec, decl.annotations)?;
-    Ok(plain_mod(decl.name, vec![core::Item::Sentence(check)]))
+/// `crate::prelude::<tag>`, a result tag. Crate-rooted, so it needs no shift
+/// wherever it lands.
+fn result_tag(tag: &str) -> ParsedValue {
+    ParsedValue::Ref(Path {
+        segments: vec![
+            PathSegment::Crate,
+            PathSegment::Identifier("prelude".to_string()),
+            PathSegment::Identifier(tag.to_string()),
+        ],
+    })
 }
 
 /// `enum Name { V(specs), … }` becomes a module per variant, each holding a
@@ -369,9 +933,21 @@ fn lower_type(decl: sugar::TypeDecl) -> Result<core::Item, String> {
 ///
 /// The tag sits on top of the payload, which is the *last* element of a pair:
 /// a value of the variant is `push …body… ; push tag ; tuple 2`.
+///
+/// Each variant also gets the sentences that save writing that by hand:
+///
+/// * `new` takes the payload's elements and builds the value, so its arity is
+///   the payload's width in and one out.
+/// * `is` asks whether a value carries the variant's tag, and nothing more.
+///   `check` is the one that also asks about the payload.
+/// * `get` answers `(payload, ok)` for a value `check` accepts and
+///   `(value, err)` for anything else, so `jump E::V::get ?` unwraps one.
+///   It is left on the variant rather than in its module: whether the crate
+///   declares those tags is for phase 3 to find out.
 fn lower_enum(decl: sugar::EnumDecl) -> Result<core::Item, String> {
     let mut items = Vec::new();
     let mut variant_specs = Vec::new();
+    let mut variants = Vec::new();
 
     for variant in decl.variants {
         // `Body::check` lands three levels below the declaration site
@@ -393,16 +969,61 @@ fn lower_enum(decl: sugar::EnumDecl) -> Result<core::Item, String> {
         ]);
         let variant_check = check_sentence(&variant_spec, Vec::new())?;
 
-        items.push(plain_mod(
-            variant.name.clone(),
+        let width = variant.elements.len();
+        let tag = || ParsedValue::Ref(ident_path(&["tag"]));
+        let variant_items = vec![
+            core::Item::Symbol(SymbolDecl {
+                name: "tag".to_string(),
+            }),
+            plain_mod("Body".to_string(), vec![core::Item::Sentence(body_check)]),
+            core::Item::Sentence(variant_check),
+            generated(
+                "new",
+                vec![
+                    ParsedInstruction::Tuple(width),
+                    ParsedInstruction::Push(tag()),
+                    ParsedInstruction::Tuple(2),
+                ],
+            ),
+            // Whatever `untuple 2` makes of a value that is not a pair, the
+            // tag it leaves is `()`, which is nobody's.
+            generated(
+                "is",
+                vec![
+                    ParsedInstruction::Untuple(2),
+                    ParsedInstruction::Push(tag()),
+                    ParsedInstruction::Equal,
+                    ParsedInstruction::Drop(1),
+                ],
+            ),
+        ];
+        let ok = vec![
+            ParsedInstruction::Untuple(2),
+            ParsedInstruction::Drop(0),
+            ParsedInstruction::Push(result_tag("ok")),
+            ParsedInstruction::Tuple(2),
+        ];
+        let err = vec![
+            ParsedInstruction::Push(result_tag("err")),
+            ParsedInstruction::Tuple(2),
+        ];
+        let get = generated_sentence(
+            "get",
             vec![
-                core::Item::Symbol(SymbolDecl {
-                    name: "tag".to_string(),
-                }),
-                plain_mod("Body".to_string(), vec![core::Item::Sentence(body_check)]),
-                core::Item::Sentence(variant_check),
+                ParsedInstruction::Pick(0),
+                ParsedInstruction::Jump(Target::Label(ident_path(&["check"]))),
+                ParsedInstruction::Branch(
+                    Target::Inline(ParsedSentence { instructions: ok }),
+                    Target::Inline(ParsedSentence { instructions: err }),
+                ),
             ],
-        ));
+        );
+        variants.push(core::Variant {
+            name: variant.name.clone(),
+            width,
+            get,
+        });
+        items.push(plain_mod(variant.name.clone(), variant_items));
 
         // `Name::check` lands in `Name`, and the variants are its own children.
         variant_specs.push(TypeSpec::Path(ident_path(&[&variant.name])));
@@ -411,7 +1032,14 @@ fn lower_enum(decl: sugar::EnumDecl) -> Result<core::Item, String> {
     let overall = check_sentence(&TypeSpec::Union(variant_specs), decl.annotations)?;
     items.push(core::Item::Sentence(overall));
 
-    Ok(plain_mod(decl.name, items))
+    Ok(core::Item::Mod(core::ModDecl {
+        name: decl.name,
+        items,
+        is_test: false,
+        exports_machine_sentences: false,
+        variants: Some(variants),
+        instance: None,
+    }))
 }
 
 /// Builds the exported `check` predicate for a spec.
@@ -425,12 +1053,14 @@ fn check_sentence(
 ) -> Result<SentenceDecl, String> {
     Ok(SentenceDecl {
         name: "check".to_string(),
+        generics: Vec::new(),
         body: ParsedSentence {
             instructions: compile_type_spec(spec)?,
         },
         annotations,
         is_exported: true,
         is_test: false,
+        span: None,
     })
 }
 
@@ -442,6 +1072,7 @@ fn compile_type_spec(spec: &TypeSpec) -> Result<Vec<ParsedInstruction>, String>
             PrimitiveType::ConstString => ParsedInstruction::IsConstString,
             PrimitiveType::Symbol => ParsedInstruction::IsSymbol,
             PrimitiveType::Tuple => ParsedInstruction::IsTuple,
+            PrimitiveType::Map => ParsedInstruction::IsMap,
         }]),
         TypeSpec::Literal(val) => Ok(vec![
             ParsedInstruction::Push(val.clone()),
@@ -546,13 +1177,89 @@ fn compile_union(variants: &[TypeSpec]) -> Result<Vec<ParsedInstruction>, String
     Ok(insts)
 }
 
-const TEMPLATE_CONCURRENT: &str = include_str!("templates/compose_concurrent.tmpl.hana");
-const TEMPLATE_HIDDEN: &str = include_str!("templates/compose_hidden.tmpl.hana");
-const TEMPLATE_PREFIX: &str = include_str!("templates/compose_prefix.tmpl.hana");
-const TEMPLATE_RENAME_PREFIX: &str = include_str!("templates/compose_rename_prefix.tmpl.hana");
-const TEMPLATE_STATIC_CLOSURE: &str = include_str!("templates/compose_static_closure.tmpl.hana");
-const TEMPLATE_DONE: &str = include_str!("templates/compose_done.tmpl.hana");
-const TEMPLATE_EMIT: &str = include_str!("templates/compose_emit.tmpl.hana");
-const TEMPLATE_EMIT_STATIC: &str = include_str!("templates/compose_emit_static.tmpl.hana");
-const TEMPLATE_ACCEPT: &str = include_str!("templates/compose_accept.tmpl.hana");
-const TEMPLATE_ACCEPT_STATIC: &str = include_str!("templates/compose_accept_static.tmpl.hana");
+/// What a built-in composer's parameter stands for.
+#[derive(Clone, Copy)]
+enum Hole {
+    /// A machine module, named through: `machine::init`.
+    Machine,
+    /// A sentence, jumped to.
+    Sentence,
+    /// A symbol, pushed. Written as a path, since that is what a symbol is.
+    Symbol,
+    /// Any value, pushed: a literal, or a path to a declared constant.
+    Value,
+}
+
+impl Hole {
+    /// What an argument for this hole must be, when it must be a path.
+    fn path(self) -> Option<&'static str> {
+        match self {
+            Hole::Machine => Some("a machine module path"),
+            Hole::Sentence => Some("a sentence path"),
+            Hole::Symbol => Some("a symbol path"),
+            Hole::Value => None,
+        }
+    }
+}
+
+/// A built-in composer's parameters, in argument order. Each name is a hole in
+/// its template, written there as a plain identifier.
+fn signature(composer: Composer) -> &'static [(&'static str, Hole)] {
+    use Hole::*;
+    match composer {
+        Composer::Concurrent => &[("p1", Machine), ("p2", Machine), ("sync_fn", Sentence)],
+        Composer::Hidden => &[("concurrent", Machine), ("hidden_fn", Sentence)],
+        Composer::Prefix => &[("target", Machine), ("prefix", Symbol)],
+        Composer::RenamePrefix => &[
+            ("from_symbol", Symbol),
+            ("to_symbol", Symbol),
+            ("target", Machine),
+        ],
+        Composer::StaticClosure => &[("machine", Machine), ("val", Value)],
+        Composer::Done => &[],
+        Composer::Emit => &[("machine", Machine)],
+        Composer::EmitStatic => &[("val", Value), ("machine", Machine)],
+        Composer::Accept => &[("val_set_path", Sentence), ("machine", Machine)],
+        Composer::AcceptStatic => &[("val", Value), ("machine", Machine)],
+    }
+}
+
+fn builtin_source(composer: Composer) -> &'static str {
+    match composer {
+        Composer::Concurrent => include_str!("templates/compose_concurrent.tmpl.hana"),
+        Composer::Hidden => include_str!("templates/compose_hidden.tmpl.hana"),
+        Composer::Prefix => include_str!("templates/compose_prefix.tmpl.hana"),
+        Composer::RenamePrefix => include_str!("templates/compose_rename_prefix.tmpl.hana"),
+        Composer::StaticClosure => include_str!("templates/compose_static_closure.tmpl.hana"),
+        Composer::Done => include_str!("templates/compose_done.tmpl.hana"),
+        Composer::Emit => include_str!("templates/compose_emit.tmpl.hana"),
+        Composer::EmitStatic => include_str!("templates/compose_emit_static.tmpl.hana"),
+        Composer::Accept => include_str!("templates/compose_accept.tmpl.hana"),
+        Composer::AcceptStatic => include_str!("templates/compose_accept_static.tmpl.hana"),
+    }
+}
+
+/// A built-in composer's template, parsed the first time anything asks for it
+/// and shared from then on.
+///
+/// A template that does not parse is a bug in the compiler rather than in the
+/// program being compiled, so it panics, with the error rendered against the
+/// template's own text.
+fn builtin_items(composer: Composer) -> &'static [sugar::Item] {
+    static PARSED: [OnceLock<Vec<sugar::Item>>; Composer::ALL.len()] =
+        [const { OnceLock::new() }; Composer::ALL.len()];
+    PARSED[composer as usize].get_or_init(|| {
+        let mut map = crate::source::SourceMap::new();
+        let file = map.add(
+            format!("<{}>", composer.name()),
+            builtin_source(composer).to_string(),
+        );
+        crate::assembly::parse_source(&mut map, file, None).unwrap_or_else(|e| {
+            panic!(
+                "the `{}` template does not parse:\n{}",
+                composer.name(),
+                map.render(&e)
+            )
+        })
+    })
+}
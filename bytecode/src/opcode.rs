@@ -106,6 +106,26 @@ pub enum Instruction {
     /// Negate the numeric top value on the stack.
     Negate,
 
+    /// Pop two Ints and push the bits set in both.
+    BitAnd,
+    /// Pop two Ints and push the bits set in either.
+    BitOr,
+    /// Pop two Ints and push the bits set in exactly one of them.
+    BitXor,
+    /// Pop a shift amount and an Int, and push the Int multiplied by two that
+    /// many times, wrapping as `multiply` does: the bits shifted past the top
+    /// are lost, so a shift of 64 or more leaves `0`. A negative amount has no
+    /// shift to make, and answers `0` like any operand that is not an Int.
+    ShiftLeft,
+    /// Pop a shift amount and an Int, and push the Int divided by two that many
+    /// times, rounding down: the sign is kept, so a shift of 64 or more leaves
+    /// `0` for a non-negative Int and `-1` for a negative one. A negative
+    /// amount answers `0`, as for `shift_left`.
+    ShiftRight,
+    /// Pop an Int and push how many of its 64 bits are set, in two's
+    /// complement: `-1` has all of them.
+    Popcount,
+
     /// Call the sentence at the target.
     Jump(SentenceIndex),
     /// Hide the top value, call the sentence at the target, then restore the
@@ -117,10 +137,12 @@ pub enum Instruction {
     /// wrong. The hidden value is inaccessible to the callee, so it may be
     /// treated as unchanged across the call.
     ///
-    /// The two call instructions are the only ones, and [`Self::callee`] is how
-    /// a traversal reaches both — writing them as two variants is what makes
-    /// the hidden value part of the shape, and the accessor is what keeps a
-    /// walk from handling one and silently missing the other.
+    /// The two call instructions are the only ones that hand their target the
+    /// stack as it is, and [`Self::callee`] is how a traversal reaches both —
+    /// and the tuple walks, which call a target too. Writing them as separate
+    /// variants is what makes the hidden value part of the shape, and the
+    /// accessor is what keeps a walk from handling one and silently missing
+    /// another.
     Dip(SentenceIndex),
     /// Conditionally branch: if the top value on the stack is truthy, jump to the first SentenceIndex;
     /// otherwise, jump to the second SentenceIndex.
@@ -141,6 +163,60 @@ pub enum Instruction {
     /// ; as_tuple n ; equal` is the question, and it is the one the `type` sugar
     /// and `?` both write.
     Untuple(usize),
+    /// Pop a value and push element `i` of it, without taking the rest apart.
+    /// A value that is not a tuple with an element `i` has nothing to give,
+    /// and leaves `()`.
+    TupleGet(usize),
+    /// Pop a value and a tuple, and push the tuple with element `i` replaced by
+    /// the value. A tuple with no element `i`, or an operand that is no tuple,
+    /// has no slot to fill and leaves `()`.
+    TupleSet(usize),
+    /// Pop two tuples and push one holding the deeper one's elements followed
+    /// by the top one's. An operand that is no tuple leaves `()`.
+    TupleConcat,
+    /// Pop a tuple and push its elements from the first index up to but not
+    /// including the second. A range that is not inside the tuple leaves `()`,
+    /// as does a value that is no tuple: the range is not clamped, the same way
+    /// `const_string_slice`'s is not.
+    TupleSlice(usize, usize),
+    /// Pop a tuple and push a tuple as wide, each element replaced by what the
+    /// target leaves when called on it alone. The target must be `1 -> 1`. A
+    /// value that is no tuple has no elements to visit, and leaves `()`.
+    ///
+    /// This and [`TupleFold`][Instruction::TupleFold] are the loops recursion
+    /// is not needed for: the target runs once per element of a tuple already
+    /// on the stack, so a sentence's steps stay bounded by its text times the
+    /// size of the data it holds.
+    TupleMap(SentenceIndex),
+    /// Pop a tuple and an initial accumulator beneath it, and push the
+    /// accumulator after the target has been called on it and each element in
+    /// turn, element 0 first: `acc elem -> acc`, so the target must be
+    /// `2 -> 1`. A value that is no tuple has no elements to visit, and leaves
+    /// the accumulator as it was.
+    TupleFold(SentenceIndex),
+
+    /// Push the map with no entries.
+    MapEmpty,
+    /// Pop a value, a key and a map, and push the map with the key bound to
+    /// the value, in place of whatever it was bound to before. An operand that
+    /// is no map has no entries to add to, and leaves the empty map.
+    MapInsert,
+    /// Pop a key and a map, and push a result: `(value, ok)` where the map
+    /// binds the key, and `(key, err)` where it does not — which is every key,
+    /// for a value that is no map.
+    ///
+    /// The two values are the tags `ok` and `err`, in that order. Which
+    /// symbols those are is the program's to say, so the assembler resolves
+    /// `crate::prelude::ok` and `err` where `map_get` is written, as it does
+    /// for `?`, and the instruction carries what it found.
+    MapGet(Value, Value),
+    /// Pop a key and a map, and push the map without the key. A key the map
+    /// does not bind leaves it as it was; an operand that is no map leaves the
+    /// empty map.
+    MapRemove,
+    /// Pop a map and push a tuple of its keys, in the order the map keeps
+    /// them. A value that is no map has no keys, and leaves `()`.
+    MapKeys,
 
     /// Pop the top two values on the stack, evaluate logical AND on their truthiness, and push the result.
     And,
@@ -155,6 +231,27 @@ pub enum Instruction {
     /// range reads no code point, and answers `0` — as does an operand of the
     /// wrong type.
     ConstStringCharAt,
+    /// Pop two ConstStrings and push the deeper one followed by the top one.
+    /// An operand of the wrong type leaves the empty string.
+    ConstStringConcat,
+    /// Pop an end index, a start index and a ConstString, and push the
+    /// characters from start up to but not including end. Indices count
+    /// characters, as `const_string_char_at`'s do; a range that is not inside
+    /// the string, or runs backwards, has no characters to give and leaves the
+    /// empty string — as does an operand of the wrong type.
+    ConstStringSlice,
+    /// Pop a Unicode code point (Int) and push the one-character ConstString
+    /// it names, undoing `const_string_char_at`. An Int that is no code point,
+    /// or a value that is no Int, leaves the empty string.
+    ConstStringFromChar,
+    /// Pop an Int and push its decimal spelling as a ConstString: `-12` is
+    /// `"-12"`. Anything else has no digits to spell and leaves the empty
+    /// string.
+    IntToConstString,
+    /// Pop two ConstStrings and push whether the deeper one sorts before the
+    /// top one, comparing code point by code point. As with `less`, a pair
+    /// that is not two ConstStrings has no order and answers `false`.
+    ConstStringLess,
 
     /// Pop the top value and push true if it is an Int, else false.
     IsInt,
@@ -166,6 +263,8 @@ pub enum Instruction {
     IsSymbol,
     /// Pop the top value and push true if it is a Tuple, else false.
     IsTuple,
+    /// Pop the top value and push true if it is a Map, else false.
+    IsMap,
     /// Pop the top value and push its length as an Int: the element count of a
     /// Tuple, and `0` for anything else, which has no length to count.
     TupleLength,
@@ -197,22 +296,28 @@ impl Instruction {
     /// The sentence this calls, if it calls one.
     ///
     /// The two call instructions differ in whether a value is hidden, and in
-    /// nothing else. A traversal that asks this reaches both; one that matches
-    /// on [`Instruction::Jump`] alone silently walks past every `dip`, and
-    /// arity inference — which is what refuses recursion — is one of the walks
-    /// that must not.
+    /// nothing else. A traversal that asks this reaches both, and the target of
+    /// a `tuple_map` or `tuple_fold` too; one that matches on
+    /// [`Instruction::Jump`] alone silently walks past every `dip`, and arity
+    /// inference — which is what refuses recursion — is one of the walks that
+    /// must not.
     pub fn callee(&self) -> Option<SentenceIndex> {
         match self {
-            Instruction::Jump(s) | Instruction::Dip(s) => Some(*s),
+            Instruction::Jump(s)
+            | Instruction::Dip(s)
+            | Instruction::TupleMap(s)
+            | Instruction::TupleFold(s) => Some(*s),
             _ => None,
         }
     }
 
     /// How many values this hides from the sentence it calls.
     ///
-    /// `None` for anything that is not a call. `Some(0)` and `Some(1)` are the
-    /// only answers, which is the point: a frame is one value deep, and the
-    /// depth an old `Dip(n, _)` carried is now the number of these nested.
+    /// `None` for anything that is not a call, and for `tuple_map` and
+    /// `tuple_fold`, which call theirs on values they hand it rather than on
+    /// the stack they were given. `Some(0)` and `Some(1)` are the only
+    /// answers, which is the point: a frame is one value deep, and the depth an
+    /// old `Dip(n, _)` carried is now the number of these nested.
     pub fn hidden(&self) -> Option<usize> {
         match self {
             Instruction::Jump(_) => Some(0),
@@ -237,6 +342,9 @@ impl Instruction {
             self,
             Instruction::Add
                 | Instruction::Multiply
+                | Instruction::BitAnd
+                | Instruction::BitOr
+                | Instruction::BitXor
                 | Instruction::And
                 | Instruction::Or
                 | Instruction::Equal
@@ -281,11 +389,13 @@ impl Instruction {
                 | Instruction::Not
                 | Instruction::And
                 | Instruction::Or
+                | Instruction::ConstStringLess
                 | Instruction::IsInt
                 | Instruction::IsBool
                 | Instruction::IsConstString
                 | Instruction::IsSymbol
                 | Instruction::IsTuple
+                | Instruction::IsMap
                 // A coercion's whole point is its codomain, and this one's is
                 // `Bool`. The other two leave an Int and a Tuple, which is the
                 // same fact about a different type and has nowhere to be said.
@@ -312,20 +422,45 @@ impl std::fmt::Display for Instruction {
             Instruction::Modulo => write!(f, "modulo"),
             Instruction::Not => write!(f, "not"),
             Instruction::Negate => write!(f, "negate"),
+            Instruction::BitAnd => write!(f, "bit_and"),
+            Instruction::BitOr => write!(f, "bit_or"),
+            Instruction::BitXor => write!(f, "bit_xor"),
+            Instruction::ShiftLeft => write!(f, "shift_left"),
+            Instruction::ShiftRight => write!(f, "shift_right"),
+            Instruction::Popcount => write!(f, "popcount"),
             Instruction::Jump(s) => write!(f, "jump {:?}", s),
             Instruction::Dip(s) => write!(f, "dip {:?}", s),
             Instruction::Branch(t, e) => write!(f, "branch {:?} {:?}", t, e),
             Instruction::Tuple(n) => write!(f, "tuple {}", n),
             Instruction::Untuple(n) => write!(f, "untuple {}", n),
+            Instruction::TupleGet(i) => write!(f, "tuple_get {}", i),
+            Instruction::TupleSet(i) => write!(f, "tuple_set {}", i),
+            Instruction::TupleConcat => write!(f, "tuple_concat"),
+            Instruction::TupleSlice(start, end) => write!(f, "tuple_slice {} {}", start, end),
+            Instruction::TupleMap(s) => write!(f, "tuple_map {:?}", s),
+            Instruction::TupleFold(s) => write!(f, "tuple_fold {:?}", s),
+            Instruction::MapEmpty => write!(f, "map_empty"),
+            Instruction::MapInsert => write!(f, "map_insert"),
+            // The tags are the program's, and the same at every `map_get` in
+            // it, so the mnemonic is what the source wrote.
+            Instruction::MapGet(..) => write!(f, "map_get"),
+            Instruction::MapRemove => write!(f, "map_remove"),
+            Instruction::MapKeys => write!(f, "map_keys"),
             Instruction::And => write!(f, "and"),
             Instruction::Or => write!(f, "or"),
             Instruction::ConstStringLen => write!(f, "const_string_len"),
             Instruction::ConstStringCharAt => write!(f, "const_string_char_at"),
+            Instruction::ConstStringConcat => write!(f, "const_string_concat"),
+            Instruction::ConstStringSlice => write!(f, "const_string_slice"),
+            Instruction::ConstStringFromChar => write!(f, "const_string_from_char"),
+            Instruction::IntToConstString => write!(f, "int_to_const_string"),
+            Instruction::ConstStringLess => write!(f, "const_string_less"),
             Instruction::IsInt => write!(f, "is_int"),
             Instruction::IsBool => write!(f, "is_bool"),
             Instruction::IsConstString => write!(f, "is_const_string"),
             Instruction::IsSymbol => write!(f, "is_symbol"),
             Instruction::IsTuple => write!(f, "is_tuple"),
+            Instruction::IsMap => write!(f, "is_map"),
             Instruction::TupleLength => write!(f, "tuple_length"),
             Instruction::AsBool => write!(f, "as_bool"),
             Instruction::AsInt => write!(f, "as_int"),
@@ -50,12 +50,29 @@ pub struct ModuleId(usize);
 /// The crate root, which every [`ModuleTree`] has from the moment it is created.
 pub const ROOT: ModuleId = ModuleId(0);
 
+/// A type-safe index wrapper for a generic sentence, which becomes a
+/// [`SentenceIndex`] only once it is given arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, From, Into)]
+pub struct GenericIndex(usize);
+
+/// A type-safe index wrapper for a `const`, whose value phase 4 works out by
+/// running its body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, From, Into)]
+pub struct ConstIndex(usize);
+
 /// A single name binding in a module.
 #[derive(Debug, Clone)]
 pub enum ModuleItem {
     /// A declared value: a `symbol` or a `const_string`.
     Const(Value),
+    /// A `const`: a value too, but one that is not known until its body has
+    /// been compiled and run. [`ModuleTree::resolve`] refuses it; phase 4
+    /// evaluates it where it is used.
+    Computed(ConstIndex),
     Sentence(SentenceIndex),
+    /// A sentence with parameters. A path may only name it with arguments,
+    /// `name<f>`, and what that names is an instance of it.
+    Generic(GenericIndex),
     /// A stated equation. Here, in the one namespace a module has, so that
     /// `identity foo` collides with `sentence foo` and a fully qualified
     /// identity name therefore denotes exactly one thing.
@@ -65,12 +82,13 @@ pub enum ModuleItem {
 
 impl ModuleItem {
     /// How to refer to this kind of item in an error message.
-    fn describe(&self) -> &'static str {
+    pub fn describe(&self) -> &'static str {
         match self {
             ModuleItem::Const(Value::Symbol(_)) => "symbol",
             ModuleItem::Const(Value::ConstString(_)) => "const string",
-            ModuleItem::Const(_) => "constant",
+            ModuleItem::Const(_) | ModuleItem::Computed(_) => "constant",
             ModuleItem::Sentence(_) => "sentence",
+            ModuleItem::Generic(_) => "generic sentence",
             ModuleItem::Identity(_) => "identity",
             ModuleItem::Mod(_) => "module",
         }
@@ -248,6 +266,14 @@ impl ModuleTree {
         match self.resolve_entry(scope, path)? {
             ModuleItem::Const(val) => Ok(ResolvedItem::Const(val.clone())),
             ModuleItem::Sentence(idx) => Ok(ResolvedItem::Sentence(*idx)),
+            ModuleItem::Computed(_) => Err(format!(
+                "Path '{}' names a const, which has no value until it is evaluated",
+                path
+            )),
+            ModuleItem::Generic(_) => Err(format!(
+                "Path '{}' names a generic sentence, which needs arguments: `{}<...>`",
+                path, path
+            )),
             ModuleItem::Identity(_) => Err(format!(
                 "Path '{}' names an identity, which is not an item",
                 path
@@ -140,6 +140,8 @@ impl SourceMap {
     ///  6 |     add {
     ///    |         ^
     /// ```
+    ///
+    /// A note gets an excerpt of its own, under a `note:` line, before any help.
     pub fn render(&self, err: &Error) -> String {
         let Some(span) = err.span else {
             let mut out = format!("error: {}\n", err.message);
@@ -149,6 +151,21 @@ impl SourceMap {
             return out;
         };
 
+        let mut out = format!("error: {}\n", err.message);
+        let pad = self.excerpt(span, &mut out);
+        if let Some((note, note_span)) = &err.note {
+            out.push_str(&format!("note: {}\n", note));
+            self.excerpt(*note_span, &mut out);
+        }
+        if let Some(help) = &err.help {
+            out.push_str(&format!("{} = help: {}\n", pad, help));
+        }
+        out
+    }
+
+    /// Writes the `-->` line and the underlined source line for one span, and
+    /// returns the gutter padding it used.
+    fn excerpt(&self, span: Span, out: &mut String) -> String {
         let file = self.file(span.file);
         let (name, line_no, column) = self.locate(span);
         let line_idx = self.line_index(span);
@@ -167,7 +184,6 @@ impl SourceMap {
         let gutter = line_no.to_string();
         let pad = " ".repeat(gutter.len());
 
-        let mut out = format!("error: {}\n", err.message);
         out.push_str(&format!("{} --> {}:{}:{}\n", pad, name, line_no, column));
         out.push_str(&format!("{} |\n", pad));
         out.push_str(&format!("{} | {}\n", gutter, line));
@@ -177,10 +193,7 @@ impl SourceMap {
             " ".repeat(column - 1),
             "^".repeat(width)
         ));
-        if let Some(help) = &err.help {
-            out.push_str(&format!("{} = help: {}\n", pad, help));
-        }
-        out
+        pad
     }
 }
 
@@ -190,6 +203,9 @@ pub struct Error {
     pub message: String,
     pub span: Option<Span>,
     pub help: Option<String>,
+    /// A second place the error concerns, with what it is: for an error in
+    /// code a template generated, the template it came from.
+    pub note: Option<(String, Span)>,
 }
 
 impl Error {
@@ -199,6 +215,7 @@ impl Error {
             message: message.into(),
             span: Some(span),
             help: None,
+            note: None,
         }
     }
 
@@ -208,6 +225,7 @@ impl Error {
             message: message.into(),
             span: None,
             help: None,
+            note: None,
         }
     }
 
@@ -215,6 +233,11 @@ impl Error {
         self.help = Some(help.into());
         self
     }
+
+    pub fn with_note(mut self, note: impl Into<String>, span: Span) -> Self {
+        self.note = Some((note.into(), span));
+        self
+    }
 }
 
 impl From<String> for Error {
@@ -234,6 +257,9 @@ impl std::fmt::Display for Error {
     /// [`SourceMap::render`] instead, which adds the location and the snippet.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.message)?;
+        if let Some((note, _)) = &self.note {
+            write!(f, "\nnote: {}", note)?;
+        }
         if let Some(help) = &self.help {
             write!(f, "\nhelp: {}", help)?;
         }
@@ -315,6 +341,28 @@ mod tests {
         assert!(map.render(&err).ends_with("= help: try `abd`\n"));
     }
 
+    #[test]
+    fn renders_a_note_against_its_own_line() {
+        let mut map = SourceMap::new();
+        let f = map.add("main.hana", "abc\ndef\n".to_string());
+        let err = Error::at("nope", Span::new(f, 4, 7)).with_note("from here", Span::new(f, 0, 3));
+        assert_eq!(
+            map.render(&err),
+            concat!(
+                "error: nope\n",
+                "  --> main.hana:2:1\n",
+                "  |\n",
+                "2 | def\n",
+                "  | ^^^\n",
+                "note: from here\n",
+                "  --> main.hana:1:1\n",
+                "  |\n",
+                "1 | abc\n",
+                "  | ^^^\n",
+            )
+        );
+    }
+
     #[test]
     fn renders_a_spanless_error() {
         let map = SourceMap::new();
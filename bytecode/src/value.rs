@@ -1,4 +1,7 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 
 /// A unique identity, and nothing else.
 ///
@@ -23,6 +26,13 @@ impl PartialEq for Symbol {
     }
 }
 
+/// By `id` alone, as equality goes.
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
 /// Represents any value that can be operated on or stored by the VM.
 ///
 /// Equality here is structural **identity**: two values compare equal exactly
@@ -30,7 +40,7 @@ impl PartialEq for Symbol {
 /// derivable, and it is what [`crate::opcode::Instruction::Equal`] means.
 /// Floats were the one exception — `0.0 == -0.0` on two values that stay
 /// distinguishable — and they are gone.
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum Value {
     /// A boolean value (true or false).
     Bool(bool),
@@ -53,6 +63,14 @@ pub enum Value {
     Tuple(Vec<Value>),
     /// A unique symbol value.
     Symbol(Symbol),
+    /// A finite map from keys to values, any value being either.
+    ///
+    /// Like every other value it is immutable: `map_insert` and `map_remove`
+    /// answer a new map and leave the one they were handed as it was. The
+    /// entries are kept in [`Value`]'s order, so two maps holding the same
+    /// entries are one value however they were built, print the same, and
+    /// hand `map_keys` the same tuple.
+    Map(BTreeMap<Value, Value>),
 }
 
 impl Value {
@@ -88,6 +106,46 @@ impl Value {
     }
 }
 
+/// A total order on values, consistent with their equality: by kind first —
+/// bools, ints, const strings, tuples, symbols, then maps — and then by
+/// content, symbols by `id`, and tuples and maps by size before their
+/// elements.
+///
+/// This is the order a [`Value::Map`] keeps its keys in, and the one the
+/// prover sorts literals by. It is not what `less` answers: that is
+/// [`numeric_cmp`], and only numbers have it.
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        fn rank(v: &Value) -> u8 {
+            match v {
+                Value::Bool(_) => 0,
+                Value::Int(_) => 1,
+                Value::ConstString(_) => 2,
+                Value::Tuple(_) => 3,
+                Value::Symbol(_) => 4,
+                Value::Map(_) => 5,
+            }
+        }
+        rank(self)
+            .cmp(&rank(other))
+            .then_with(|| match (self, other) {
+                (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+                (Value::Int(x), Value::Int(y)) => x.cmp(y),
+                (Value::ConstString(x), Value::ConstString(y)) => x.cmp(y),
+                (Value::Tuple(x), Value::Tuple(y)) => x.len().cmp(&y.len()).then_with(|| x.cmp(y)),
+                (Value::Symbol(x), Value::Symbol(y)) => x.id.cmp(&y.id),
+                (Value::Map(x), Value::Map(y)) => x.len().cmp(&y.len()).then_with(|| x.cmp(y)),
+                _ => unreachable!("ranks matched"),
+            })
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// Compares two values numerically.
 ///
 /// `None` says there is no ordering, which since `Int` is the only number is
@@ -125,6 +183,17 @@ impl fmt::Display for Value {
             // declared: `crate::io::stdout::putch` prints as `io::stdout::putch`,
             // the same fully qualified name the library keys it under.
             Value::Symbol(sym) => write!(f, "{}", sym.path),
+            // In key order, which is what makes two equal maps print alike.
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in entries.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
         }
     }
 }
@@ -20,7 +20,8 @@
 use std::collections::HashMap;
 use std::path::Path;
 
-use bytecode::{IdentityIndex, Library, SourceMap, assemble_source};
+use bytecode::{IdentityIndex, Library, SourceMap};
+use vm::assemble_source;
 
 use crate::hant::{Body, ProofEntry, Step, Strategy, parse_hant};
 use crate::parse::{Scope, parse_term, sentence_named};
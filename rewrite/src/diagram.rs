@@ -44,11 +44,15 @@
 //! itself ([`run_window`], so there is no second semantics), a literal
 //! condition takes its arm (`fold-branch`), a retested condition is decided
 //! (`retest`), commutative operands sort (`commute`), `tuple n ; untuple n`
-//! cancels (`tuple-cancel`), `untuple n ; tuple n` reads back as the
+//! cancels (`tuple-cancel`), `tuple_get`, `tuple_set`, `tuple_slice`,
+//! `tuple_concat` and `as_tuple n` on a built tuple read its wires
+//! (`tuple-index`), `untuple n ; tuple n` reads back as the
 //! coercion (`untuple-retuple`), coercions idempote (`as-*-idem`), a test
 //! of a `yields_bool` answer is `true` (`bool-result`), and a zero-output
-//! computation vanishes (`drop-nat` at the codomain). Calls stay opaque:
-//! `inline` remains the step that spends a definition.
+//! computation vanishes (`drop-nat` at the codomain). A `tuple_map` or
+//! `tuple_fold` over a built or literal tuple becomes one call of its body
+//! per element (`walk-unroll`), which is also how two maps fuse. Calls stay
+//! opaque: `inline` remains the step that spends a definition.
 //!
 //! **Trust.** Nothing here produces a derivation yet. This module *is* the
 //! prover's judge of equality — the `diagram` step normalizes both sides of
@@ -66,7 +70,7 @@ use std::hash::{Hash, Hasher};
 
 use bytecode::{Instruction, Library, SentenceIndex, Value};
 
-use crate::term::{Arity, Context, Prim, Term, TermIndex};
+use crate::term::{Arity, Context, Prim, Term, TermIndex, Walk};
 
 // ---- the arena ----------------------------------------------------------------
 
@@ -367,18 +371,35 @@ fn hash_value<H: Hasher>(v: &Value, state: &mut H) {
             }
         }
         Value::Symbol(s) => s.id.hash(state),
+        Value::Map(entries) => {
+            entries.len().hash(state);
+            for (k, v) in entries {
+                hash_value(k, state);
+                hash_value(v, state);
+            }
+        }
     }
 }
 
-/// An operation a box can be: a primitive, or a call left opaque.
+/// An operation a box can be: a primitive, or a call or tuple walk left
+/// opaque.
 ///
 /// `push` and `swap` never appear — the first is a [`ValNode::Lit`], the
 /// second is wiring — and a call carries its arity for the same reason
-/// [`Term::Call`] does: the diagram stays meaningful without a library.
+/// [`Term::Call`] does: the diagram stays meaningful without a library. A
+/// walk carries its body's, which is what unrolling it needs.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Op {
     Prim(Prim),
-    Call { target: SentenceIndex, arity: Arity },
+    Call {
+        target: SentenceIndex,
+        arity: Arity,
+    },
+    Walk {
+        walk: Walk,
+        target: SentenceIndex,
+        body: Arity,
+    },
 }
 
 impl Op {
@@ -386,6 +407,7 @@ impl Op {
         match self {
             Op::Prim(p) => p.arity(),
             Op::Call { arity, .. } => *arity,
+            Op::Walk { walk, .. } => walk.arity(),
         }
     }
 }
@@ -397,10 +419,19 @@ impl Hash for Op {
                 0u8.hash(state);
                 std::mem::discriminant(p).hash(state);
                 match p {
-                    Prim::Tuple(n) | Prim::Untuple(n) | Prim::AsTuple(n) => n.hash(state),
+                    Prim::Tuple(n)
+                    | Prim::Untuple(n)
+                    | Prim::AsTuple(n)
+                    | Prim::TupleGet(n)
+                    | Prim::TupleSet(n) => n.hash(state),
+                    Prim::TupleSlice(start, end) => (start, end).hash(state),
                     // `push` is a Lit, never an Op; hash it anyway so the
                     // impl is total.
                     Prim::Push(v) => hash_value(v, state),
+                    Prim::MapGet(ok, err) => {
+                        hash_value(ok, state);
+                        hash_value(err, state);
+                    }
                     _ => {}
                 }
             }
@@ -410,6 +441,12 @@ impl Hash for Op {
                 arity.inputs.hash(state);
                 arity.outputs.hash(state);
             }
+            Op::Walk { walk, target, body } => {
+                2u8.hash(state);
+                walk.hash(state);
+                usize::from(*target).hash(state);
+                body.hash(state);
+            }
         }
     }
 }
@@ -474,6 +511,18 @@ fn eval(
             );
             ctx.leaf(out)
         }
+        Term::Walk { walk, target, body } => {
+            let out = apply(
+                ctx,
+                &Op::Walk {
+                    walk: *walk,
+                    target: *target,
+                    body: *body,
+                },
+                stack,
+            );
+            ctx.leaf(out)
+        }
         Term::Compose(a, b) => {
             let (a, b) = (*a, *b);
             let first = eval(ctx, terms, a, stack, path);
@@ -636,6 +685,14 @@ fn apply(ctx: &mut Ctx, op: &Op, args: Vec<ValId>) -> Vec<ValId> {
         return Vec::new();
     }
 
+    // `walk-unroll`: a walk over a tuple whose elements are known is its
+    // body, called once per element.
+    if let Op::Walk { walk, target, body } = op
+        && let Some(unrolled) = unroll(ctx, *walk, *target, *body, &args)
+    {
+        return unrolled;
+    }
+
     if let Op::Prim(prim) = op {
         // `eval`: an all-literal window runs on the machine itself, junk
         // semantics included, so folding cannot disagree with `vm`.
@@ -664,6 +721,12 @@ fn apply(ctx: &mut Ctx, op: &Op, args: Vec<ValId>) -> Vec<ValId> {
             return elems.clone();
         }
 
+        // `tuple-index`: the whole-tuple operations on what `tuple n` just
+        // built, answered from its wires without building it.
+        if let Some(folded) = tuple_index(ctx, prim, &args) {
+            return folded;
+        }
+
         // `untuple-retuple`: rebuilding what `untuple n` took apart is the
         // coercion, not the identity — the slots may have been junk-filled.
         if let Prim::Tuple(n) = prim
@@ -703,6 +766,121 @@ fn apply(ctx: &mut Ctx, op: &Op, args: Vec<ValId>) -> Vec<ValId> {
     build(ctx, op, args, outputs)
 }
 
+/// `tuple_get`, `tuple_set`, `tuple_concat`, `tuple_slice` and `as_tuple n`
+/// on tuples whose elements are wires: the element, the tuple rebuilt, or the
+/// `()` the machine leaves for an index the width rules out.
+fn tuple_index(ctx: &mut Ctx, prim: &Prim, args: &[ValId]) -> Option<Vec<ValId>> {
+    let built = |ctx: &Ctx, arg: ValId| match ctx.val(arg) {
+        ValNode::App {
+            op: Op::Prim(Prim::Tuple(_)),
+            args: elems,
+        } => Some(elems.clone()),
+        _ => None,
+    };
+    let retuple = |ctx: &mut Ctx, elems: Vec<ValId>| {
+        let n = elems.len();
+        apply(ctx, &Op::Prim(Prim::Tuple(n)), elems)
+    };
+    let unit = |ctx: &mut Ctx| vec![ctx.lit(Value::unit())];
+    Some(match prim {
+        Prim::TupleGet(i) => {
+            let elems = built(ctx, args[0])?;
+            match elems.get(*i) {
+                Some(&elem) => vec![elem],
+                None => unit(ctx),
+            }
+        }
+        Prim::TupleSet(i) => {
+            let mut elems = built(ctx, args[0])?;
+            if *i >= elems.len() {
+                return Some(unit(ctx));
+            }
+            elems[*i] = args[1];
+            retuple(ctx, elems)
+        }
+        Prim::TupleSlice(start, end) => {
+            let elems = built(ctx, args[0])?;
+            if start > end || *end > elems.len() {
+                return Some(unit(ctx));
+            }
+            retuple(ctx, elems[*start..*end].to_vec())
+        }
+        Prim::TupleConcat => {
+            let mut elems = built(ctx, args[0])?;
+            elems.extend(built(ctx, args[1])?);
+            retuple(ctx, elems)
+        }
+        // A tuple of the width asked for is already what the coercion would
+        // have made it.
+        Prim::AsTuple(n) => match built(ctx, args[0])? {
+            elems if elems.len() == *n => vec![args[0]],
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// `tuple_map` and `tuple_fold` on a tuple that was built, or is a literal:
+/// the body called on each element in turn, exactly as the machine steps
+/// through them. A literal that is no tuple has no elements, and leaves what
+/// the machine leaves for one.
+///
+/// The body stays a call. What this buys is the element-wise reading, so that
+/// a walk over a built tuple normalizes like the calls written out — and two
+/// walks in a row, the second over what the first built, fuse into one pass
+/// without a fusion law to state.
+fn unroll(
+    ctx: &mut Ctx,
+    walk: Walk,
+    target: SentenceIndex,
+    body: Arity,
+    args: &[ValId],
+) -> Option<Vec<ValId>> {
+    let tuple = *args.last().expect("a walk reads a tuple");
+    let elems = match ctx.val(tuple) {
+        ValNode::App {
+            op: Op::Prim(Prim::Tuple(_)),
+            args: elems,
+        } => elems.clone(),
+        ValNode::Lit(HVal(Value::Tuple(values))) => {
+            let values = values.clone();
+            values.into_iter().map(|v| ctx.lit(v)).collect()
+        }
+        ValNode::Lit(_) => Vec::new(),
+        _ => return None,
+    };
+    let call = Op::Call {
+        target,
+        arity: body,
+    };
+    // One step is the body on the top of `[accumulator, element]`, with
+    // whatever it does not take passing underneath — which for a narrow body
+    // is the element or the accumulator, unchanged.
+    let step = |ctx: &mut Ctx, mut stack: Vec<ValId>| {
+        let taken = stack.split_off(stack.len() - body.inputs);
+        stack.extend(apply(ctx, &call, taken));
+        debug_assert_eq!(
+            stack.len(),
+            1,
+            "the arity checker held the body to one value"
+        );
+        stack[0]
+    };
+    Some(match walk {
+        Walk::Map => {
+            let out: Vec<ValId> = elems.into_iter().map(|e| step(ctx, vec![e])).collect();
+            let n = out.len();
+            apply(ctx, &Op::Prim(Prim::Tuple(n)), out)
+        }
+        Walk::Fold => {
+            let acc = elems
+                .into_iter()
+                .fold(args[0], |acc, e| step(ctx, vec![acc, e]));
+            vec![acc]
+        }
+    })
+}
+
 /// The box nothing folded: an `App` wire, or one `Proj` wire per output.
 fn build(ctx: &mut Ctx, op: &Op, args: Vec<ValId>, outputs: usize) -> Vec<ValId> {
     if outputs == 1 {
@@ -784,7 +962,7 @@ pub fn cmp_val(ctx: &Ctx, a: ValId, b: ValId) -> Ordering {
     let (na, nb) = (ctx.val(a), ctx.val(b));
     rank(na).cmp(&rank(nb)).then_with(|| match (na, nb) {
         (ValNode::Var(i), ValNode::Var(j)) => i.cmp(j),
-        (ValNode::Lit(HVal(v)), ValNode::Lit(HVal(w))) => cmp_value(v, w),
+        (ValNode::Lit(HVal(v)), ValNode::Lit(HVal(w))) => v.cmp(w),
         (ValNode::App { op, args }, ValNode::App { op: oq, args: brgs }) => {
             cmp_op(op, oq).then_with(|| cmp_args(ctx, args, brgs))
         }
@@ -816,12 +994,16 @@ fn cmp_op(a: &Op, b: &Op) -> Ordering {
     match (a, b) {
         (Op::Prim(p), Op::Prim(q)) => {
             // Distinct prims print distinct mnemonics (widths included), and
-            // `push` never becomes an `Op`, so the spelling is a faithful key.
+            // `push` never becomes an `Op`, so the spelling is a faithful key
+            // — but for the tags a `map_get` carries, which it does not print.
             debug_assert!(!matches!(p, Prim::Push(_)));
-            p.to_string().cmp(&q.to_string())
+            p.to_string()
+                .cmp(&q.to_string())
+                .then_with(|| match (p, q) {
+                    (Prim::MapGet(ok, err), Prim::MapGet(ok2, err2)) => (ok, err).cmp(&(ok2, err2)),
+                    _ => Ordering::Equal,
+                })
         }
-        (Op::Prim(_), Op::Call { .. }) => Ordering::Less,
-        (Op::Call { .. }, Op::Prim(_)) => Ordering::Greater,
         (
             Op::Call { target, arity },
             Op::Call {
@@ -831,35 +1013,30 @@ fn cmp_op(a: &Op, b: &Op) -> Ordering {
         ) => usize::from(*target)
             .cmp(&usize::from(*t2))
             .then_with(|| (arity.inputs, arity.outputs).cmp(&(a2.inputs, a2.outputs))),
-    }
-}
-
-/// A total order on the machine's values, consistent with [`Value`]'s
-/// equality: symbols by `id`, everything else by content.
-fn cmp_value(a: &Value, b: &Value) -> Ordering {
-    fn rank(v: &Value) -> u8 {
-        match v {
-            Value::Bool(_) => 0,
-            Value::Int(_) => 1,
-            Value::ConstString(_) => 2,
-            Value::Tuple(_) => 3,
-            Value::Symbol(_) => 4,
+        (
+            Op::Walk { walk, target, body },
+            Op::Walk {
+                walk: w2,
+                target: t2,
+                body: b2,
+            },
+        ) => (walk, usize::from(*target), body.inputs, body.outputs).cmp(&(
+            w2,
+            usize::from(*t2),
+            b2.inputs,
+            b2.outputs,
+        )),
+        _ => {
+            fn rank(op: &Op) -> u8 {
+                match op {
+                    Op::Prim(_) => 0,
+                    Op::Call { .. } => 1,
+                    Op::Walk { .. } => 2,
+                }
+            }
+            rank(a).cmp(&rank(b))
         }
     }
-    rank(a).cmp(&rank(b)).then_with(|| match (a, b) {
-        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
-        (Value::Int(x), Value::Int(y)) => x.cmp(y),
-        (Value::ConstString(x), Value::ConstString(y)) => x.cmp(y),
-        (Value::Tuple(x), Value::Tuple(y)) => x.len().cmp(&y.len()).then_with(|| {
-            x.iter()
-                .zip(y)
-                .map(|(v, w)| cmp_value(v, w))
-                .find(|o| *o != Ordering::Equal)
-                .unwrap_or(Ordering::Equal)
-        }),
-        (Value::Symbol(x), Value::Symbol(y)) => x.id.cmp(&y.id),
-        _ => unreachable!("ranks matched"),
-    })
 }
 
 // ---- the machine, consulted ----------------------------------------------------
@@ -1079,6 +1256,9 @@ fn op_term(terms: &mut Context, op: &Op) -> TermIndex {
     match op {
         Op::Prim(p) => terms.op(p.clone()),
         Op::Call { target, arity } => terms.call(*target, *arity),
+        Op::Walk { walk, target, body } => terms
+            .walk(*walk, *target, *body)
+            .expect("the body was checked when the term was built"),
     }
 }
 
@@ -1106,6 +1286,7 @@ fn write_app(f: &mut fmt::Formatter<'_>, ctx: &Ctx, op: &Op, args: &[ValId]) ->
     match op {
         Op::Prim(p) => write!(f, "{}", p)?,
         Op::Call { target, .. } => write!(f, "call#{}", usize::from(*target))?,
+        Op::Walk { walk, target, .. } => write!(f, "{}#{}", walk.mnemonic(), usize::from(*target))?,
     }
     write!(f, "(")?;
     for (i, &a) in args.iter().enumerate() {
@@ -1232,6 +1413,83 @@ mod tests {
         agree("push 1 push 2 add", "push 3");
         // Junk semantics included.
         agree("push true push 2 add", "push 0");
+        // The string builders too: a string computed from literals is one.
+        agree(
+            r#"push "ab" push 12 int_to_const_string const_string_concat"#,
+            r#"push "ab12""#,
+        );
+        agree(
+            r#"push "hello" push 1 push 9 const_string_slice"#,
+            r#"push """#,
+        );
+    }
+
+    #[test]
+    fn whole_tuple_operations_read_the_wires_they_were_built_from() {
+        agree("tuple 3 tuple_get 1", "drop 0 drop 1");
+        agree("tuple 2 tuple_get 2", "drop 0 drop 0 push ()");
+        agree("roll 1 tuple 2 roll 1 tuple_set 0", "drop 0 tuple 2");
+        agree("tuple 3 tuple_slice 1 3", "tuple 2 roll 1 drop 0");
+        agree(
+            "tuple 1 roll 2 roll 2 tuple 2 tuple_concat",
+            "roll 2 roll 2 tuple 3",
+        );
+        agree("tuple 2 as_tuple 2", "tuple 2");
+        // Nothing is known of a tuple that was not built here.
+        differ("tuple_get 0", "untuple 1");
+    }
+
+    #[test]
+    fn a_walk_over_a_built_tuple_is_its_body_per_element() {
+        // Built by hand, since both sides have to name one body.
+        let mut terms = Context::new();
+        let f = SentenceIndex::from(5);
+        let g = SentenceIndex::from(6);
+        let one = Arity::new(1, 1);
+        let same = |terms: &Context, a: TermIndex, b: TermIndex| {
+            let mut ctx = Ctx::default();
+            normalize(&mut ctx, terms, a) == normalize(&mut ctx, terms, b)
+        };
+
+        let pair = terms.op(Prim::Tuple(2));
+        let map_f = terms.walk(Walk::Map, f, one).unwrap();
+        let map_g = terms.walk(Walk::Map, g, one).unwrap();
+        let (call_f, call_g) = (terms.call(f, one), terms.call(g, one));
+
+        // `tuple 2 ; tuple_map f` = `f * f ; tuple 2`.
+        let walked = terms.compose(pair, map_f).unwrap();
+        let each = terms.par(call_f, call_f);
+        let written = terms.compose(each, pair).unwrap();
+        assert!(same(&terms, walked, written));
+
+        // Two maps in a row fuse, with no law to say so: the second walks
+        // what the first built.
+        let twice = terms.compose(walked, map_g).unwrap();
+        let both = terms.compose(call_f, call_g).unwrap();
+        let each = terms.par(both, both);
+        let fused = terms.compose(each, pair).unwrap();
+        assert!(same(&terms, twice, fused));
+
+        // A fold is the body on the accumulator and each element in turn.
+        let fold = terms.walk(Walk::Fold, f, Arity::new(2, 1)).unwrap();
+        let under = terms.under(pair, 1);
+        let walked = terms.compose(under, fold).unwrap();
+        let call = terms.call(f, Arity::new(2, 1));
+        let id = terms.id(1);
+        let first = terms.par(call, id);
+        let written = terms.compose(first, call).unwrap();
+        assert!(same(&terms, walked, written));
+
+        // A body that takes nothing leaves each element where it was.
+        let nothing = terms.walk(Walk::Map, f, Arity::new(0, 0)).unwrap();
+        let walked = terms.compose(pair, nothing).unwrap();
+        assert!(same(&terms, walked, pair));
+
+        // Nothing is known of a walk over a tuple that was not built here,
+        // beyond which body it walks.
+        assert!(!same(&terms, map_f, map_g));
+        let again = terms.walk(Walk::Map, f, one).unwrap();
+        assert!(same(&terms, map_f, again));
     }
 
     #[test]
@@ -1498,7 +1756,7 @@ mod tests {
         let text = std::fs::read_to_string(tests.join("main.hana")).unwrap();
         let mut map = bytecode::SourceMap::new();
         let file = map.add("main.hana", text);
-        let library = bytecode::assemble_source(&mut map, file, Some(&tests))
+        let library = vm::assemble_source(&mut map, file, Some(&tests))
             .unwrap_or_else(|e| panic!("{}", map.render(&e)));
 
         let mut arena = Context::new();
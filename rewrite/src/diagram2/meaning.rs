@@ -151,6 +151,9 @@ pub(super) fn eval_term(
             Some(name) => m.apply(name, stack, prim.arity().outputs),
         },
         Term::Call { target, arity } => m.apply(format!("call {:?}", target), stack, arity.outputs),
+        Term::Walk { walk, target, .. } => {
+            m.apply(format!("{} {:?}", walk.mnemonic(), target), stack, 1)
+        }
         // Both spines are walked rather than recursed down. A read-back
         // emits a step per box and folds them left, and a routing layer
         // is a `*`-product over the whole width, so these chains are as
@@ -238,6 +241,9 @@ pub(super) fn eval_graph(m: &mut Meaning, graph: &Graph, inputs: &[SymId]) -> Ve
             NodeKind::Call { target, arity } => {
                 m.apply(format!("call {:?}", target), args, arity.outputs)
             }
+            NodeKind::Walk { walk, target, .. } => {
+                m.apply(format!("{} {:?}", walk.mnemonic(), target), args, 1)
+            }
             // A fork means what a copy means; only rewriting tells them
             // apart. Its condition is read and not used — a rule wants to
             // see it, the value does not depend on it.
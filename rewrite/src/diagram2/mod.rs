@@ -142,7 +142,7 @@ use std::fmt;
 
 use bytecode::SentenceIndex;
 
-use crate::term::{Arity, Context, Prim, Term, TermIndex};
+use crate::term::{Arity, Context, Prim, Term, TermIndex, Walk};
 
 #[cfg(test)]
 mod meaning;
@@ -245,6 +245,13 @@ pub enum NodeKind {
     /// A sentence called by name, left unopened; the arity is carried for
     /// the same reason [`Term::Call`] carries it.
     Call { target: SentenceIndex, arity: Arity },
+    /// A tuple walk, as opaque as a call: the body is named, not held, so no
+    /// rule reaches into it.
+    Walk {
+        walk: Walk,
+        target: SentenceIndex,
+        body: Arity,
+    },
     /// `fork(n)`: the two views of the stack a branch's arms get.
     ///
     /// **Input 0 is the condition**, inputs `1..=n` the stack; `2n` out,
@@ -299,6 +306,7 @@ impl NodeKind {
             NodeKind::Drop(n) => Arity::new(*n, 0),
             NodeKind::Op(prim) => prim.arity(),
             NodeKind::Call { arity, .. } => *arity,
+            NodeKind::Walk { walk, .. } => walk.arity(),
             NodeKind::Fork { arity, .. } => Arity::new(arity + 1, 2 * arity),
             NodeKind::Select { arity, .. } => Arity::new(2 * arity + 1, *arity),
         }
@@ -527,6 +535,14 @@ fn emit(graph: &mut Graph, terms: &Context, term: TermIndex, inputs: Vec<Source>
             },
             inputs,
         ),
+        Term::Walk { walk, target, body } => graph.add(
+            NodeKind::Walk {
+                walk: *walk,
+                target: *target,
+                body: *body,
+            },
+            inputs,
+        ),
         // `;` is not a node: sequencing is one box's output port being
         // another's input.
         Term::Compose(first, then) => {
@@ -927,6 +943,9 @@ fn box_term(terms: &mut Context, kind: &NodeKind) -> TermIndex {
         NodeKind::Drop(n) => terms.drop(*n),
         NodeKind::Op(prim) => terms.op(prim.clone()),
         NodeKind::Call { target, arity } => terms.call(*target, *arity),
+        NodeKind::Walk { walk, target, body } => terms
+            .walk(*walk, *target, *body)
+            .expect("the body was checked when the term was built"),
         // The two views of the stack are what a `copy` makes; the node is
         // only distinct so that rewriting leaves it alone. Its condition
         // computes nothing — it is read so that a rule can see it — so what
@@ -1175,6 +1194,9 @@ impl fmt::Display for NodeKind {
             NodeKind::Drop(n) => write!(f, "drop({})", n),
             NodeKind::Op(prim) => write!(f, "{}", prim),
             NodeKind::Call { target, .. } => write!(f, "call #{}", usize::from(*target)),
+            NodeKind::Walk { walk, target, .. } => {
+                write!(f, "{} #{}", walk.mnemonic(), usize::from(*target))
+            }
             NodeKind::Fork { arity, branch } => write!(f, "fork({}){}", arity, branch),
             NodeKind::Select { arity, branch } => write!(f, "select({}){}", arity, branch),
         }
@@ -1259,7 +1281,7 @@ mod tests {
         let text = std::fs::read_to_string(tests.join("main.hana")).unwrap();
         let mut map = bytecode::SourceMap::new();
         let file = map.add("main.hana", text);
-        let library = bytecode::assemble_source(&mut map, file, Some(&tests))
+        let library = vm::assemble_source(&mut map, file, Some(&tests))
             .unwrap_or_else(|e| panic!("{}", map.render(&e)));
         let mut arena = Context::new();
         let lowered = crate::term::lower_all(&mut arena, &library).unwrap();
@@ -24,7 +24,7 @@
 
 use bytecode::{Library, SentenceIndex, Value};
 
-use crate::term::{Context, Prim, TermIndex};
+use crate::term::{Context, Prim, TermIndex, Walk};
 
 /// What names a body may use: the library it is written against.
 pub struct Scope<'l> {
@@ -122,10 +122,44 @@ fn atom<'t>(
                 crate::term::call_arity(scope.library, target).map_err(|e| e.to_string())?;
             Ok((ctx.call(target, arity), rest))
         }
+        "tuple_map" | "tuple_fold" => {
+            let walk = if head == "tuple_map" {
+                Walk::Map
+            } else {
+                Walk::Fold
+            };
+            let (path, rest) = word(rest.trim_start());
+            let target = sentence_named(scope.library, path)?;
+            let body = crate::term::call_arity(scope.library, target).map_err(|e| e.to_string())?;
+            Ok((
+                ctx.walk(walk, target, body).map_err(|e| e.to_string())?,
+                rest,
+            ))
+        }
         "push" => {
             let (value, rest) = literal(rest, scope)?;
             Ok((ctx.op(Prim::Push(value)), rest))
         }
+        "map_get" => {
+            // The tags are the program's, so they come out of the library
+            // rather than the text, as the assembler takes them out of the
+            // module tree.
+            let tag = |name: &str| {
+                scope
+                    .library
+                    .symbols
+                    .get(&format!("prelude::{}", name))
+                    .cloned()
+                    .ok_or_else(|| {
+                        format!(
+                            "`map_get` answers with `crate::prelude::{}`, which this library \
+                             does not declare",
+                            name
+                        )
+                    })
+            };
+            Ok((ctx.op(Prim::MapGet(tag("ok")?, tag("err")?)), rest))
+        }
         mnemonic => {
             // Everything else is an instruction, spelled the way the machine
             // spells it. The three that carry a width read theirs here; the
@@ -372,6 +406,24 @@ mod tests {
         assert!(err.contains("no symbol"), "{}", err);
     }
 
+    #[test]
+    fn a_map_get_answers_with_the_librarys_tags() {
+        let library = assemble("mod prelude { symbol ok symbol err }").unwrap();
+        let mut ctx = Context::new();
+        let term = parse_term(&mut ctx, "map_get", &Scope::new(&library)).unwrap();
+        let Term::Op(Prim::MapGet(ok, err)) = ctx.get(term) else {
+            panic!("{}", ctx.display(term))
+        };
+        assert_eq!(ok, &library.symbols["prelude::ok"]);
+        assert_eq!(err, &library.symbols["prelude::err"]);
+        assert_eq!(
+            round_trip(&library, "map_empty * push 1 ; map_get"),
+            "map_empty * push 1 ; map_get"
+        );
+        let err = parse_term(&mut ctx, "map_get", &Scope::new(&Library::new())).unwrap_err();
+        assert!(err.contains("crate::prelude::ok"), "{}", err);
+    }
+
     #[test]
     fn halves_that_do_not_meet_are_refused_rather_than_padded() {
         let library = Library::new();
@@ -920,6 +920,8 @@ mod tests {
                 "identities::comparing_two_built_tuples",
                 "identities::untupling_and_retupling_is_the_coercion",
                 "identities::specializing_a_tested_value",
+                "identities::masking_either_way_round",
+                "identities::a_mask_of_literals_folds",
             ],
             "calls-opaque: the diagram's reach changed"
         );
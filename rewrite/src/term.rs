@@ -69,7 +69,7 @@ impl fmt::Display for Arity {
     }
 }
 
-/// An instruction a term can hold: every [`Instruction`] but the five the model
+/// An instruction a term can hold: every [`Instruction`] but the seven the model
 /// says structurally instead.
 ///
 /// Each exclusion is a thing the algebra already expresses, and keeping both
@@ -81,6 +81,7 @@ impl fmt::Display for Arity {
 /// | `jump` | [`Term::Call`] |
 /// | `dip` | a [`Par`][Term::Par] against `id(1)` |
 /// | `branch` | [`Term::Branch`] |
+/// | `tuple_map`, `tuple_fold` | [`Term::Walk`] |
 ///
 /// A separate enum rather than a validated `Instruction` also gives the rule
 /// set somewhere to put facts that are true of the local instructions and of
@@ -107,17 +108,41 @@ pub enum Prim {
     And,
     Or,
 
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Popcount,
+
     Tuple(usize),
     Untuple(usize),
+    TupleGet(usize),
+    TupleSet(usize),
+    TupleConcat,
+    TupleSlice(usize, usize),
+
+    MapEmpty,
+    MapInsert,
+    /// Carries the result tags, `ok` then `err`, as the instruction does.
+    MapGet(Value, Value),
+    MapRemove,
+    MapKeys,
 
     ConstStringLen,
     ConstStringCharAt,
+    ConstStringConcat,
+    ConstStringSlice,
+    ConstStringFromChar,
+    IntToConstString,
+    ConstStringLess,
 
     IsInt,
     IsBool,
     IsConstString,
     IsSymbol,
     IsTuple,
+    IsMap,
     TupleLength,
 
     AsBool,
@@ -126,7 +151,7 @@ pub enum Prim {
 }
 
 impl Prim {
-    /// The instruction as a prim, or `None` for the five the model expresses
+    /// The instruction as a prim, or `None` for the seven the model expresses
     /// structurally.
     ///
     /// The match is **exhaustive on purpose**: a new instruction is a
@@ -148,26 +173,49 @@ impl Prim {
             Instruction::Negate => Prim::Negate,
             Instruction::And => Prim::And,
             Instruction::Or => Prim::Or,
+            Instruction::BitAnd => Prim::BitAnd,
+            Instruction::BitOr => Prim::BitOr,
+            Instruction::BitXor => Prim::BitXor,
+            Instruction::ShiftLeft => Prim::ShiftLeft,
+            Instruction::ShiftRight => Prim::ShiftRight,
+            Instruction::Popcount => Prim::Popcount,
             Instruction::Tuple(n) => Prim::Tuple(*n),
             Instruction::Untuple(n) => Prim::Untuple(*n),
+            Instruction::TupleGet(i) => Prim::TupleGet(*i),
+            Instruction::TupleSet(i) => Prim::TupleSet(*i),
+            Instruction::TupleConcat => Prim::TupleConcat,
+            Instruction::TupleSlice(start, end) => Prim::TupleSlice(*start, *end),
+            Instruction::MapEmpty => Prim::MapEmpty,
+            Instruction::MapInsert => Prim::MapInsert,
+            Instruction::MapGet(ok, err) => Prim::MapGet(ok.clone(), err.clone()),
+            Instruction::MapRemove => Prim::MapRemove,
+            Instruction::MapKeys => Prim::MapKeys,
             Instruction::ConstStringLen => Prim::ConstStringLen,
             Instruction::ConstStringCharAt => Prim::ConstStringCharAt,
+            Instruction::ConstStringConcat => Prim::ConstStringConcat,
+            Instruction::ConstStringSlice => Prim::ConstStringSlice,
+            Instruction::ConstStringFromChar => Prim::ConstStringFromChar,
+            Instruction::IntToConstString => Prim::IntToConstString,
+            Instruction::ConstStringLess => Prim::ConstStringLess,
             Instruction::IsInt => Prim::IsInt,
             Instruction::IsBool => Prim::IsBool,
             Instruction::IsConstString => Prim::IsConstString,
             Instruction::IsSymbol => Prim::IsSymbol,
             Instruction::IsTuple => Prim::IsTuple,
+            Instruction::IsMap => Prim::IsMap,
             Instruction::TupleLength => Prim::TupleLength,
             Instruction::AsBool => Prim::AsBool,
             Instruction::AsInt => Prim::AsInt,
             Instruction::AsTuple(n) => Prim::AsTuple(*n),
 
-            // The five with a structural spelling. See the type's docs.
+            // The seven with a structural spelling. See the type's docs.
             Instruction::Drop
             | Instruction::Copy
             | Instruction::Jump(_)
             | Instruction::Dip(_)
-            | Instruction::Branch(_, _) => return None,
+            | Instruction::Branch(_, _)
+            | Instruction::TupleMap(_)
+            | Instruction::TupleFold(_) => return None,
         })
     }
 
@@ -188,15 +236,36 @@ impl Prim {
             Prim::Negate => Instruction::Negate,
             Prim::And => Instruction::And,
             Prim::Or => Instruction::Or,
+            Prim::BitAnd => Instruction::BitAnd,
+            Prim::BitOr => Instruction::BitOr,
+            Prim::BitXor => Instruction::BitXor,
+            Prim::ShiftLeft => Instruction::ShiftLeft,
+            Prim::ShiftRight => Instruction::ShiftRight,
+            Prim::Popcount => Instruction::Popcount,
             Prim::Tuple(n) => Instruction::Tuple(*n),
             Prim::Untuple(n) => Instruction::Untuple(*n),
+            Prim::TupleGet(i) => Instruction::TupleGet(*i),
+            Prim::TupleSet(i) => Instruction::TupleSet(*i),
+            Prim::TupleConcat => Instruction::TupleConcat,
+            Prim::TupleSlice(start, end) => Instruction::TupleSlice(*start, *end),
+            Prim::MapEmpty => Instruction::MapEmpty,
+            Prim::MapInsert => Instruction::MapInsert,
+            Prim::MapGet(ok, err) => Instruction::MapGet(ok.clone(), err.clone()),
+            Prim::MapRemove => Instruction::MapRemove,
+            Prim::MapKeys => Instruction::MapKeys,
             Prim::ConstStringLen => Instruction::ConstStringLen,
             Prim::ConstStringCharAt => Instruction::ConstStringCharAt,
+            Prim::ConstStringConcat => Instruction::ConstStringConcat,
+            Prim::ConstStringSlice => Instruction::ConstStringSlice,
+            Prim::ConstStringFromChar => Instruction::ConstStringFromChar,
+            Prim::IntToConstString => Instruction::IntToConstString,
+            Prim::ConstStringLess => Instruction::ConstStringLess,
             Prim::IsInt => Instruction::IsInt,
             Prim::IsBool => Instruction::IsBool,
             Prim::IsConstString => Instruction::IsConstString,
             Prim::IsSymbol => Instruction::IsSymbol,
             Prim::IsTuple => Instruction::IsTuple,
+            Prim::IsMap => Instruction::IsMap,
             Prim::TupleLength => Instruction::TupleLength,
             Prim::AsBool => Instruction::AsBool,
             Prim::AsInt => Instruction::AsInt,
@@ -212,7 +281,7 @@ impl Prim {
     /// holds this to what `op_arity` says.
     pub fn arity(&self) -> Arity {
         match self {
-            Prim::Push(_) => Arity::new(0, 1),
+            Prim::Push(_) | Prim::MapEmpty => Arity::new(0, 1),
             Prim::Swap => Arity::new(2, 2),
 
             // Everything that reads two values and answers with one. No
@@ -229,17 +298,36 @@ impl Prim {
             | Prim::Multiply
             | Prim::Divide
             | Prim::Modulo
-            | Prim::ConstStringCharAt => Arity::new(2, 1),
+            | Prim::BitAnd
+            | Prim::BitOr
+            | Prim::BitXor
+            | Prim::ShiftLeft
+            | Prim::ShiftRight
+            | Prim::ConstStringCharAt
+            | Prim::ConstStringConcat
+            | Prim::ConstStringLess
+            | Prim::TupleSet(_)
+            | Prim::TupleConcat
+            | Prim::MapGet(..)
+            | Prim::MapRemove => Arity::new(2, 1),
+            Prim::ConstStringSlice | Prim::MapInsert => Arity::new(3, 1),
 
             Prim::Not
             | Prim::Negate
+            | Prim::Popcount
             | Prim::ConstStringLen
+            | Prim::ConstStringFromChar
+            | Prim::IntToConstString
             | Prim::TupleLength
+            | Prim::TupleGet(_)
+            | Prim::TupleSlice(..)
+            | Prim::MapKeys
             | Prim::IsInt
             | Prim::IsBool
             | Prim::IsConstString
             | Prim::IsSymbol
             | Prim::IsTuple
+            | Prim::IsMap
             | Prim::AsBool
             | Prim::AsInt
             | Prim::AsTuple(_) => Arity::new(1, 1),
@@ -270,15 +358,36 @@ impl Prim {
             Prim::Negate,
             Prim::And,
             Prim::Or,
+            Prim::BitAnd,
+            Prim::BitOr,
+            Prim::BitXor,
+            Prim::ShiftLeft,
+            Prim::ShiftRight,
+            Prim::Popcount,
             Prim::Tuple(3),
             Prim::Untuple(4),
+            Prim::TupleGet(6),
+            Prim::TupleSet(7),
+            Prim::TupleConcat,
+            Prim::TupleSlice(1, 8),
+            Prim::MapEmpty,
+            Prim::MapInsert,
+            Prim::MapGet(Value::Int(1), Value::Int(0)),
+            Prim::MapRemove,
+            Prim::MapKeys,
             Prim::ConstStringLen,
             Prim::ConstStringCharAt,
+            Prim::ConstStringConcat,
+            Prim::ConstStringSlice,
+            Prim::ConstStringFromChar,
+            Prim::IntToConstString,
+            Prim::ConstStringLess,
             Prim::IsInt,
             Prim::IsBool,
             Prim::IsConstString,
             Prim::IsSymbol,
             Prim::IsTuple,
+            Prim::IsMap,
             Prim::TupleLength,
             Prim::AsBool,
             Prim::AsInt,
@@ -295,6 +404,44 @@ impl fmt::Display for Prim {
     }
 }
 
+/// Which of the two tuple walks a [`Term::Walk`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Walk {
+    /// `tuple_map`: the body once per element, each answer in its place.
+    Map,
+    /// `tuple_fold`: the body on the accumulator and each element in turn.
+    Fold,
+}
+
+impl Walk {
+    /// What the walk takes and leaves, whatever its body: a tuple in and a
+    /// tuple out, or an accumulator and a tuple in and the accumulator out.
+    ///
+    /// It is also what the body has to be, padded: one step hands it an
+    /// element, or the accumulator and an element, and takes one value back.
+    pub fn arity(&self) -> Arity {
+        match self {
+            Walk::Map => Arity::new(1, 1),
+            Walk::Fold => Arity::new(2, 1),
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Walk::Map => "tuple_map",
+            Walk::Fold => "tuple_fold",
+        }
+    }
+
+    /// The instruction this walk is, over `target`.
+    pub fn to_instruction(&self, target: SentenceIndex) -> Instruction {
+        match self {
+            Walk::Map => Instruction::TupleMap(target),
+            Walk::Fold => Instruction::TupleFold(target),
+        }
+    }
+}
+
 /// Where a term lives in the [`Context`] that built it.
 ///
 /// An index is meaningful only against its own context. Nothing checks that —
@@ -358,6 +505,18 @@ pub enum Term {
     /// `#[arity]` annotation is a claim about the sentence, not about what a
     /// call to it does.
     Call { target: SentenceIndex, arity: Arity },
+    /// `tuple_map` or `tuple_fold` over a sentence, left unopened.
+    ///
+    /// Opaque the way a [`Call`][Term::Call] is, and for the same reason: the
+    /// body is named rather than held, and `body` is its inferred arity, which
+    /// may be narrower than [`Walk::arity`] — a `1 -> 0` fold body leaves the
+    /// accumulator alone. The walk's own arity is the wider one, whatever the
+    /// body's.
+    Walk {
+        walk: Walk,
+        target: SentenceIndex,
+        body: Arity,
+    },
     /// `A ; B`: everything `A` leaves, `B` takes. Requires `A.outputs ==
     /// B.inputs`, which is the whole point — see [`Context::pad_compose`] for
     /// how a sentence's implicit padding is made explicit to get there.
@@ -447,6 +606,7 @@ impl Context {
             Term::Copy(n) => Arity::new(*n, 2 * n),
             Term::Op(prim) => prim.arity(),
             Term::Call { arity, .. } => *arity,
+            Term::Walk { walk, .. } => walk.arity(),
             Term::Compose(left, right) => {
                 Arity::new(self.arity(*left).inputs, self.arity(*right).outputs)
             }
@@ -483,6 +643,23 @@ impl Context {
         self.push(Term::Call { target, arity })
     }
 
+    /// A tuple walk over `target`, whose inferred arity is `body`.
+    ///
+    /// Refused unless the body fits the walk: no more inputs than one step
+    /// hands it, and one value back for each step to carry on with.
+    pub fn walk(
+        &mut self,
+        walk: Walk,
+        target: SentenceIndex,
+        body: Arity,
+    ) -> Result<TermIndex, Error> {
+        let wanted = walk.arity();
+        if body.inputs > wanted.inputs || body.net() != wanted.net() {
+            return Err(Error::WalkBody { walk, body });
+        }
+        Ok(self.push(Term::Walk { walk, target, body }))
+    }
+
     // ---- operators ----
 
     /// `left ; right`, which exists only when the halves meet exactly.
@@ -642,7 +819,12 @@ impl Context {
                 continue;
             }
             match self.get(idx) {
-                Term::Id(_) | Term::Drop(_) | Term::Copy(_) | Term::Op(_) | Term::Call { .. } => {}
+                Term::Id(_)
+                | Term::Drop(_)
+                | Term::Copy(_)
+                | Term::Op(_)
+                | Term::Call { .. }
+                | Term::Walk { .. } => {}
                 Term::Compose(left, right) => {
                     let (l, r) = (self.arity(*left), self.arity(*right));
                     if l.outputs != r.inputs {
@@ -812,6 +994,10 @@ impl Context {
                 Some(name) => write!(f, "call {}", name),
                 None => write!(f, "call #{}", usize::from(*target)),
             },
+            Term::Walk { walk, target, .. } => match names.of(*target) {
+                Some(name) => write!(f, "{} {}", walk.mnemonic(), name),
+                None => write!(f, "{} #{}", walk.mnemonic(), usize::from(*target)),
+            },
             Term::Compose(left, right) => {
                 self.write(f, *left, PREC_COMPOSE, names)?;
                 write!(f, " ; ")?;
@@ -911,6 +1097,8 @@ pub enum Error {
     Mismatch { left: Arity, right: Arity },
     /// Two branch arms that cannot be brought to a common arity.
     ArmsDiffer { if_true: Arity, if_false: Arity },
+    /// A tuple walk over a body that does not fit it.
+    WalkBody { walk: Walk, body: Arity },
     /// A sentence whose stack effect could not be worked out. A library that
     /// compiled has none of these: inference is what refuses recursion, so a
     /// sentence with no arity never got this far.
@@ -933,6 +1121,13 @@ impl fmt::Display for Error {
                 if_true.net(),
                 if_false.net()
             ),
+            Error::WalkBody { walk, body } => write!(
+                f,
+                "`{}` wants a body that is {}, and this one is {}",
+                walk.mnemonic(),
+                walk.arity(),
+                body
+            ),
             Error::NoArity(idx) => write!(f, "sentence {:?} has no stack effect", idx),
         }
     }
@@ -1044,6 +1239,17 @@ impl<'a> Lowering<'a> {
                 let if_false = self.target(ctx, *if_false)?;
                 ctx.pad_branch(if_true, if_false)?
             }
+            // The body is called, not spliced, even when it is a block: a walk
+            // runs it on values of its own, so there is no stack around the
+            // walk for the body's term to be composed into.
+            Instruction::TupleMap(target) => {
+                let body = self.arity_of(*target)?;
+                ctx.walk(Walk::Map, *target, body)?
+            }
+            Instruction::TupleFold(target) => {
+                let body = self.arity_of(*target)?;
+                ctx.walk(Walk::Fold, *target, body)?
+            }
             Instruction::Drop => ctx.drop(1),
             Instruction::Copy => ctx.copy(1),
             local => ctx.op(Prim::from_instruction(local)
@@ -1143,6 +1349,8 @@ mod tests {
             Instruction::Jump(SentenceIndex::from(0)),
             Instruction::Dip(SentenceIndex::from(0)),
             Instruction::Branch(SentenceIndex::from(0), SentenceIndex::from(1)),
+            Instruction::TupleMap(SentenceIndex::from(0)),
+            Instruction::TupleFold(SentenceIndex::from(0)),
         ] {
             assert_eq!(Prim::from_instruction(&inst), None, "{}", inst);
         }
@@ -1501,6 +1709,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn a_walk_stays_closed_even_over_a_block() {
+        let library = assemble(
+            r#"
+            sentence helper { push 1 add }
+            sentence probe { tuple_map crate::helper tuple_fold { drop 0 } }
+        "#,
+        )
+        .unwrap();
+        let helper = sentence_named(&library, "helper");
+        let probe = sentence_named(&library, "probe");
+        let mut ctx = Context::new();
+        let term = lower(&mut ctx, &library, probe).unwrap();
+        ctx.check(term).unwrap();
+        // The fold's body is a block, and is named by its index all the same:
+        // there is no stack around the walk to splice it into.
+        let Term::Compose(_, fold) = *ctx.get(term) else {
+            panic!("two instructions lower to a composition");
+        };
+        let Term::Walk { target: block, .. } = *ctx.get(fold) else {
+            panic!("a fold lowers to a walk");
+        };
+        assert_eq!(
+            format!("{}", ctx.display(term).named(&library)),
+            format!(
+                "id(1) * tuple_map {} ; tuple_fold {}",
+                library.names[helper], library.names[block]
+            )
+        );
+        assert_eq!(ctx.arity(term), Arity::new(2, 1));
+    }
+
+    #[test]
+    fn a_walk_refuses_a_body_that_does_not_fit() {
+        let mut ctx = Context::new();
+        let target = SentenceIndex::from(0);
+        assert!(ctx.walk(Walk::Map, target, Arity::new(1, 1)).is_ok());
+        assert!(ctx.walk(Walk::Fold, target, Arity::new(1, 0)).is_ok());
+        assert_eq!(
+            ctx.walk(Walk::Map, target, Arity::new(2, 1)),
+            Err(Error::WalkBody {
+                walk: Walk::Map,
+                body: Arity::new(2, 1)
+            })
+        );
+        assert!(ctx.walk(Walk::Fold, target, Arity::new(1, 1)).is_err());
+    }
+
     #[test]
     fn a_reach_expands_into_the_frames_it_compiles_to() {
         // `pick 1` is `dip { copy } ; swap`, and the block it dips into is
@@ -1541,7 +1797,7 @@ mod tests {
 
         let mut map = bytecode::SourceMap::new();
         let file = map.add("main.hana", text);
-        let library = bytecode::assemble_source(&mut map, file, Some(&tests))
+        let library = vm::assemble_source(&mut map, file, Some(&tests))
             .unwrap_or_else(|e| panic!("{}", map.render(&e)));
 
         let mut ctx = Context::new();
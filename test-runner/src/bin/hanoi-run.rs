@@ -0,0 +1,152 @@
+use clap::Parser;
+use std::fs;
+use std::process;
+
+/// Runs a Hanoi main machine, connected to stdout, stdin and a real-time clock.
+#[derive(Parser, Debug)]
+#[command(version, about = "Run a Hanoi main machine", long_about = None)]
+struct Args {
+    /// The .hana file to run, or with `--system` the topology file
+    file: String,
+
+    /// The file is a topology: run the processes it names, joined by its
+    /// channels
+    #[arg(long = "system", conflicts_with_all = ["machine", "replay", "record", "alphabet"])]
+    system: bool,
+
+    /// The module holding the machine to run
+    #[arg(long = "machine", default_value = "main")]
+    machine: String,
+
+    /// Read stdin from this file instead
+    #[arg(long = "input")]
+    input: Option<String>,
+
+    /// Answer `std::fs` requests inside this directory
+    #[arg(long = "fs-root")]
+    fs_root: Option<String>,
+
+    /// Record the session to this file, as JSON Lines
+    #[arg(long = "record")]
+    record: Option<String>,
+
+    /// Replay a recorded session instead of running against the real world,
+    /// and report the first place the machine does something else
+    #[arg(long = "replay", conflicts_with_all = ["record", "input", "fs_root"])]
+    replay: Option<String>,
+
+    /// An event the environment could offer, written as a value prints; a
+    /// machine left stuck is reported with the ones it would accept
+    #[arg(long = "alphabet")]
+    alphabet: Vec<String>,
+
+    /// Arguments handed to the machine's `init`, as a tuple of const strings;
+    /// with `--system`, to every machine's
+    #[arg(trailing_var_arg = true)]
+    args: Vec<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    if args.system {
+        run_system(args).await;
+    }
+
+    let file_path = std::path::Path::new(&args.file);
+    let code = match fs::read_to_string(file_path) {
+        Ok(content) => content,
+        Err(err) => {
+            eprintln!("Error reading file '{}': {}", file_path.display(), err);
+            process::exit(1);
+        }
+    };
+
+    let mut sources = bytecode::SourceMap::new();
+    let root = sources.add_path(file_path, code);
+    let res = match vm::assemble_source(&mut sources, root, file_path.parent()) {
+        Ok(result) => result,
+        Err(err) => {
+            eprint!("{}", sources.render(&err));
+            process::exit(1);
+        }
+    };
+
+    let alphabet = args
+        .alphabet
+        .iter()
+        .map(|text| match vm::script::value(text, &res) {
+            Ok((event, rest)) if rest.trim().is_empty() => event,
+            Ok((_, rest)) => fail(format!("unexpected `{}` after the event", rest.trim())),
+            Err(err) => fail(format!("reading event `{}`: {}", text, err)),
+        })
+        .collect::<Vec<_>>();
+
+    let outcome = if let Some(replay) = args.replay {
+        let text = fs::read_to_string(&replay)
+            .unwrap_or_else(|err| fail(format!("reading '{}': {}", replay, err)));
+        let env = vm::ReplayEnvironment::parse(&text, &res).unwrap_or_else(|err| fail(err));
+        let machine = env.machine().to_string();
+        let mut runtime = vm::Runtime::new(res, &machine, env).unwrap_or_else(|err| fail(err));
+        runtime.set_args(args.args);
+        runtime.set_alphabet(alphabet);
+        let run = runtime.run().await;
+        runtime.environment.verdict(run).map(|()| {
+            eprintln!("The replay matched the recording.");
+            0
+        })
+    } else {
+        let mut env = vm::DefaultEnvironment::new(&res).with_clock(vm::Clock::real_time(&res));
+        if let Some(input) = args.input {
+            env = env.with_input(vm::Input::File(input.into()));
+        }
+        if let Some(fs_root) = args.fs_root {
+            env = env.with_fs(vm::Filesystem::new(&res, fs_root).unwrap_or_else(|err| fail(err)));
+        }
+        let mut runtime = vm::Runtime::new(res, &args.machine, env).unwrap_or_else(|err| fail(err));
+        runtime.set_args(args.args);
+        runtime.set_alphabet(alphabet);
+        if let Some(record) = args.record {
+            let out = fs::File::create(&record)
+                .unwrap_or_else(|err| fail(format!("creating '{}': {}", record, err)));
+            runtime.record_to(out).unwrap_or_else(|err| fail(err));
+        }
+        runtime.run().await
+    };
+
+    exit(outcome)
+}
+
+async fn run_system(args: Args) -> ! {
+    let input = args.input;
+    let fs_root = args.fs_root;
+    let mut system = vm::System::load(std::path::Path::new(&args.file), |res| {
+        let mut env = vm::DefaultEnvironment::new(res).with_clock(vm::Clock::real_time(res));
+        if let Some(input) = input {
+            env = env.with_input(vm::Input::File(input.into()));
+        }
+        if let Some(fs_root) = fs_root {
+            env = env.with_fs(vm::Filesystem::new(res, fs_root).unwrap_or_else(|err| fail(err)));
+        }
+        env
+    })
+    .unwrap_or_else(|err| fail(err));
+    system.set_args(args.args);
+    exit(system.run().await)
+}
+
+/// Exits with the code a run answered, or reports how it failed.
+fn exit(outcome: Result<i64, String>) -> ! {
+    match outcome {
+        // A code the host cannot report is a plain failure, rather than
+        // wrapping round to whatever it comes to, which might be success.
+        Ok(code) => process::exit(u8::try_from(code).map_or(1, i32::from)),
+        Err(err) => fail(err),
+    }
+}
+
+fn fail(err: impl std::fmt::Display) -> ! {
+    eprintln!("Error: {}", err);
+    process::exit(1);
+}
@@ -56,6 +56,58 @@ fn verdict(library: &bytecode::Library, stack: &[bytecode::Value]) -> Result<(),
     }
 }
 
+/// What a test is.
+enum Kind {
+    /// A `test sentence`, run to the result it answers.
+    Sentence(bytecode::SentenceIndex),
+    /// A `test mod`, run against `prelude::start`, `pass` and `fail`.
+    Machine,
+    /// An event script, driving the machine it names.
+    Script(std::path::PathBuf),
+}
+
+/// Every `.events` file under `dir`, in no particular order.
+fn event_scripts(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut scripts = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return scripts;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            scripts.extend(event_scripts(&path));
+        } else if path.extension().is_some_and(|ext| ext == "events") {
+            scripts.push(path);
+        }
+    }
+    scripts
+}
+
+/// Plays the script at `path` against the machine it names, answering the
+/// steps the machine took, or why it failed and, if it got to run, how many.
+async fn run_script(
+    library: &bytecode::Library,
+    path: &std::path::Path,
+    trace: bool,
+    gas_limit: u64,
+) -> Result<u64, (String, Option<u64>)> {
+    let text = fs::read_to_string(path).map_err(|e| (e.to_string(), None))?;
+    let script = vm::Script::parse(&text, library).map_err(|e| (e, None))?;
+    let env = vm::ScriptedEnvironment::new(&script);
+    let mut runtime =
+        vm::Runtime::new(library.clone(), &script.machine, env).map_err(|e| (e, None))?;
+    runtime.vm_mut().set_tracing(trace);
+    runtime.vm_mut().set_gas_limit(Some(gas_limit));
+
+    let run = runtime.run().await;
+    let steps = runtime.vm().steps_executed();
+    runtime
+        .environment
+        .verdict(run)
+        .map(|()| steps)
+        .map_err(|e| (e, Some(steps)))
+}
+
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
@@ -82,7 +134,7 @@ async fn main() {
     let base_dir = file_path.parent();
     let mut sources = bytecode::SourceMap::new();
     let root = sources.add_path(&file_path, code);
-    let res = match bytecode::assemble_source(&mut sources, root, base_dir) {
+    let res = match vm::assemble_source(&mut sources, root, base_dir) {
         Ok(result) => result,
         Err(err) => {
             eprint!("{}", sources.render(&err));
@@ -92,10 +144,18 @@ async fn main() {
 
     let mut all_tests = Vec::new();
     for (name, &idx) in &res.tests {
-        all_tests.push((name.clone(), idx, false));
+        all_tests.push((name.clone(), Kind::Sentence(idx)));
     }
     for name in &res.test_machines {
-        all_tests.push((name.clone(), bytecode::SentenceIndex::from(0), true));
+        all_tests.push((name.clone(), Kind::Machine));
+    }
+    for script in event_scripts(std::path::Path::new(path)) {
+        let name = script
+            .strip_prefix(path)
+            .unwrap_or(&script)
+            .display()
+            .to_string();
+        all_tests.push((name, Kind::Script(script)));
     }
 
     if all_tests.is_empty() {
@@ -109,7 +169,7 @@ async fn main() {
     all_tests.sort_by(|a, b| a.0.cmp(&b.0));
 
     if let Some(ref pattern) = filter {
-        all_tests.retain(|(name, _, _)| name.contains(pattern));
+        all_tests.retain(|(name, _)| name.contains(pattern));
     }
 
     if all_tests.is_empty() {
@@ -126,7 +186,7 @@ async fn main() {
     println!("Running {} tests...", tests_run);
     let mut failed = 0;
 
-    for (name, index, is_machine) in all_tests {
+    for (name, kind) in all_tests {
         if trace {
             println!("test {}", name);
         } else {
@@ -134,128 +194,149 @@ async fn main() {
             io::stdout().flush().unwrap();
         }
 
-        if is_machine {
-            let env = vm::DefaultEnvironment::new(&res);
-            let mut runtime = match vm::Runtime::new(res.clone(), &name, env) {
-                Ok(rt) => rt,
-                Err(err) => {
-                    if trace {
-                        println!("result: FAILED ({})", err);
-                    } else {
-                        println!("FAILED ({})", err);
-                    }
+        match kind {
+            Kind::Script(script) => {
+                let result = match run_script(&res, &script, trace, gas_limit).await {
+                    Ok(steps) => format!("ok ({} steps)", steps),
+                    Err((err, Some(steps))) => format!("FAILED ({}) ({} steps)", err, steps),
+                    Err((err, None)) => format!("FAILED ({})", err),
+                };
+                if result.starts_with("FAILED") {
                     failed += 1;
-                    continue;
                 }
-            };
-            runtime.vm_mut().set_tracing(trace);
-            runtime.vm_mut().set_gas_limit(Some(gas_limit));
+                if trace {
+                    println!("result: {}", result);
+                } else {
+                    println!("{}", result);
+                }
+            }
+            Kind::Machine => {
+                // A test reads no input, so one that waits for some is told stdin
+                // has ended instead of blocking on the terminal.
+                let env = vm::DefaultEnvironment::new(&res)
+                    .with_input(vm::Input::Scripted(String::new()));
+                let mut runtime = match vm::Runtime::new(res.clone(), &name, env) {
+                    Ok(rt) => rt,
+                    Err(err) => {
+                        if trace {
+                            println!("result: FAILED ({})", err);
+                        } else {
+                            println!("FAILED ({})", err);
+                        }
+                        failed += 1;
+                        continue;
+                    }
+                };
+                runtime.vm_mut().set_tracing(trace);
+                runtime.vm_mut().set_gas_limit(Some(gas_limit));
 
-            let start_val = match res.symbols.get("prelude::start").cloned() {
-                Some(v) => v,
-                None => {
-                    let err = "prelude::start symbol not found";
-                    if trace {
-                        println!("result: FAILED ({})", err);
-                    } else {
-                        println!("FAILED ({})", err);
+                let start_val = match res.symbols.get("prelude::start").cloned() {
+                    Some(v) => v,
+                    None => {
+                        let err = "prelude::start symbol not found";
+                        if trace {
+                            println!("result: FAILED ({})", err);
+                        } else {
+                            println!("FAILED ({})", err);
+                        }
+                        failed += 1;
+                        continue;
                     }
-                    failed += 1;
-                    continue;
-                }
-            };
-            let pass_val = match res.symbols.get("prelude::pass").cloned() {
-                Some(v) => v,
-                None => {
-                    let err = "prelude::pass symbol not found";
-                    if trace {
-                        println!("result: FAILED ({})", err);
-                    } else {
-                        println!("FAILED ({})", err);
+                };
+                let pass_val = match res.symbols.get("prelude::pass").cloned() {
+                    Some(v) => v,
+                    None => {
+                        let err = "prelude::pass symbol not found";
+                        if trace {
+                            println!("result: FAILED ({})", err);
+                        } else {
+                            println!("FAILED ({})", err);
+                        }
+                        failed += 1;
+                        continue;
                     }
-                    failed += 1;
-                    continue;
-                }
-            };
-            let fail_val = match res.symbols.get("prelude::fail").cloned() {
-                Some(v) => v,
-                None => {
-                    let err = "prelude::fail symbol not found";
-                    if trace {
-                        println!("result: FAILED ({})", err);
-                    } else {
-                        println!("FAILED ({})", err);
+                };
+                let fail_val = match res.symbols.get("prelude::fail").cloned() {
+                    Some(v) => v,
+                    None => {
+                        let err = "prelude::fail symbol not found";
+                        if trace {
+                            println!("result: FAILED ({})", err);
+                        } else {
+                            println!("FAILED ({})", err);
+                        }
+                        failed += 1;
+                        continue;
                     }
-                    failed += 1;
-                    continue;
-                }
-            };
+                };
 
-            match runtime.run_test(&start_val, &pass_val, &fail_val).await {
-                Ok(()) => {
-                    if runtime.vm().stack().is_empty() {
-                        if trace {
-                            println!("result: ok ({} steps)", runtime.vm().steps_executed());
+                match runtime.run_test(&start_val, &pass_val, &fail_val).await {
+                    Ok(()) => {
+                        if runtime.vm().stack().is_empty() {
+                            if trace {
+                                println!("result: ok ({} steps)", runtime.vm().steps_executed());
+                            } else {
+                                println!("ok ({} steps)", runtime.vm().steps_executed());
+                            }
                         } else {
-                            println!("ok ({} steps)", runtime.vm().steps_executed());
+                            if trace {
+                                println!(
+                                    "result: FAILED (stack was not empty: {:?}) ({} steps)",
+                                    runtime.vm().stack(),
+                                    runtime.vm().steps_executed()
+                                );
+                            } else {
+                                println!(
+                                    "FAILED (stack was not empty: {:?}) ({} steps)",
+                                    runtime.vm().stack(),
+                                    runtime.vm().steps_executed()
+                                );
+                            }
+                            failed += 1;
                         }
-                    } else {
+                    }
+                    Err(err) => {
                         if trace {
                             println!(
-                                "result: FAILED (stack was not empty: {:?}) ({} steps)",
-                                runtime.vm().stack(),
+                                "result: FAILED ({}) ({} steps)",
+                                err,
                                 runtime.vm().steps_executed()
                             );
                         } else {
-                            println!(
-                                "FAILED (stack was not empty: {:?}) ({} steps)",
-                                runtime.vm().stack(),
-                                runtime.vm().steps_executed()
-                            );
+                            println!("FAILED ({}) ({} steps)", err, runtime.vm().steps_executed());
                         }
                         failed += 1;
                     }
                 }
-                Err(err) => {
-                    if trace {
-                        println!(
-                            "result: FAILED ({}) ({} steps)",
-                            err,
-                            runtime.vm().steps_executed()
-                        );
-                    } else {
-                        println!("FAILED ({}) ({} steps)", err, runtime.vm().steps_executed());
-                    }
-                    failed += 1;
-                }
             }
-        } else {
-            // Each test runs in its own fresh VM instance
-            let mut vm = vm::VM::new(res.clone());
-            vm.set_tracing(trace);
-            vm.set_gas_limit(Some(gas_limit));
-            let outcome = match vm.execute(index) {
-                Ok(()) => verdict(&res, vm.stack()),
-                // A test that halts the machine has gone wrong in a way it was
-                // not written to report: every check it makes hands back a
-                // result instead.
-                Err(err) => Err(err),
-            };
-            match outcome {
-                Ok(()) => {
-                    if trace {
-                        println!("result: ok ({} steps)", vm.steps_executed());
-                    } else {
-                        println!("ok ({} steps)", vm.steps_executed());
+            Kind::Sentence(index) => {
+                // Each test runs in its own fresh VM instance
+                let mut vm = vm::VM::new(res.clone());
+                vm.set_tracing(trace);
+                vm.set_gas_limit(Some(gas_limit));
+                let outcome = match vm.execute(index) {
+                    Ok(()) => verdict(&res, vm.stack()),
+                    // A test that halts the machine has gone wrong in a way it was
+                    // not written to report: every check it makes hands back a
+                    // result instead.
+                    Err(err) => Err(err),
+                };
+                match outcome {
+                    Ok(()) => {
+                        if trace {
+                            println!("result: ok ({} steps)", vm.steps_executed());
+                        } else {
+                            println!("ok ({} steps)", vm.steps_executed());
+                        }
                     }
-                }
-                Err(why) => {
-                    if trace {
-                        println!("result: FAILED ({}) ({} steps)", why, vm.steps_executed());
-                    } else {
-                        println!("FAILED ({}) ({} steps)", why, vm.steps_executed());
+                    Err(why) => {
+                        if trace {
+                            println!("result: FAILED ({}) ({} steps)", why, vm.steps_executed());
+                        } else {
+                            println!("FAILED ({}) ({} steps)", why, vm.steps_executed());
+                        }
+                        failed += 1;
                     }
-                    failed += 1;
                 }
             }
         }
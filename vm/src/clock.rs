@@ -0,0 +1,193 @@
+//! A clock device, for machines that time things out, retry, or beat.
+//!
+//! A machine talks to it over `std::time`, in the path notation the other
+//! devices use. Time is an `Int` count of milliseconds since the clock
+//! started.
+//!
+//! - `((), now)` under `time` asks the time, and is answered with
+//!   `(((t, now), reply), time)`.
+//! - `((delay, token), set_timer)` under `time` sets a timer, and `delay`
+//!   milliseconds later the machine is offered `((token, timer_fired), time)`.
+//!   A negative delay is no delay at all.
+//!
+//! Nothing the clock is asked can fail, so there is no error to answer: a
+//! request under `time` that is no op of the clock, or whose arguments are
+//! not the shape above, is a mistake in the machine and stops the run.
+//!
+//! In virtual time the clock only moves when the machine is waiting and a
+//! timer is set: it jumps straight to the earliest deadline, so a test runs
+//! as fast as it can and the same way every time. In real time it waits for
+//! the deadline on a tokio timer. Timers with the same deadline fire in the
+//! order they were set.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+use bytecode::{Library, Value};
+use tokio::time::Instant;
+
+use crate::runtime::{Environment, device_request};
+
+/// The symbols a program declares for `std::time`.
+struct Symbols {
+    time: Value,
+    now: Value,
+    set_timer: Value,
+    timer_fired: Value,
+    reply: Value,
+}
+
+impl Symbols {
+    fn new(library: &Library) -> Option<Self> {
+        let symbol = |name: &str| library.symbols.get(name).cloned();
+        Some(Self {
+            time: symbol("std::time::time")?,
+            now: symbol("std::time::now")?,
+            set_timer: symbol("std::time::set_timer")?,
+            timer_fired: symbol("std::time::timer_fired")?,
+            reply: symbol("std::time::reply")?,
+        })
+    }
+}
+
+enum Mode {
+    /// The time the clock has been moved to.
+    Virtual(i64),
+    /// When the clock started.
+    Real(Instant),
+}
+
+/// An [`Environment`] that tells the time and fires timers.
+///
+/// [`DefaultEnvironment::with_clock`](crate::DefaultEnvironment::with_clock)
+/// puts it next to stdout and stdin.
+pub struct Clock {
+    mode: Mode,
+    symbols: Option<Symbols>,
+    /// Tokens by deadline, then by the order they were set in.
+    timers: BTreeMap<(i64, u64), Value>,
+    next_timer: u64,
+    replies: VecDeque<Value>,
+}
+
+impl Clock {
+    /// A clock at 0 that moves only when the machine waits on a timer.
+    pub fn virtual_time(library: &Library) -> Self {
+        Self::new(library, Mode::Virtual(0))
+    }
+
+    /// A clock that starts now and keeps the wall's time.
+    pub fn real_time(library: &Library) -> Self {
+        Self::new(library, Mode::Real(Instant::now()))
+    }
+
+    /// A program that does not declare `std::time::{time, now, set_timer,
+    /// timer_fired, reply}` makes no requests, so the clock then ignores every
+    /// event.
+    fn new(library: &Library, mode: Mode) -> Self {
+        Self {
+            mode,
+            symbols: Symbols::new(library),
+            timers: BTreeMap::new(),
+            next_timer: 0,
+            replies: VecDeque::new(),
+        }
+    }
+
+    /// The time, in milliseconds since the clock started.
+    pub fn now(&self) -> i64 {
+        match self.mode {
+            Mode::Virtual(now) => now,
+            Mode::Real(started) => started.elapsed().as_millis() as i64,
+        }
+    }
+
+    /// Whether a timer is set and has yet to fire.
+    pub fn has_timer(&self) -> bool {
+        !self.timers.is_empty()
+    }
+
+    /// Answers `event` if it is a `std::time` request, and says whether it
+    /// was. A request the clock cannot make sense of is an error.
+    pub fn request(&mut self, event: &Value) -> Result<bool, String> {
+        let Some(symbols) = &self.symbols else {
+            return Ok(false);
+        };
+        let Some((args, op)) = device_request(event, &symbols.time) else {
+            return Ok(false);
+        };
+
+        if op == &symbols.now {
+            if args != &Value::unit() {
+                return Err(format!("{} takes (), not {}", op, args));
+            }
+            let answer = Value::Tuple(vec![
+                Value::Tuple(vec![
+                    Value::Tuple(vec![Value::Int(self.now()), op.clone()]),
+                    symbols.reply.clone(),
+                ]),
+                symbols.time.clone(),
+            ]);
+            self.replies.push_back(answer);
+        } else if op == &symbols.set_timer {
+            let malformed = || format!("{} takes (delay, token), not {}", op, args);
+            let Value::Tuple(pair) = args else {
+                return Err(malformed());
+            };
+            let [Value::Int(delay), token] = &pair[..] else {
+                return Err(malformed());
+            };
+            let deadline = self.now().saturating_add((*delay).max(0));
+            self.timers
+                .insert((deadline, self.next_timer), token.clone());
+            self.next_timer += 1;
+        } else {
+            return Err(format!("{} is no request the clock takes", event));
+        }
+        Ok(true)
+    }
+
+    /// The next answer to a `now`, if one is waiting.
+    pub fn next_reply(&mut self) -> Option<Value> {
+        self.replies.pop_front()
+    }
+
+    /// Fires the earliest timer, once its deadline has come, or `None` if no
+    /// timer is set. Dropped before then, it leaves the timer set.
+    pub async fn next_timer(&mut self) -> Option<Value> {
+        let (&(deadline, _), _) = self.timers.first_key_value()?;
+        match &mut self.mode {
+            Mode::Virtual(now) => *now = (*now).max(deadline),
+            Mode::Real(started) => {
+                let at = *started + Duration::from_millis(deadline as u64);
+                tokio::time::sleep_until(at).await;
+            }
+        }
+        let (_, token) = self.timers.pop_first()?;
+        let symbols = self.symbols.as_ref()?;
+        Some(Value::Tuple(vec![
+            Value::Tuple(vec![token, symbols.timer_fired.clone()]),
+            symbols.time.clone(),
+        ]))
+    }
+}
+
+impl Environment for Clock {
+    async fn handle_event(&mut self, event: Value) -> Result<(), String> {
+        self.request(&event)?;
+        Ok(())
+    }
+
+    async fn wait_for_event(&mut self) -> Result<Value, String> {
+        if let Some(reply) = self.next_reply() {
+            return Ok(reply);
+        }
+        self.next_timer()
+            .await
+            .ok_or_else(|| "The machine is waiting on the clock, and no timer is set".to_string())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.replies.is_empty() && !self.has_timer()
+    }
+}
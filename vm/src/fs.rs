@@ -0,0 +1,315 @@
+//! A file system device, for machines that read and write files.
+//!
+//! A machine talks to it over `std::fs`, in the path notation the other
+//! devices use. It emits a request, `((args, op), fs)`, and is later offered
+//! the answer, `(((result, op), reply), fs)`, where `result` is a Hana result:
+//! `(value, ok)` or `(reason, err)`, with `reason` a const string.
+//!
+//! | `op`    | `args`             | `value`                                  |
+//! |---------|--------------------|------------------------------------------|
+//! | `open`  | `(path, writable)` | a handle (an `Int`)                      |
+//! | `read`  | `(handle, max)`    | up to `max` bytes, `""` at end of file   |
+//! | `write` | `(handle, chunk)`  | `()`                                     |
+//! | `close` | `handle`           | `()`                                     |
+//! | `list`  | `path`             | the names in the directory, sorted       |
+//!
+//! A file opened with `writable` false is read from and has to exist; one
+//! opened with `writable` true is created, or emptied if it exists, and is
+//! written to. Every path is relative to the device's root, and one that
+//! leads outside it — by `..`, by being absolute, or through a symlink — is
+//! refused like any other request that cannot be answered.
+//!
+//! Only the world refuses with `(reason, err)`: a missing file, a handle
+//! that is not open, a path outside the root. A request under `fs` that is
+//! no op of the device, or whose `args` are not the shape the table gives,
+//! is a mistake in the machine, and stops the run instead.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use bytecode::{Library, Value};
+
+use crate::runtime::{Environment, device_request};
+
+/// The symbols a program declares for `std::fs`.
+struct Symbols {
+    fs: Value,
+    open: Value,
+    read: Value,
+    write: Value,
+    close: Value,
+    list: Value,
+    reply: Value,
+    ok: Value,
+    err: Value,
+}
+
+impl Symbols {
+    fn new(library: &Library) -> Option<Self> {
+        let symbol = |name: &str| library.symbols.get(name).cloned();
+        Some(Self {
+            fs: symbol("std::fs::fs")?,
+            open: symbol("std::fs::open")?,
+            read: symbol("std::fs::read")?,
+            write: symbol("std::fs::write")?,
+            close: symbol("std::fs::close")?,
+            list: symbol("std::fs::list")?,
+            reply: symbol("std::fs::reply")?,
+            ok: symbol("prelude::ok")?,
+            err: symbol("prelude::err")?,
+        })
+    }
+}
+
+enum Handle {
+    Reading(BufReader<File>),
+    Writing(File),
+}
+
+/// An [`Environment`] that answers `std::fs` requests inside a root directory.
+///
+/// On its own it is offered every event a machine emits, answers the ones
+/// that are file system requests, and offers the answers back in the order
+/// it was asked. [`DefaultEnvironment::with_fs`](crate::DefaultEnvironment::with_fs)
+/// puts it next to stdout and stdin.
+pub struct Filesystem {
+    root: PathBuf,
+    symbols: Option<Symbols>,
+    handles: BTreeMap<i64, Handle>,
+    next_handle: i64,
+    replies: VecDeque<Value>,
+}
+
+impl Filesystem {
+    /// A device rooted at `root`, which has to be an existing directory.
+    ///
+    /// A program that does not declare `std::fs::{fs, open, read, write,
+    /// close, list, reply}` and `prelude::{ok, err}` makes no requests it
+    /// could read the answer to, so the device then ignores every event.
+    pub fn new(library: &Library, root: impl AsRef<Path>) -> Result<Self, String> {
+        let root = root.as_ref();
+        let root = root.canonicalize().map_err(|e| {
+            format!(
+                "Could not open file system root '{}': {}",
+                root.display(),
+                e
+            )
+        })?;
+        if !root.is_dir() {
+            return Err(format!(
+                "File system root '{}' is not a directory",
+                root.display()
+            ));
+        }
+        Ok(Self {
+            root,
+            symbols: Symbols::new(library),
+            handles: BTreeMap::new(),
+            next_handle: 0,
+            replies: VecDeque::new(),
+        })
+    }
+
+    /// Answers `event` if it is a `std::fs` request, and says whether it was.
+    /// A request whose arguments are not the shape its op takes is an error.
+    pub fn request(&mut self, event: &Value) -> Result<bool, String> {
+        let Some(symbols) = &self.symbols else {
+            return Ok(false);
+        };
+        let Some((args, op)) = device_request(event, &symbols.fs) else {
+            return Ok(false);
+        };
+        let malformed = |takes: &str| Err(format!("{} takes {}, not {}", op, takes, args));
+        let pair = match args {
+            Value::Tuple(args) => match &args[..] {
+                [a, b] => Some((a, b)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let result = if op == &symbols.open {
+            match pair {
+                Some((Value::ConstString(path), Value::Bool(writable))) => {
+                    self.open(path, *writable)
+                }
+                _ => return malformed("(path, writable)"),
+            }
+        } else if op == &symbols.read {
+            match pair {
+                Some((Value::Int(handle), Value::Int(max))) => self.read(*handle, *max),
+                _ => return malformed("(handle, max)"),
+            }
+        } else if op == &symbols.write {
+            match pair {
+                Some((Value::Int(handle), Value::ConstString(chunk))) => self.write(*handle, chunk),
+                _ => return malformed("(handle, chunk)"),
+            }
+        } else if op == &symbols.close {
+            match args {
+                Value::Int(handle) => self.close(*handle),
+                _ => return malformed("a handle"),
+            }
+        } else if op == &symbols.list {
+            match args {
+                Value::ConstString(path) => self.list(path),
+                _ => return malformed("a path"),
+            }
+        } else {
+            return Err(format!("{} is no request the file system takes", event));
+        };
+
+        let symbols = self.symbols.as_ref().unwrap();
+        let result = match result {
+            Ok(value) => Value::Tuple(vec![value, symbols.ok.clone()]),
+            Err(reason) => Value::Tuple(vec![Value::ConstString(reason), symbols.err.clone()]),
+        };
+        let answer = Value::Tuple(vec![
+            Value::Tuple(vec![
+                Value::Tuple(vec![result, op.clone()]),
+                symbols.reply.clone(),
+            ]),
+            symbols.fs.clone(),
+        ]);
+        self.replies.push_back(answer);
+        Ok(true)
+    }
+
+    /// The next answer, if one is waiting.
+    pub fn next_reply(&mut self) -> Option<Value> {
+        self.replies.pop_front()
+    }
+
+    /// Where `path` is, if it stays inside the root.
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(path);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(format!("'{}' leads outside the root", path));
+        }
+        // Every symlink along the way has to land inside the root too. One
+        // that lands nowhere is refused as well: creating the file would
+        // create whatever it points at, wherever that is.
+        let mut at = self.root.clone();
+        for component in relative.components() {
+            at.push(component);
+            match at.symlink_metadata() {
+                Ok(meta) if meta.file_type().is_symlink() => {
+                    let target = at.canonicalize().map_err(|_| {
+                        format!("'{}' goes through a symlink that leads nowhere", path)
+                    })?;
+                    if !target.starts_with(&self.root) {
+                        return Err(format!("'{}' leads outside the root", path));
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        Ok(self.root.join(relative))
+    }
+
+    fn handle(&mut self, id: i64) -> Result<&mut Handle, String> {
+        self.handles
+            .get_mut(&id)
+            .ok_or_else(|| format!("no file is open as handle {}", id))
+    }
+
+    fn open(&mut self, path: &str, writable: bool) -> Result<Value, String> {
+        let full = self.resolve(path)?;
+        let handle = if writable {
+            Handle::Writing(File::create(&full).map_err(|e| format!("'{}': {}", path, e))?)
+        } else {
+            Handle::Reading(BufReader::new(
+                File::open(&full).map_err(|e| format!("'{}': {}", path, e))?,
+            ))
+        };
+        let id = self.next_handle;
+        self.next_handle += 1;
+        self.handles.insert(id, handle);
+        Ok(Value::Int(id))
+    }
+
+    fn read(&mut self, id: i64, max: i64) -> Result<Value, String> {
+        let max = max.max(0) as u64;
+        let Handle::Reading(reader) = self.handle(id)? else {
+            return Err(format!("handle {} was opened for writing", id));
+        };
+        let mut bytes = Vec::new();
+        let failed = |e: std::io::Error| format!("reading handle {}: {}", id, e);
+        reader
+            .by_ref()
+            .take(max)
+            .read_to_end(&mut bytes)
+            .map_err(failed)?;
+        // A chunk that ends partway through a character takes the rest of it.
+        loop {
+            match std::str::from_utf8(&bytes) {
+                Ok(_) => break,
+                Err(e) if e.error_len().is_none() => {
+                    let before = bytes.len();
+                    reader
+                        .by_ref()
+                        .take(1)
+                        .read_to_end(&mut bytes)
+                        .map_err(failed)?;
+                    if bytes.len() == before {
+                        return Err(format!("handle {} is not UTF-8", id));
+                    }
+                }
+                Err(_) => return Err(format!("handle {} is not UTF-8", id)),
+            }
+        }
+        Ok(Value::ConstString(String::from_utf8(bytes).unwrap()))
+    }
+
+    fn write(&mut self, id: i64, chunk: &str) -> Result<Value, String> {
+        let Handle::Writing(file) = self.handle(id)? else {
+            return Err(format!("handle {} was opened for reading", id));
+        };
+        file.write_all(chunk.as_bytes())
+            .map_err(|e| format!("writing handle {}: {}", id, e))?;
+        Ok(Value::unit())
+    }
+
+    fn close(&mut self, id: i64) -> Result<Value, String> {
+        self.handle(id)?;
+        self.handles.remove(&id);
+        Ok(Value::unit())
+    }
+
+    fn list(&mut self, path: &str) -> Result<Value, String> {
+        let full = self.resolve(path)?;
+        let entries = std::fs::read_dir(&full).map_err(|e| format!("'{}': {}", path, e))?;
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("'{}': {}", path, e))?;
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        names.sort();
+        Ok(Value::Tuple(
+            names.into_iter().map(Value::ConstString).collect(),
+        ))
+    }
+}
+
+impl Environment for Filesystem {
+    async fn handle_event(&mut self, event: Value) -> Result<(), String> {
+        self.request(&event)?;
+        Ok(())
+    }
+
+    async fn wait_for_event(&mut self) -> Result<Value, String> {
+        self.next_reply().ok_or_else(|| {
+            "The machine is waiting, and the file system has no answer to give it".to_string()
+        })
+    }
+
+    fn is_closed(&self) -> bool {
+        self.replies.is_empty()
+    }
+}
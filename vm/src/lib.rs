@@ -1,7 +1,21 @@
-use bytecode::{Instruction, Library, SentenceIndex, Value};
+use std::collections::BTreeMap;
 
+use bytecode::{Error, FileId, Instruction, Library, SentenceIndex, SourceMap, Value};
+
+pub mod clock;
+pub mod fs;
+pub mod record;
+pub mod router;
 pub mod runtime;
-pub use runtime::{DefaultEnvironment, Environment, Runtime};
+pub mod script;
+pub mod system;
+pub use clock::Clock;
+pub use fs::Filesystem;
+pub use record::ReplayEnvironment;
+pub use router::Router;
+pub use runtime::{DefaultEnvironment, Environment, Input, Runtime};
+pub use script::{Script, ScriptedEnvironment};
+pub use system::System;
 
 use bytecode::value::numeric_cmp;
 
@@ -13,6 +27,28 @@ struct Frame {
     sentence: SentenceIndex,
     ip: usize,
     hidden: Vec<Value>,
+    /// Set while `tuple_map` or `tuple_fold` is calling its target: what to
+    /// do with what the call left, and the elements it has yet to visit.
+    walk: Option<Walk>,
+}
+
+/// A tuple walk part way through its elements.
+///
+/// The walk is a frame rather than a loop in the instruction's arm because
+/// the target is a sentence like any other, and runs on this machine's stack
+/// one step at a time — counted, traced and limited the same way.
+enum Walk {
+    /// Collecting what the target left for each element so far.
+    Map {
+        target: SentenceIndex,
+        rest: std::vec::IntoIter<Value>,
+        done: Vec<Value>,
+    },
+    /// The accumulator stays on the stack between calls.
+    Fold {
+        target: SentenceIndex,
+        rest: std::vec::IntoIter<Value>,
+    },
 }
 
 /// The virtual machine that executes sentences from a loaded library.
@@ -83,7 +119,31 @@ impl VM {
 
             if ip >= sentence.len() {
                 // Return to the caller if there's an address on the call stack
-                if let Some(frame) = self.call_stack.pop() {
+                if let Some(mut frame) = self.call_stack.pop() {
+                    // A walk with elements left calls its target again, from
+                    // the same frame; one that is done resumes like any call.
+                    if let Some(walk) = frame.walk.as_mut() {
+                        let next = match walk {
+                            Walk::Map { target, rest, done } => {
+                                done.push(self.pop()?);
+                                match rest.next() {
+                                    Some(element) => Some((*target, element)),
+                                    None => {
+                                        self.stack.push(Value::Tuple(std::mem::take(done)));
+                                        None
+                                    }
+                                }
+                            }
+                            Walk::Fold { target, rest } => rest.next().map(|e| (*target, e)),
+                        };
+                        if let Some((target, element)) = next {
+                            self.stack.push(element);
+                            self.call_stack.push(frame);
+                            current_sentence = target;
+                            ip = 0;
+                            continue;
+                        }
+                    }
                     if self.tracing {
                         println!(
                             "[TRACE] Returning to Sentence: {:?}, IP: {}",
@@ -216,6 +276,43 @@ impl VM {
                         _ => Value::Int(0),
                     });
                 }
+                Instruction::BitAnd | Instruction::BitOr | Instruction::BitXor => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(match (a, b) {
+                        (Value::Int(x), Value::Int(y)) => Value::Int(match instruction {
+                            Instruction::BitAnd => x & y,
+                            Instruction::BitOr => x | y,
+                            _ => x ^ y,
+                        }),
+                        _ => Value::Int(0),
+                    });
+                }
+                Instruction::ShiftLeft | Instruction::ShiftRight => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(match (a, b) {
+                        // A shift of 64 or more moves every bit out, which
+                        // the host refuses to do rather than answering: what
+                        // is left is `0`, or the sign for a right shift.
+                        (Value::Int(x), Value::Int(n)) if n >= 0 => {
+                            let n = n.min(64) as u32;
+                            Value::Int(if matches!(instruction, Instruction::ShiftLeft) {
+                                x.checked_shl(n).unwrap_or(0)
+                            } else {
+                                x.checked_shr(n).unwrap_or(x >> 63)
+                            })
+                        }
+                        _ => Value::Int(0),
+                    });
+                }
+                Instruction::Popcount => {
+                    let val = self.pop()?;
+                    self.stack.push(match val {
+                        Value::Int(x) => Value::Int(x.count_ones() as i64),
+                        _ => Value::Int(0),
+                    });
+                }
                 Instruction::Not => {
                     let val = self.pop()?;
                     self.stack.push(Value::Bool(!val.truthy()));
@@ -242,6 +339,7 @@ impl VM {
                         sentence: current_sentence,
                         ip,
                         hidden: Vec::new(),
+                        walk: None,
                     });
                     current_sentence = target;
                     ip = 0;
@@ -257,7 +355,46 @@ impl VM {
                         sentence: current_sentence,
                         ip,
                         hidden: vec![hidden],
+                        walk: None,
+                    });
+                    current_sentence = target;
+                    ip = 0;
+                }
+                Instruction::TupleMap(target) | Instruction::TupleFold(target) => {
+                    let val = self.pop()?;
+                    let map = matches!(instruction, Instruction::TupleMap(_));
+                    if !map && self.stack.is_empty() {
+                        return Err("Stack underflow on TupleFold".to_string());
+                    }
+                    // No tuple, or no elements, and the target is never
+                    // called: the map's answer is `()` either way, and the
+                    // fold's is the accumulator it was given.
+                    let mut rest = match val {
+                        Value::Tuple(elements) => elements.into_iter(),
+                        _ => Vec::new().into_iter(),
+                    };
+                    let Some(first) = rest.next() else {
+                        if map {
+                            self.stack.push(Value::unit());
+                        }
+                        continue;
+                    };
+                    let walk = if map {
+                        Walk::Map {
+                            target,
+                            rest,
+                            done: Vec::new(),
+                        }
+                    } else {
+                        Walk::Fold { target, rest }
+                    };
+                    self.call_stack.push(Frame {
+                        sentence: current_sentence,
+                        ip,
+                        hidden: Vec::new(),
+                        walk: Some(walk),
                     });
+                    self.stack.push(first);
                     current_sentence = target;
                     ip = 0;
                 }
@@ -272,6 +409,7 @@ impl VM {
                         sentence: current_sentence,
                         ip,
                         hidden: Vec::new(),
+                        walk: None,
                     });
                     if b {
                         current_sentence = then_target;
@@ -312,6 +450,93 @@ impl VM {
                         _ => self.stack.extend(std::iter::repeat_n(Value::unit(), n)),
                     }
                 }
+                // The tuple operations that keep a tuple whole. Each leaves
+                // `()` where it had no element to read or slot to fill, the
+                // same `()` an `untuple` slot is filled with.
+                Instruction::TupleGet(i) => {
+                    let val = self.pop()?;
+                    self.stack.push(match val {
+                        Value::Tuple(mut elements) if i < elements.len() => elements.swap_remove(i),
+                        _ => Value::unit(),
+                    });
+                }
+                Instruction::TupleSet(i) => {
+                    let new = self.pop()?;
+                    let val = self.pop()?;
+                    self.stack.push(match val {
+                        Value::Tuple(mut elements) if i < elements.len() => {
+                            elements[i] = new;
+                            Value::Tuple(elements)
+                        }
+                        _ => Value::unit(),
+                    });
+                }
+                Instruction::TupleConcat => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(match (a, b) {
+                        (Value::Tuple(mut a), Value::Tuple(b)) => {
+                            a.extend(b);
+                            Value::Tuple(a)
+                        }
+                        _ => Value::unit(),
+                    });
+                }
+                Instruction::TupleSlice(start, end) => {
+                    let val = self.pop()?;
+                    self.stack.push(match val {
+                        Value::Tuple(elements) if start <= end && end <= elements.len() => {
+                            Value::Tuple(elements[start..end].to_vec())
+                        }
+                        _ => Value::unit(),
+                    });
+                }
+                // The map operations. A value that is no map has no entries,
+                // so the ones that answer a map answer the empty one, and a
+                // lookup in it finds nothing.
+                Instruction::MapEmpty => self.stack.push(Value::Map(BTreeMap::new())),
+                Instruction::MapInsert => {
+                    let value = self.pop()?;
+                    let key = self.pop()?;
+                    let map = self.pop()?;
+                    self.stack.push(match map {
+                        Value::Map(mut entries) => {
+                            entries.insert(key, value);
+                            Value::Map(entries)
+                        }
+                        _ => Value::Map(BTreeMap::new()),
+                    });
+                }
+                Instruction::MapGet(ok, err) => {
+                    let key = self.pop()?;
+                    let map = self.pop()?;
+                    let found = match map {
+                        Value::Map(mut entries) => entries.remove(&key),
+                        _ => None,
+                    };
+                    self.stack.push(match found {
+                        Some(value) => Value::Tuple(vec![value, ok]),
+                        None => Value::Tuple(vec![key, err]),
+                    });
+                }
+                Instruction::MapRemove => {
+                    let key = self.pop()?;
+                    let map = self.pop()?;
+                    self.stack.push(match map {
+                        Value::Map(mut entries) => {
+                            entries.remove(&key);
+                            Value::Map(entries)
+                        }
+                        _ => Value::Map(BTreeMap::new()),
+                    });
+                }
+                Instruction::MapKeys => {
+                    let map = self.pop()?;
+                    self.stack.push(match map {
+                        Value::Map(entries) => Value::Tuple(entries.into_keys().collect()),
+                        _ => Value::unit(),
+                    });
+                }
                 Instruction::IsInt => {
                     let val = self.pop()?;
                     self.stack.push(Value::Bool(matches!(val, Value::Int(_))));
@@ -334,6 +559,10 @@ impl VM {
                     let val = self.pop()?;
                     self.stack.push(Value::Bool(matches!(val, Value::Tuple(_))));
                 }
+                Instruction::IsMap => {
+                    let val = self.pop()?;
+                    self.stack.push(Value::Bool(matches!(val, Value::Map(_))));
+                }
                 Instruction::TupleLength => {
                     // A length is an `Int` whatever it was asked about, so a
                     // non-tuple takes the same `0` the arithmetic does rather
@@ -394,6 +623,63 @@ impl VM {
                     };
                     self.stack.push(Value::Int(ch.unwrap_or(0)));
                 }
+                // The string builders. Each leaves a ConstString whatever it
+                // was handed, and the empty one where it had nothing to build
+                // from. See `docs/totality.md`.
+                Instruction::ConstStringConcat => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(Value::ConstString(match (a, b) {
+                        (Value::ConstString(a), Value::ConstString(b)) => a + &b,
+                        _ => String::new(),
+                    }));
+                }
+                Instruction::ConstStringSlice => {
+                    let end = self.pop()?;
+                    let start = self.pop()?;
+                    let str_val = self.pop()?;
+                    // A range partly outside the string is as much off the
+                    // domain as one wholly outside it: clamping would answer
+                    // with characters nobody asked for.
+                    let slice = match (str_val, start, end) {
+                        (Value::ConstString(s), Value::Int(start), Value::Int(end))
+                            if 0 <= start && start <= end && end <= s.chars().count() as i64 =>
+                        {
+                            s.chars()
+                                .skip(start as usize)
+                                .take((end - start) as usize)
+                                .collect()
+                        }
+                        _ => String::new(),
+                    };
+                    self.stack.push(Value::ConstString(slice));
+                }
+                Instruction::ConstStringFromChar => {
+                    let val = self.pop()?;
+                    let ch = match val {
+                        Value::Int(code) => u32::try_from(code).ok().and_then(char::from_u32),
+                        _ => None,
+                    };
+                    self.stack
+                        .push(Value::ConstString(ch.map(String::from).unwrap_or_default()));
+                }
+                Instruction::IntToConstString => {
+                    let val = self.pop()?;
+                    self.stack.push(Value::ConstString(match val {
+                        Value::Int(i) => i.to_string(),
+                        _ => String::new(),
+                    }));
+                }
+                Instruction::ConstStringLess => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    // `str`'s order is byte order, which UTF-8 makes the same
+                    // as code point order.
+                    self.stack.push(Value::Bool(match (a, b) {
+                        (Value::ConstString(a), Value::ConstString(b)) => a < b,
+                        _ => false,
+                    }));
+                }
             }
         }
 
@@ -401,6 +687,35 @@ impl VM {
     }
 }
 
+/// Runs `sentence` on an empty stack and answers the stack it leaves.
+///
+/// The evaluator the compiler works out a `const` and checks a
+/// `static_assert` with. No gas limit: the compiler runs nothing that could
+/// recurse, so everything it runs stops.
+pub fn evaluate(library: Library, sentence: SentenceIndex) -> Result<Vec<Value>, String> {
+    let mut vm = VM::new(library);
+    vm.execute(sentence)?;
+    Ok(vm.stack)
+}
+
+/// Assembles `input` as [`bytecode::assemble`] does, with this VM to work out
+/// its constants.
+pub fn assemble(input: &str) -> Result<Library, String> {
+    let mut map = SourceMap::new();
+    let file = map.add("<input>", input.to_string());
+    assemble_source(&mut map, file, None).map_err(|e| map.render(&e))
+}
+
+/// Assembles a source file as [`bytecode::assemble_source`] does, with this
+/// VM to work out its constants.
+pub fn assemble_source(
+    map: &mut SourceMap,
+    file: FileId,
+    base_dir: Option<&std::path::Path>,
+) -> Result<Library, Error> {
+    bytecode::assemble_source_evaluating(map, file, base_dir, Some(&evaluate))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -650,6 +965,194 @@ mod tests {
         assert_eq!(vm.stack(), &[Value::Int(30), Value::Int(100)]);
     }
 
+    #[test]
+    fn a_tuple_map_calls_its_target_once_per_element() {
+        let code = r#"
+            export sentence start {
+                push 1 push 2 push 3 tuple 3
+                tuple_map { push 10 multiply }
+            }
+        "#;
+        let res = bytecode::assemble(code).unwrap();
+        let start_idx = *res.exports.get("start").unwrap();
+
+        let mut vm = VM::new(res);
+        vm.execute(start_idx).unwrap();
+        assert_eq!(
+            vm.stack(),
+            &[Value::Tuple(vec![
+                Value::Int(10),
+                Value::Int(20),
+                Value::Int(30)
+            ])]
+        );
+        // Four to build the tuple, one for the map, and the body's two for
+        // each of three elements: the text times the data, and no more.
+        assert_eq!(vm.steps_executed(), 4 + 1 + 2 * 3);
+    }
+
+    #[test]
+    fn a_tuple_fold_visits_the_elements_from_the_first() {
+        // Pairing the accumulator with each element in turn records the order
+        // they were visited in.
+        let code = r#"
+            export sentence start {
+                push 0
+                push 1 push 2 tuple 2
+                tuple_fold { tuple 2 }
+            }
+        "#;
+        let res = bytecode::assemble(code).unwrap();
+        let start_idx = *res.exports.get("start").unwrap();
+
+        let mut vm = VM::new(res);
+        vm.execute(start_idx).unwrap();
+        let first = Value::Tuple(vec![Value::Int(0), Value::Int(1)]);
+        assert_eq!(vm.stack(), &[Value::Tuple(vec![first, Value::Int(2)])]);
+    }
+
+    #[test]
+    fn a_walk_over_no_elements_never_calls_its_target() {
+        // Something that is no tuple has no elements, the same as `()`: the
+        // map answers `()`, the fold its accumulator, and neither body runs.
+        for walked in ["push 5", "tuple 0"] {
+            let code = format!(
+                "export sentence map {{ {} tuple_map {{ push 1 add }} }}
+                 export sentence fold {{ push 7 {} tuple_fold {{ add }} }}",
+                walked, walked
+            );
+            let res = bytecode::assemble(&code).unwrap();
+            let (map, fold) = (res.exports["map"], res.exports["fold"]);
+            let mut vm = VM::new(res);
+            vm.execute(map).unwrap();
+            assert_eq!(vm.stack(), &[Value::unit()], "map over `{}`", walked);
+            assert_eq!(vm.steps_executed(), 2, "map over `{}`", walked);
+            let mut vm = VM::new(vm.library);
+            vm.execute(fold).unwrap();
+            assert_eq!(vm.stack(), &[Value::Int(7)], "fold over `{}`", walked);
+            assert_eq!(vm.steps_executed(), 3, "fold over `{}`", walked);
+        }
+    }
+
+    #[test]
+    fn a_narrow_fold_body_leaves_the_accumulator_underneath() {
+        // `{ drop 0 }` is `1 -> 0`: it takes the element and never sees the
+        // accumulator, which the walk hands on unchanged.
+        let code = r#"
+            export sentence start {
+                push 7
+                push 1 push 2 tuple 2
+                tuple_fold { drop 0 }
+            }
+        "#;
+        let res = bytecode::assemble(code).unwrap();
+        let start_idx = *res.exports.get("start").unwrap();
+
+        let mut vm = VM::new(res);
+        vm.execute(start_idx).unwrap();
+        assert_eq!(vm.stack(), &[Value::Int(7)]);
+    }
+
+    // -----------------------------------------------------------------------
+    // `const` and `static_assert`, which the compiler runs on this VM
+    // -----------------------------------------------------------------------
+
+    /// What `entry` leaves, in a program assembled with its constants worked
+    /// out.
+    fn run_entry(code: &str) -> Vec<Value> {
+        let res = crate::assemble(code).unwrap();
+        let idx = *res.exports.get("entry").unwrap();
+        let mut vm = VM::new(res);
+        vm.execute(idx).unwrap();
+        vm.stack().to_vec()
+    }
+
+    #[test]
+    fn a_const_is_pushed_as_the_value_its_body_left() {
+        let code = r#"
+            sentence triple { push 3 multiply }
+            const table = { push 1 push 2 jump triple tuple 2 };
+            export sentence entry { push table }
+        "#;
+        assert_eq!(
+            run_entry(code),
+            vec![Value::Tuple(vec![Value::Int(1), Value::Int(6)])]
+        );
+    }
+
+    #[test]
+    fn a_const_body_leaves_early_the_way_the_program_would() {
+        // The `?` takes the error path, and its failure arm has to drop the
+        // `7` the rest would have added to: balanced before the run, not only
+        // in the library handed back.
+        let code = r#"
+            mod prelude { symbol ok symbol err }
+            const failed = { push 7 push (1, crate::prelude::err) ? add };
+            export sentence entry { push failed }
+        "#;
+        let [Value::Tuple(pair)] = &run_entry(code)[..] else {
+            panic!("expected one tuple");
+        };
+        assert_eq!(pair[0], Value::Int(1));
+    }
+
+    #[test]
+    fn a_failed_static_assert_shows_what_it_left() {
+        let err = crate::assemble("sentence a { push 1 }\n\nstatic_assert {\n    push (1, 2)\n}\n")
+            .unwrap_err();
+        assert!(err.contains("static assertion failed"), "{}", err);
+        assert!(err.contains("left `(1, 2)`"), "{}", err);
+        assert!(err.contains("--> <input>:3:1"), "{}", err);
+    }
+
+    #[test]
+    fn a_const_has_to_leave_one_value() {
+        let err = crate::assemble("const two = { push 1 push 2 };").unwrap_err();
+        assert!(
+            err.contains("const `two` has to leave one value, but left `1`, `2`"),
+            "{}",
+            err
+        );
+        assert!(err.contains("--> <input>:1:7"), "{}", err);
+    }
+
+    #[test]
+    fn a_const_cannot_depend_on_itself() {
+        let err = crate::assemble("const a = { push b };\nconst b = { push a };").unwrap_err();
+        assert!(
+            err.contains("const `a` depends on its own value"),
+            "{}",
+            err
+        );
+
+        // Nor on a sentence that needs another const worked out first, which
+        // needs that sentence in turn.
+        let err =
+            crate::assemble("sentence s { push b }\nconst a = { jump s };\nconst b = { jump s };")
+                .unwrap_err();
+        assert!(
+            err.contains("it calls 's', which is still being compiled"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn a_const_never_runs_a_recursive_sentence() {
+        let err = crate::assemble("sentence s { jump s }\nconst a = { jump s };").unwrap_err();
+        assert!(
+            err.contains("'s' calls itself, so running it might never stop"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn a_const_needs_something_to_run_it() {
+        let err = bytecode::assemble("const a = { push 1 };").unwrap_err();
+        assert!(err.contains("assemble through `vm::assemble`"), "{}", err);
+    }
+
     #[test]
     fn test_symbols_vm() {
         let code = r#"
@@ -1134,12 +1637,23 @@ mod totality_tests {
             Value::Int(6),
             cs("hi"),
             cs(""),
+            // A second non-empty string, for the same reason as `6`: `""` is
+            // the one string every concatenation commutes with.
+            cs("yo"),
             sym(7),
             unit(),
             Value::Tuple(vec![Value::Int(1), Value::Int(2)]),
+            // And a second non-empty tuple, which `()` cannot stand in for.
+            Value::Tuple(vec![sym(7)]),
+            Value::Map(BTreeMap::new()),
+            map(&[(Value::Int(1), sym(7))]),
         ]
     }
 
+    fn map(entries: &[(Value, Value)]) -> Value {
+        Value::Map(entries.iter().cloned().collect())
+    }
+
     /// Every instruction that reads two operands, whether or not it commutes.
     ///
     /// The list is the *candidates*: the point of the sweep below is to find
@@ -1156,7 +1670,18 @@ mod totality_tests {
             Instruction::Modulo,
             Instruction::And,
             Instruction::Or,
+            Instruction::BitAnd,
+            Instruction::BitOr,
+            Instruction::BitXor,
+            Instruction::ShiftLeft,
+            Instruction::ShiftRight,
             Instruction::ConstStringCharAt,
+            Instruction::ConstStringConcat,
+            Instruction::ConstStringLess,
+            Instruction::TupleSet(0),
+            Instruction::TupleConcat,
+            Instruction::MapGet(sym(1), sym(2)),
+            Instruction::MapRemove,
             Instruction::Tuple(2),
         ]
     }
@@ -1229,8 +1754,19 @@ mod totality_tests {
             Instruction::Negate,
             Instruction::And,
             Instruction::Or,
+            Instruction::BitAnd,
+            Instruction::BitOr,
+            Instruction::BitXor,
+            Instruction::ShiftLeft,
+            Instruction::ShiftRight,
+            Instruction::Popcount,
             Instruction::ConstStringLen,
             Instruction::ConstStringCharAt,
+            Instruction::ConstStringConcat,
+            Instruction::ConstStringSlice,
+            Instruction::ConstStringFromChar,
+            Instruction::IntToConstString,
+            Instruction::ConstStringLess,
             Instruction::IsInt,
             Instruction::IsBool,
             Instruction::IsConstString,
@@ -1239,6 +1775,16 @@ mod totality_tests {
             Instruction::TupleLength,
             Instruction::Untuple(2),
             Instruction::Tuple(2),
+            Instruction::TupleGet(1),
+            Instruction::TupleSet(1),
+            Instruction::TupleConcat,
+            Instruction::TupleSlice(0, 1),
+            Instruction::MapEmpty,
+            Instruction::MapInsert,
+            Instruction::MapGet(sym(1), sym(2)),
+            Instruction::MapRemove,
+            Instruction::MapKeys,
+            Instruction::IsMap,
             // `as_tuple 2` is here at the width `every_shape` supplies a
             // matching tuple for, so the sweep sees both of its cases.
             Instruction::AsBool,
@@ -1269,17 +1815,19 @@ mod totality_tests {
         for inst in every_computation() {
             let (n, _) = bytecode::arity::op_arity(&inst)
                 .unwrap_or_else(|| panic!("{:?} has no arity", inst));
-            let operand_sets: Vec<Vec<Value>> = match n {
-                1 => every_shape().into_iter().map(|a| vec![a]).collect(),
-                2 => every_shape()
+            let mut operand_sets: Vec<Vec<Value>> = vec![vec![]];
+            for _ in 0..n {
+                operand_sets = operand_sets
                     .into_iter()
-                    .flat_map(|a| every_shape().into_iter().map(move |b| vec![a.clone(), b]))
-                    .collect(),
-                other => panic!(
-                    "{:?} reads {} operands, which the sweep does not build",
-                    inst, other
-                ),
-            };
+                    .flat_map(|set| {
+                        every_shape().into_iter().map(move |v| {
+                            let mut set = set.clone();
+                            set.push(v);
+                            set
+                        })
+                    })
+                    .collect();
+            }
 
             let mut always = true;
             let mut witness = None;
@@ -1337,6 +1885,17 @@ mod totality_tests {
                 zero.clone(),
             ),
             (Instruction::Negate, vec![sym(7)], zero.clone()),
+            (
+                Instruction::BitXor,
+                vec![Value::Int(6), cs("3")],
+                zero.clone(),
+            ),
+            (
+                Instruction::ShiftLeft,
+                vec![Value::Int(6), Value::Int(-1)],
+                zero.clone(),
+            ),
+            (Instruction::Popcount, vec![Value::Bool(true)], zero.clone()),
             (
                 Instruction::Greater,
                 vec![sym(1), sym(2)],
@@ -1366,6 +1925,62 @@ mod totality_tests {
                 vec![cs("hi"), Value::Int(9)],
                 zero,
             ),
+            (
+                Instruction::ConstStringConcat,
+                vec![cs("hi"), sym(7)],
+                vec![cs("")],
+            ),
+            // Partly out of range is out of range: nothing is clamped.
+            (
+                Instruction::ConstStringSlice,
+                vec![cs("hi"), Value::Int(1), Value::Int(3)],
+                vec![cs("")],
+            ),
+            (
+                Instruction::ConstStringSlice,
+                vec![cs("hi"), Value::Int(2), Value::Int(1)],
+                vec![cs("")],
+            ),
+            // A surrogate is a number, but names no character.
+            (
+                Instruction::ConstStringFromChar,
+                vec![Value::Int(0xD800)],
+                vec![cs("")],
+            ),
+            (Instruction::IntToConstString, vec![cs("12")], vec![cs("")]),
+            (
+                Instruction::ConstStringLess,
+                vec![cs("a"), Value::Int(1)],
+                vec![Value::Bool(false)],
+            ),
+            (
+                Instruction::TupleGet(2),
+                vec![Value::Tuple(vec![sym(1), sym(2)])],
+                vec![unit()],
+            ),
+            (Instruction::TupleGet(0), vec![sym(1)], vec![unit()]),
+            (
+                Instruction::TupleSet(2),
+                vec![Value::Tuple(vec![sym(1), sym(2)]), sym(3)],
+                vec![unit()],
+            ),
+            (
+                Instruction::TupleConcat,
+                vec![Value::Tuple(vec![sym(1)]), sym(2)],
+                vec![unit()],
+            ),
+            (
+                Instruction::TupleSlice(1, 3),
+                vec![Value::Tuple(vec![sym(1), sym(2)])],
+                vec![unit()],
+            ),
+            (
+                Instruction::MapInsert,
+                vec![unit(), sym(1), sym(2)],
+                vec![map(&[])],
+            ),
+            (Instruction::MapRemove, vec![sym(1), sym(1)], vec![map(&[])]),
+            (Instruction::MapKeys, vec![Value::Int(3)], vec![unit()]),
         ]
     }
 
@@ -1442,6 +2057,70 @@ mod totality_tests {
                 vec![cs("hi"), Value::Int(0)],
                 vec![Value::Int('h' as i64)],
             ),
+            (
+                Instruction::ConstStringConcat,
+                vec![cs("caf"), cs("é!")],
+                vec![cs("café!")],
+            ),
+            // Indices count characters, not bytes.
+            (
+                Instruction::ConstStringSlice,
+                vec![cs("café!"), Value::Int(3), Value::Int(5)],
+                vec![cs("é!")],
+            ),
+            (
+                Instruction::ConstStringSlice,
+                vec![cs("hi"), Value::Int(2), Value::Int(2)],
+                vec![cs("")],
+            ),
+            (
+                Instruction::ConstStringFromChar,
+                vec![Value::Int('é' as i64)],
+                vec![cs("é")],
+            ),
+            (
+                Instruction::IntToConstString,
+                vec![Value::Int(-12)],
+                vec![cs("-12")],
+            ),
+            (
+                Instruction::ConstStringLess,
+                vec![cs("ab"), cs("b")],
+                vec![Value::Bool(true)],
+            ),
+            (
+                Instruction::ConstStringLess,
+                vec![cs("b"), cs("b")],
+                vec![Value::Bool(false)],
+            ),
+            (
+                Instruction::TupleGet(1),
+                vec![Value::Tuple(vec![sym(1), sym(2), sym(3)])],
+                vec![sym(2)],
+            ),
+            (
+                Instruction::TupleSet(1),
+                vec![Value::Tuple(vec![sym(1), sym(2), sym(3)]), sym(9)],
+                vec![Value::Tuple(vec![sym(1), sym(9), sym(3)])],
+            ),
+            (
+                Instruction::TupleConcat,
+                vec![
+                    Value::Tuple(vec![sym(1)]),
+                    Value::Tuple(vec![sym(2), sym(3)]),
+                ],
+                vec![Value::Tuple(vec![sym(1), sym(2), sym(3)])],
+            ),
+            (
+                Instruction::TupleSlice(1, 3),
+                vec![Value::Tuple(vec![sym(1), sym(2), sym(3)])],
+                vec![Value::Tuple(vec![sym(2), sym(3)])],
+            ),
+            (
+                Instruction::TupleSlice(2, 2),
+                vec![Value::Tuple(vec![sym(1), sym(2)])],
+                vec![unit()],
+            ),
         ];
         for (inst, good, want) in cases {
             assert_eq!(apply(&good, inst.clone()), want, "{:?} on {:?}", inst, good);
@@ -1793,6 +2472,7 @@ mod totality_tests {
                 ),
                 (Instruction::IsSymbol, matches!(a, Value::Symbol(_))),
                 (Instruction::IsTuple, matches!(a, Value::Tuple(_))),
+                (Instruction::IsMap, matches!(a, Value::Map(_))),
             ] {
                 assert_eq!(
                     apply(std::slice::from_ref(&a), inst.clone()),
@@ -1834,6 +2514,33 @@ mod totality_tests {
         );
     }
 
+    #[test]
+    fn bit_operations_read_an_int_as_its_64_bits() {
+        let int = |n: i64| vec![Value::Int(n)];
+        let on = |a: i64, b: i64, inst: Instruction| apply(&[Value::Int(a), Value::Int(b)], inst);
+        assert_eq!(on(12, 10, Instruction::BitAnd), int(8));
+        assert_eq!(on(12, 10, Instruction::BitOr), int(14));
+        assert_eq!(on(12, 10, Instruction::BitXor), int(6));
+        assert_eq!(apply(&[Value::Int(-1)], Instruction::Popcount), int(64));
+        assert_eq!(
+            apply(&[Value::Int(i64::MIN)], Instruction::Popcount),
+            int(1)
+        );
+
+        // A shift is multiplying or dividing by a power of two: left wraps as
+        // `multiply` does, and right rounds down, keeping the sign.
+        assert_eq!(on(3, 4, Instruction::ShiftLeft), int(48));
+        assert_eq!(on(1, 63, Instruction::ShiftLeft), int(i64::MIN));
+        assert_eq!(on(-7, 1, Instruction::ShiftRight), int(-4));
+        assert_eq!(on(5, 0, Instruction::ShiftRight), int(5));
+        // Past the width every bit is gone, and only the sign is left.
+        assert_eq!(on(3, 64, Instruction::ShiftLeft), int(0));
+        assert_eq!(on(3, i64::MAX, Instruction::ShiftRight), int(0));
+        assert_eq!(on(-3, 200, Instruction::ShiftRight), int(-1));
+        // A negative amount is no shift, and answers the `0` junk does.
+        assert_eq!(on(-3, -1, Instruction::ShiftRight), int(0));
+    }
+
     #[test]
     fn comparisons_answer_on_two_ints_and_answer_false_off_the_numbers() {
         assert_eq!(
@@ -1854,20 +2561,123 @@ mod totality_tests {
         );
     }
 
-    // -- What is still an error ---------------------------------------------
+    // -- Maps ---------------------------------------------------------------
 
     #[test]
-    fn underflow_is_still_an_error_because_it_is_structural() {
-        // The only way a run can still end early, and it is structural rather
-        // than about values: ruled out by arity checking, so a sentence that
-        // would underflow does not assemble in the first place.
-        for body in [
-            vec![Instruction::Drop],
-            vec![Instruction::Copy],
-            vec![Instruction::Swap],
-            vec![Instruction::Tuple(1)],
-            vec![Instruction::Add],
-        ] {
+    fn a_map_get_answers_a_result_carrying_what_it_found() {
+        let (ok, err) = (sym(100), sym(101));
+        let get = Instruction::MapGet(ok.clone(), err.clone());
+        let m = map(&[(cs("a"), Value::Int(1)), (sym(7), unit())]);
+        assert_eq!(
+            apply(&[m.clone(), cs("a")], get.clone()),
+            vec![Value::Tuple(vec![Value::Int(1), ok.clone()])]
+        );
+        assert_eq!(
+            apply(&[m.clone(), sym(7)], get.clone()),
+            vec![Value::Tuple(vec![unit(), ok])]
+        );
+        // A missing key is an `err` carrying the key, and a value that is no
+        // map is missing every key.
+        for (on, key) in [(m, cs("b")), (Value::Int(3), cs("a"))] {
+            assert_eq!(
+                apply(&[on.clone(), key.clone()], get.clone()),
+                vec![Value::Tuple(vec![key, err.clone()])],
+                "map_get on {:?}",
+                on
+            );
+        }
+    }
+
+    #[test]
+    fn maps_holding_the_same_entries_are_one_value_however_they_were_built() {
+        let build = |keys: &[i64]| {
+            let mut body = vec![Instruction::MapEmpty];
+            for &k in keys {
+                body.extend([
+                    Instruction::Push(Value::Int(k)),
+                    Instruction::Push(Value::Int(k * 10)),
+                    Instruction::MapInsert,
+                ]);
+            }
+            let mut library = Library::new();
+            library.sentences.push(body);
+            let mut vm = VM::new(library);
+            vm.execute(SentenceIndex::from(0)).unwrap();
+            vm.stack()[0].clone()
+        };
+        let forwards = build(&[1, 2, 3]);
+        assert_eq!(forwards, build(&[3, 1, 2]));
+        assert_eq!(forwards, build(&[2, 2, 3, 1]));
+        assert_eq!(forwards.to_string(), "{1: 10, 2: 20, 3: 30}");
+        assert_eq!(
+            apply(std::slice::from_ref(&forwards), Instruction::MapKeys),
+            vec![Value::Tuple(vec![
+                Value::Int(1),
+                Value::Int(2),
+                Value::Int(3)
+            ])]
+        );
+        // Keys of different kinds keep one order too: by kind, then content.
+        let mixed = map(&[(sym(1), unit()), (cs("z"), unit()), (Value::Int(9), unit())]);
+        assert_eq!(
+            apply(&[mixed], Instruction::MapKeys),
+            vec![Value::Tuple(vec![Value::Int(9), cs("z"), sym(1)])]
+        );
+    }
+
+    #[test]
+    fn inserting_and_removing_answer_a_new_map_and_leave_the_old_one() {
+        let m = map(&[(Value::Int(1), cs("one"))]);
+        assert_eq!(
+            apply(
+                &[m.clone(), Value::Int(1), cs("uno")],
+                Instruction::MapInsert
+            ),
+            vec![map(&[(Value::Int(1), cs("uno"))])]
+        );
+        assert_eq!(
+            apply(&[m.clone(), Value::Int(1)], Instruction::MapRemove),
+            vec![map(&[])]
+        );
+        assert_eq!(
+            apply(&[m.clone(), Value::Int(2)], Instruction::MapRemove),
+            vec![m.clone()]
+        );
+
+        // The copy under the insert is the map as it was.
+        let mut library = Library::new();
+        library.sentences.push(vec![
+            Instruction::Push(m.clone()),
+            Instruction::Copy,
+            Instruction::Push(Value::Int(2)),
+            Instruction::Push(cs("two")),
+            Instruction::MapInsert,
+        ]);
+        let mut vm = VM::new(library);
+        vm.execute(SentenceIndex::from(0)).unwrap();
+        assert_eq!(
+            vm.stack(),
+            &[
+                m,
+                map(&[(Value::Int(1), cs("one")), (Value::Int(2), cs("two"))])
+            ]
+        );
+    }
+
+    // -- What is still an error ---------------------------------------------
+
+    #[test]
+    fn underflow_is_still_an_error_because_it_is_structural() {
+        // The only way a run can still end early, and it is structural rather
+        // than about values: ruled out by arity checking, so a sentence that
+        // would underflow does not assemble in the first place.
+        for body in [
+            vec![Instruction::Drop],
+            vec![Instruction::Copy],
+            vec![Instruction::Swap],
+            vec![Instruction::Tuple(1)],
+            vec![Instruction::Add],
+        ] {
             assert!(
                 run(body.clone()).is_err(),
                 "{:?} on an empty stack should underflow",
@@ -2138,11 +2948,1599 @@ mod runtime_tests {
         if let Err(ref e) = run_res {
             println!("Hello world run failed: {}", e);
         }
-        assert!(run_res.is_ok());
+        // Done without exiting is done with 0.
+        assert_eq!(run_res, Ok(0));
 
         let output = runtime.environment.captured_output().unwrap();
         assert_eq!(output, "Hello, World!");
     }
+
+    /// A machine that writes back every character it reads, and stops at the
+    /// end of its input. Its state is the character waiting to be written,
+    /// -1 while it waits to read one, or -2 once stdin has ended.
+    const ECHO: &str = r#"
+        mod std {
+            mod io {
+                symbol io
+                mod stdout {
+                    symbol stdout
+                    symbol putch
+                }
+                mod stdin {
+                    symbol stdin
+                    symbol getch
+                    symbol eof
+                }
+            }
+        }
+
+        mod main {
+            export function init {
+                untuple 0
+                push -1
+            }
+
+            export function accept {
+                // Anything stdin offers is read while waiting, and nothing
+                // otherwise.
+                untuple 2
+                push -1
+                equal
+                drop 1
+            }
+
+            export function tau_reduce {
+                push false
+                tuple 2
+            }
+
+            export function emit {
+                copy
+                push 0
+                less
+                branch {
+                    drop 0
+                    push ()
+                    push false
+                    tuple 2
+                } {
+                    push ()
+                    roll 1
+                    tuple 2
+                    push crate::std::io::stdout::putch
+                    tuple 2
+                    push crate::std::io::stdout::stdout
+                    tuple 2
+                    push crate::std::io::io
+                    tuple 2
+                    push true
+                    tuple 2
+                }
+            }
+
+            export function process {
+                untuple 2
+                push -1
+                equal
+                branch {
+                    // Read: either stdin has ended, or it carries a char.
+                    copy
+                    push ((((), crate::std::io::stdin::eof), crate::std::io::stdin::stdin), crate::std::io::io)
+                    equal
+                    branch {
+                        drop 0
+                        push -2
+                    } {
+                        untuple 2
+                        drop 0
+                        untuple 2
+                        drop 0
+                        untuple 2
+                        drop 0
+                        untuple 2
+                        drop 1
+                    }
+                } {
+                    // Wrote the character: wait for the next.
+                    drop 0
+                    push -1
+                }
+            }
+
+            export function is_done {
+                push -2
+                equal
+            }
+
+            export function is_ready_to_finish {
+                drop 0
+                push false
+            }
+        }
+    "#;
+
+    /// Machines that each differ from [`ECHO`]'s `main` in one sentence, and
+    /// hand it the rest. They are assembled after it, by [`echo_variants`].
+    const ECHO_VARIANTS: &str = r#"
+        // Goes back to waiting once stdin has ended, where the echo stops.
+        mod deaf {
+            export function init {
+                jump super::main::init
+            }
+
+            export function accept {
+                jump super::main::accept
+            }
+
+            export function tau_reduce {
+                jump super::main::tau_reduce
+            }
+
+            export function emit {
+                jump super::main::emit
+            }
+
+            export function process {
+                jump super::main::process
+                copy
+                push -2
+                equal
+                branch {
+                    drop 0
+                    push -1
+                } {
+                }
+            }
+
+            export function is_done {
+                jump super::main::is_done
+            }
+
+            export function is_ready_to_finish {
+                jump super::main::is_ready_to_finish
+            }
+        }
+
+        // Deaf, and ready to finish whenever it waits.
+        mod patient {
+            export function init {
+                jump super::deaf::init
+            }
+
+            export function accept {
+                jump super::deaf::accept
+            }
+
+            export function tau_reduce {
+                jump super::deaf::tau_reduce
+            }
+
+            export function emit {
+                jump super::deaf::emit
+            }
+
+            export function process {
+                jump super::deaf::process
+            }
+
+            export function is_done {
+                jump super::deaf::is_done
+            }
+
+            export function is_ready_to_finish {
+                push -1
+                equal
+            }
+        }
+
+        // Writes back each character one higher than it was read.
+        mod off_by_one {
+            export function init {
+                jump super::main::init
+            }
+
+            export function accept {
+                jump super::main::accept
+            }
+
+            export function tau_reduce {
+                jump super::main::tau_reduce
+            }
+
+            export function emit {
+                copy
+                push 0
+                less
+                branch {
+                } {
+                    push 1
+                    add
+                }
+                jump super::main::emit
+            }
+
+            export function process {
+                jump super::main::process
+            }
+
+            export function is_done {
+                jump super::main::is_done
+            }
+
+            export function is_ready_to_finish {
+                jump super::main::is_ready_to_finish
+            }
+        }
+    "#;
+
+    /// [`ECHO`] with [`ECHO_VARIANTS`] beside it.
+    fn echo_variants() -> String {
+        format!("{}{}", ECHO, ECHO_VARIANTS)
+    }
+
+    #[tokio::test]
+    async fn test_runtime_echoes_scripted_input() {
+        let res = assemble(ECHO).unwrap();
+        let env = DefaultEnvironment::with_script(&res, "two\nlines\n");
+        let mut runtime = Runtime::new(res, "main", env).unwrap();
+
+        runtime.run().await.unwrap();
+        assert_eq!(runtime.environment.captured_output(), Some("two\nlines\n"));
+
+        // Stdin ends once, and then the environment has closed: a machine
+        // that goes back to waiting after `eof` had better be ready to finish.
+        let res = assemble(&echo_variants()).unwrap();
+        let env = DefaultEnvironment::with_script(&res, "x");
+        let mut runtime = Runtime::new(res, "deaf", env).unwrap();
+        let err = runtime.run().await.unwrap_err();
+        assert_eq!(
+            err,
+            "The environment has closed, and the machine is not ready to finish, in state -1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_runtime_diagnoses_a_stuck_machine() {
+        // Blocked: waiting after `eof`, and told which events would have
+        // moved it.
+        let res = assemble(&echo_variants()).unwrap();
+        let sym = |name: &str| res.symbols.get(name).unwrap().clone();
+        let alphabet = vec![
+            runtime::make_getch_event(
+                'a',
+                &sym("std::io::io"),
+                &sym("std::io::stdin::stdin"),
+                &sym("std::io::stdin::getch"),
+            ),
+            runtime::make_eof_event(
+                &sym("std::io::io"),
+                &sym("std::io::stdin::stdin"),
+                &sym("std::io::stdin::eof"),
+            ),
+        ];
+        let env = DefaultEnvironment::with_capture(&res);
+        let mut runtime = Runtime::new(res, "deaf", env).unwrap();
+        runtime.set_alphabet(alphabet);
+        assert_eq!(
+            runtime.run().await,
+            Err(
+                "The environment has closed, and the machine is not ready to finish, in state -1\n\
+                 Of the declared alphabet, it would accept:\n    \
+                 (((((), 97), std::io::stdin::getch), std::io::stdin::stdin), std::io::io)\n    \
+                 ((((), std::io::stdin::eof), std::io::stdin::stdin), std::io::io)"
+                    .to_string()
+            )
+        );
+
+        // Livelocked: tau_reduce flips a bool for ever.
+        let code = r#"
+            mod main {
+                export function init {
+                    untuple 0
+                    push false
+                }
+                export function accept {
+                    drop 0
+                    push false
+                }
+                export function tau_reduce {
+                    not
+                    push true
+                    tuple 2
+                }
+                export function emit {
+                    drop 0
+                    push ()
+                    push false
+                    tuple 2
+                }
+                export function process {
+                    untuple 2
+                    drop 1
+                }
+                export function is_done {
+                    drop 0
+                    push false
+                }
+                export function is_ready_to_finish {
+                    drop 0
+                    push false
+                }
+            }
+
+            // Goes from 7 to 2, and then between 2 and 3.
+            mod into_cycle {
+                export function init {
+                    untuple 0
+                    push 7
+                }
+                export function accept {
+                    jump super::main::accept
+                }
+                export function tau_reduce {
+                    push 3
+                    bit_and
+                    push 1
+                    bit_xor
+                    push true
+                    tuple 2
+                }
+                export function emit {
+                    jump super::main::emit
+                }
+                export function process {
+                    jump super::main::process
+                }
+                export function is_done {
+                    jump super::main::is_done
+                }
+                export function is_ready_to_finish {
+                    jump super::main::is_ready_to_finish
+                }
+            }
+        "#;
+        let res = assemble(code).unwrap();
+        let env = DefaultEnvironment::with_capture(&res);
+        let mut runtime = Runtime::new(res, "main", env).unwrap();
+        assert_eq!(
+            runtime.run().await,
+            Err(
+                "tau_reduce goes round a cycle of 2 states and never settles:\n    false\n    true"
+                    .to_string()
+            )
+        );
+
+        // The way into a cycle is not part of it.
+        let res = assemble(code).unwrap();
+        let env = DefaultEnvironment::with_capture(&res);
+        let mut runtime = Runtime::new(res, "into_cycle", env).unwrap();
+        assert_eq!(
+            runtime.run().await,
+            Err(
+                "tau_reduce goes round a cycle of 2 states and never settles:\n    2\n    3"
+                    .to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_runtime_finishes_once_the_environment_closes() {
+        // Echoes until stdin ends, and is ready to finish whenever it waits.
+        let variants = echo_variants();
+        let res = assemble(&variants).unwrap();
+        let env = DefaultEnvironment::with_script(&res, "ok");
+        let mut runtime = Runtime::new(res, "patient", env).unwrap();
+        assert_eq!(runtime.run().await, Ok(0));
+        assert_eq!(runtime.environment.captured_output(), Some("ok"));
+
+        // A timer still to fire keeps the environment open.
+        let res = assemble(CLOCK_SYMBOLS).unwrap();
+        let mut clock = Clock::virtual_time(&res);
+        assert!(clock.is_closed());
+        assert_eq!(clock.request(&set_timer(&res, 10, "later")), Ok(true));
+        assert!(!clock.is_closed());
+        clock.wait_for_event().await.unwrap();
+        assert!(clock.is_closed());
+
+        // Replaying the session closes the environment where it closed.
+        let res = assemble(&variants).unwrap();
+        let env = DefaultEnvironment::with_script(&res, "ok");
+        let mut runtime = Runtime::new(res, "patient", env).unwrap();
+        let log = Shared::default();
+        runtime.record_to(log.clone()).unwrap();
+        runtime.run().await.unwrap();
+        let recording = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            recording.lines().last(),
+            Some(r#"{"hook":"is_ready_to_finish","input":-1,"output":true}"#)
+        );
+        assert_eq!(replay(&variants, &recording).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_runtime_reads_input_from_a_file() {
+        let path = std::env::temp_dir().join(format!("hanoi-stdin-{}.txt", std::process::id()));
+        std::fs::write(&path, "héllo").unwrap();
+
+        let res = assemble(ECHO).unwrap();
+        let env = DefaultEnvironment::with_capture(&res).with_input(Input::File(path.clone()));
+        let mut runtime = Runtime::new(res, "main", env).unwrap();
+        let run_res = runtime.run().await;
+        std::fs::remove_file(&path).unwrap();
+
+        run_res.unwrap();
+        assert_eq!(runtime.environment.captured_output(), Some("héllo"));
+    }
+
+    const FS_SYMBOLS: &str = r#"
+        mod prelude {
+            symbol ok
+            symbol err
+        }
+        mod std {
+            mod io {
+                symbol io
+                mod stdout {
+                    symbol stdout
+                    symbol putch
+                }
+                mod stdin {
+                    symbol stdin
+                    symbol getch
+                    symbol eof
+                }
+            }
+            mod fs {
+                symbol fs
+                symbol open
+                symbol read
+                symbol write
+                symbol close
+                symbol list
+                symbol reply
+            }
+        }
+    "#;
+
+    /// A directory of its own for one test, empty to start with.
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("hanoi-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Sends `op` with `args` to `fs`, and unwraps the result it answers.
+    async fn ask(
+        library: &Library,
+        fs: &mut Filesystem,
+        op: &str,
+        args: Value,
+    ) -> Result<Value, String> {
+        let sym = |name: &str| library.symbols[name].clone();
+        let op = sym(&format!("std::fs::{}", op));
+        let request = Value::Tuple(vec![
+            Value::Tuple(vec![args, op.clone()]),
+            sym("std::fs::fs"),
+        ]);
+        fs.handle_event(request).await.unwrap();
+        let Value::Tuple(reply) = fs.wait_for_event().await.unwrap() else {
+            panic!("an answer is a tuple");
+        };
+        assert_eq!(reply[1], sym("std::fs::fs"));
+        let Value::Tuple(reply) = &reply[0] else {
+            panic!("an answer is fs.reply");
+        };
+        assert_eq!(reply[1], sym("std::fs::reply"));
+        let Value::Tuple(reply) = &reply[0] else {
+            panic!("an answer carries its op");
+        };
+        assert_eq!(reply[1], op);
+        let Value::Tuple(result) = &reply[0] else {
+            panic!("an answer carries a result");
+        };
+        if result[1] == sym("prelude::ok") {
+            Ok(result[0].clone())
+        } else {
+            assert_eq!(result[1], sym("prelude::err"));
+            match &result[0] {
+                Value::ConstString(reason) => Err(reason.clone()),
+                other => panic!("a reason is a const string, not {}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filesystem_writes_lists_and_reads_back() {
+        let res = assemble(FS_SYMBOLS).unwrap();
+        let dir = scratch_dir("fs-round-trip");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        let mut fs = Filesystem::new(&res, &dir).unwrap();
+        let s = |s: &str| Value::ConstString(s.to_string());
+        let pair = |a: Value, b: Value| Value::Tuple(vec![a, b]);
+
+        let out = ask(
+            &res,
+            &mut fs,
+            "open",
+            pair(s("sub/notes.txt"), Value::Bool(true)),
+        )
+        .await
+        .unwrap();
+        for chunk in ["naïve ", "notes"] {
+            let written = ask(&res, &mut fs, "write", pair(out.clone(), s(chunk))).await;
+            assert_eq!(written, Ok(Value::unit()));
+        }
+        assert_eq!(
+            ask(&res, &mut fs, "close", out.clone()).await,
+            Ok(Value::unit())
+        );
+        assert!(
+            ask(&res, &mut fs, "close", out)
+                .await
+                .unwrap_err()
+                .contains("no file")
+        );
+
+        assert_eq!(
+            ask(&res, &mut fs, "list", s(".")).await,
+            Ok(Value::Tuple(vec![s("sub")]))
+        );
+        assert_eq!(
+            ask(&res, &mut fs, "list", s("sub")).await,
+            Ok(Value::Tuple(vec![s("notes.txt")]))
+        );
+
+        // A chunk never splits a character: the 'ï' straddling the third
+        // byte comes whole.
+        let input = ask(
+            &res,
+            &mut fs,
+            "open",
+            pair(s("sub/notes.txt"), Value::Bool(false)),
+        )
+        .await
+        .unwrap();
+        let mut chunks = Vec::new();
+        loop {
+            let chunk = ask(&res, &mut fs, "read", pair(input.clone(), Value::Int(3)))
+                .await
+                .unwrap();
+            if chunk == s("") {
+                break;
+            }
+            chunks.push(chunk);
+        }
+        assert_eq!(chunks, vec![s("naï"), s("ve "), s("not"), s("es")]);
+        let refused = ask(&res, &mut fs, "write", pair(input, s("more"))).await;
+        assert!(refused.unwrap_err().contains("opened for reading"));
+
+        // A request of the wrong shape is the machine's mistake, not the
+        // world's: it is not answered, and stops the run.
+        let sym = |name: &str| res.symbols[name].clone();
+        let request = |op: &str, args| {
+            pair(
+                pair(args, sym(&format!("std::fs::{}", op))),
+                sym("std::fs::fs"),
+            )
+        };
+        assert_eq!(
+            fs.handle_event(request("open", s("sub/notes.txt"))).await,
+            Err("std::fs::open takes (path, writable), not \"sub/notes.txt\"".to_string())
+        );
+        let err = fs.handle_event(request("reply", Value::unit())).await;
+        assert!(
+            err.as_ref()
+                .is_err_and(|e| e.ends_with("is no request the file system takes")),
+            "{:?}",
+            err
+        );
+        assert!(fs.is_closed());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_filesystem_stays_inside_its_root() {
+        let res = assemble(FS_SYMBOLS).unwrap();
+        let dir = scratch_dir("fs-root");
+        std::fs::create_dir(dir.join("root")).unwrap();
+        std::fs::write(dir.join("secret.txt"), "kept out").unwrap();
+        let mut fs = Filesystem::new(&res, dir.join("root")).unwrap();
+        let s = |s: &str| Value::ConstString(s.to_string());
+        let pair = |a: Value, b: Value| Value::Tuple(vec![a, b]);
+
+        let secret = dir.join("secret.txt").display().to_string();
+        for path in ["../secret.txt", secret.as_str(), "a/../../secret.txt"] {
+            let refused = ask(&res, &mut fs, "open", pair(s(path), Value::Bool(false))).await;
+            assert!(
+                refused.unwrap_err().contains("outside the root"),
+                "{}",
+                path
+            );
+        }
+        assert!(ask(&res, &mut fs, "list", s("..")).await.is_err());
+        let missing = ask(
+            &res,
+            &mut fs,
+            "open",
+            pair(s("nothing"), Value::Bool(false)),
+        )
+        .await;
+        assert!(missing.is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&dir, dir.join("root/escape")).unwrap();
+            let refused = ask(
+                &res,
+                &mut fs,
+                "open",
+                pair(s("escape/secret.txt"), Value::Bool(false)),
+            )
+            .await;
+            assert!(refused.unwrap_err().contains("outside the root"));
+
+            // A link to nothing would have the file created where it points.
+            std::fs::create_dir(dir.join("elsewhere")).unwrap();
+            let escaped = dir.join("elsewhere/escaped.txt");
+            std::os::unix::fs::symlink(&escaped, dir.join("root/dangling")).unwrap();
+            let refused = ask(
+                &res,
+                &mut fs,
+                "open",
+                pair(s("dangling"), Value::Bool(true)),
+            )
+            .await;
+            assert!(refused.unwrap_err().contains("leads nowhere"));
+            assert!(!escaped.exists());
+
+            // A link that stays inside the root is followed.
+            std::fs::write(dir.join("root/inside.txt"), "fine").unwrap();
+            std::os::unix::fs::symlink(dir.join("root/inside.txt"), dir.join("root/alias"))
+                .unwrap();
+            let opened = ask(&res, &mut fs, "open", pair(s("alias"), Value::Bool(false))).await;
+            assert!(opened.is_ok());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_default_environment_answers_fs_beside_stdout() {
+        let res = assemble(FS_SYMBOLS).unwrap();
+        let dir = scratch_dir("fs-default");
+        let fs = Filesystem::new(&res, &dir).unwrap();
+        let mut env = DefaultEnvironment::with_capture(&res).with_fs(fs);
+        let sym = |name: &str| res.symbols[name].clone();
+        let pair = |a: Value, b: Value| Value::Tuple(vec![a, b]);
+
+        let putch = pair(
+            pair(
+                pair(
+                    pair(Value::unit(), Value::Int('!' as i64)),
+                    sym("std::io::stdout::putch"),
+                ),
+                sym("std::io::stdout::stdout"),
+            ),
+            sym("std::io::io"),
+        );
+        env.handle_event(putch).await.unwrap();
+        let list = pair(
+            pair(Value::ConstString(".".to_string()), sym("std::fs::list")),
+            sym("std::fs::fs"),
+        );
+        env.handle_event(list).await.unwrap();
+
+        // The answer comes first, and only then does stdin have its say.
+        let answer = env.wait_for_event().await.unwrap();
+        assert_eq!(
+            answer,
+            pair(
+                pair(
+                    pair(
+                        pair(Value::Tuple(vec![]), sym("prelude::ok")),
+                        sym("std::fs::list")
+                    ),
+                    sym("std::fs::reply"),
+                ),
+                sym("std::fs::fs"),
+            )
+        );
+        assert_eq!(
+            env.wait_for_event().await.unwrap(),
+            runtime::make_eof_event(
+                &sym("std::io::io"),
+                &sym("std::io::stdin::stdin"),
+                &sym("std::io::stdin::eof"),
+            )
+        );
+        assert_eq!(env.captured_output(), Some("!"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    const CLOCK_SYMBOLS: &str = r#"
+        mod std {
+            mod io {
+                symbol io
+                mod stdin {
+                    symbol stdin
+                    symbol getch
+                    symbol eof
+                }
+            }
+            mod time {
+                symbol time
+                symbol now
+                symbol set_timer
+                symbol timer_fired
+                symbol reply
+            }
+        }
+    "#;
+
+    /// The event `((args, op), time)`, a request to the clock.
+    fn clock_request(library: &Library, op: &str, args: Value) -> Value {
+        let op = library.symbols[&format!("std::time::{}", op)].clone();
+        Value::Tuple(vec![
+            Value::Tuple(vec![args, op]),
+            library.symbols["std::time::time"].clone(),
+        ])
+    }
+
+    /// The event `((token, timer_fired), time)`.
+    fn fired(library: &Library, token: &str) -> Value {
+        Value::Tuple(vec![
+            Value::Tuple(vec![
+                Value::ConstString(token.to_string()),
+                library.symbols["std::time::timer_fired"].clone(),
+            ]),
+            library.symbols["std::time::time"].clone(),
+        ])
+    }
+
+    /// The event `(((t, now), reply), time)`.
+    fn time_is(library: &Library, t: i64) -> Value {
+        Value::Tuple(vec![
+            Value::Tuple(vec![
+                Value::Tuple(vec![
+                    Value::Int(t),
+                    library.symbols["std::time::now"].clone(),
+                ]),
+                library.symbols["std::time::reply"].clone(),
+            ]),
+            library.symbols["std::time::time"].clone(),
+        ])
+    }
+
+    fn set_timer(library: &Library, delay: i64, token: &str) -> Value {
+        let args = Value::Tuple(vec![
+            Value::Int(delay),
+            Value::ConstString(token.to_string()),
+        ]);
+        clock_request(library, "set_timer", args)
+    }
+
+    #[tokio::test]
+    async fn test_virtual_clock_jumps_to_each_deadline() {
+        let res = assemble(CLOCK_SYMBOLS).unwrap();
+        let mut clock = Clock::virtual_time(&res);
+
+        clock
+            .handle_event(set_timer(&res, 30, "late"))
+            .await
+            .unwrap();
+        clock
+            .handle_event(set_timer(&res, 10, "first"))
+            .await
+            .unwrap();
+        clock
+            .handle_event(set_timer(&res, 10, "second"))
+            .await
+            .unwrap();
+        clock
+            .handle_event(clock_request(&res, "now", Value::unit()))
+            .await
+            .unwrap();
+
+        // Asking the time is answered before any timer fires, and takes none.
+        assert_eq!(clock.wait_for_event().await, Ok(time_is(&res, 0)));
+        assert_eq!(clock.wait_for_event().await, Ok(fired(&res, "first")));
+        assert_eq!(clock.now(), 10);
+        assert_eq!(clock.wait_for_event().await, Ok(fired(&res, "second")));
+
+        // A timer set at 10 for -5 is due at once, so before "late".
+        clock
+            .handle_event(set_timer(&res, -5, "overdue"))
+            .await
+            .unwrap();
+        assert_eq!(clock.wait_for_event().await, Ok(fired(&res, "overdue")));
+        assert_eq!(clock.now(), 10);
+        assert_eq!(clock.wait_for_event().await, Ok(fired(&res, "late")));
+        assert_eq!(clock.now(), 30);
+
+        let err = clock.wait_for_event().await.unwrap_err();
+        assert!(err.contains("no timer is set"), "{}", err);
+
+        // A delay that is no `Int` sets no timer, and stops the run.
+        let token = Value::ConstString("never".to_string());
+        let bad = clock_request(&res, "set_timer", Value::Tuple(vec![token.clone(), token]));
+        assert_eq!(
+            clock.handle_event(bad).await,
+            Err(
+                "std::time::set_timer takes (delay, token), not (\"never\", \"never\")".to_string()
+            )
+        );
+        assert!(!clock.has_timer());
+    }
+
+    #[tokio::test]
+    async fn test_real_clock_waits_for_its_timers() {
+        let res = assemble(CLOCK_SYMBOLS).unwrap();
+        let mut clock = Clock::real_time(&res);
+        let started = std::time::Instant::now();
+
+        clock
+            .handle_event(set_timer(&res, 20, "tick"))
+            .await
+            .unwrap();
+        // A wait given up on leaves the timer set.
+        let given_up =
+            tokio::time::timeout(std::time::Duration::from_millis(1), clock.wait_for_event()).await;
+        assert!(given_up.is_err());
+        assert!(clock.has_timer());
+        assert_eq!(clock.wait_for_event().await, Ok(fired(&res, "tick")));
+        assert!(started.elapsed() >= std::time::Duration::from_millis(20));
+        assert!(clock.now() >= 20);
+    }
+
+    /// A virtual clock's timer is due as soon as the machine waits, so it
+    /// comes before input that is just as ready.
+    #[tokio::test]
+    async fn test_default_environment_fires_virtual_timers_before_reading_input() {
+        let res = assemble(CLOCK_SYMBOLS).unwrap();
+        let mut env =
+            DefaultEnvironment::with_script(&res, "a").with_clock(Clock::virtual_time(&res));
+        let sym = |name: &str| res.symbols[name].clone();
+
+        env.handle_event(set_timer(&res, 100, "timeout"))
+            .await
+            .unwrap();
+        env.handle_event(clock_request(&res, "now", Value::unit()))
+            .await
+            .unwrap();
+        assert_eq!(env.wait_for_event().await, Ok(time_is(&res, 0)));
+        assert_eq!(env.wait_for_event().await, Ok(fired(&res, "timeout")));
+        assert_eq!(
+            env.wait_for_event().await,
+            Ok(runtime::make_getch_event(
+                'a',
+                &sym("std::io::io"),
+                &sym("std::io::stdin::stdin"),
+                &sym("std::io::stdin::getch"),
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_default_environment_races_a_real_timer_against_input() {
+        use std::io::Write as _;
+        use std::time::Duration;
+
+        let res = assemble(CLOCK_SYMBOLS).unwrap();
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let mut env = DefaultEnvironment::with_capture(&res)
+            .with_input(Input::Reader(Box::new(reader)))
+            .with_clock(Clock::real_time(&res));
+        let sym = |name: &str| res.symbols[name].clone();
+        let getch = |ch| {
+            Ok(runtime::make_getch_event(
+                ch,
+                &sym("std::io::io"),
+                &sym("std::io::stdin::stdin"),
+                &sym("std::io::stdin::getch"),
+            ))
+        };
+        async fn wait(env: &mut DefaultEnvironment) -> Result<Value, String> {
+            tokio::time::timeout(Duration::from_secs(10), env.wait_for_event())
+                .await
+                .expect("the wait never ended")
+        }
+
+        // Input that comes before the deadline is offered before it.
+        env.handle_event(set_timer(&res, 60_000, "timeout"))
+            .await
+            .unwrap();
+        writer.write_all(b"a\n").unwrap();
+        assert_eq!(wait(&mut env).await, getch('a'));
+        assert_eq!(wait(&mut env).await, getch('\n'));
+
+        // A deadline that comes before any input fires while it is read.
+        env.handle_event(set_timer(&res, 20, "soon")).await.unwrap();
+        assert_eq!(wait(&mut env).await, Ok(fired(&res, "soon")));
+
+        // And the line that was being read is not lost to the timer.
+        writer.write_all(b"b\n").unwrap();
+        assert_eq!(wait(&mut env).await, getch('b'));
+    }
+
+    /// Keeps every event it is handed, and never offers one.
+    #[derive(Clone, Default)]
+    struct Log(std::sync::Arc<std::sync::Mutex<Vec<Value>>>);
+
+    impl Environment for Log {
+        async fn handle_event(&mut self, event: Value) -> Result<(), String> {
+            self.0.lock().unwrap().push(event);
+            Ok(())
+        }
+
+        async fn wait_for_event(&mut self) -> Result<Value, String> {
+            Err("a log has nothing to offer".to_string())
+        }
+
+        fn is_closed(&self) -> bool {
+            true
+        }
+    }
+
+    /// Is never closed, and fails every wait with its message.
+    struct Broken(&'static str);
+
+    impl Environment for Broken {
+        async fn handle_event(&mut self, _event: Value) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn wait_for_event(&mut self) -> Result<Value, String> {
+            Err(self.0.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_router_hands_events_to_the_longest_route() {
+        // Stdin comes from the environment routed all of `io`, and stdout
+        // goes to the log routed `io.stdout`.
+        let res = assemble(ECHO).unwrap();
+        let log = Log::default();
+        let env = Router::new(&res)
+            .route(
+                &["std::io::io"],
+                DefaultEnvironment::with_script(&res, "hi"),
+            )
+            .unwrap()
+            .route(&["std::io::io", "std::io::stdout::stdout"], log.clone())
+            .unwrap();
+        let mut runtime = Runtime::new(res.clone(), "main", env).unwrap();
+        assert_eq!(runtime.run().await, Ok(0));
+        let printed: Vec<_> = log
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| {
+                runtime::extract_putch_char(
+                    event,
+                    &res.symbols.get("std::io::io").cloned(),
+                    &res.symbols.get("std::io::stdout::stdout").cloned(),
+                    &res.symbols.get("std::io::stdout::putch").cloned(),
+                )
+            })
+            .collect();
+        assert_eq!(printed, vec![Some('h'), Some('i')]);
+
+        let mut router = Router::new(&res)
+            .route(&["std::io::io"], Log::default())
+            .unwrap();
+        assert_eq!(
+            router.handle_event(Value::Int(7)).await,
+            Err("The router has no route for 7".to_string())
+        );
+        assert_eq!(
+            router
+                .route(&["std::io::io"], Log::default())
+                .err()
+                .unwrap(),
+            "std::io::io is already routed"
+        );
+        assert_eq!(
+            Router::new(&res)
+                .route(&["std::fs::fs"], Log::default())
+                .err()
+                .unwrap(),
+            "this program declares no symbol std::fs::fs"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_router_waits_on_every_handler_at_once() {
+        use std::io::Write as _;
+        use std::time::Duration;
+
+        async fn wait(router: &mut Router) -> Result<Value, String> {
+            tokio::time::timeout(Duration::from_secs(10), router.wait_for_event())
+                .await
+                .expect("the wait never ended")
+        }
+
+        // The clock answers while stdin, routed before it, has nothing to
+        // read, and stdin answers once it does.
+        let res = assemble(CLOCK_SYMBOLS).unwrap();
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let stdin =
+            DefaultEnvironment::with_capture(&res).with_input(Input::Reader(Box::new(reader)));
+        let mut router = Router::new(&res)
+            .route(&["std::io::io"], stdin)
+            .unwrap()
+            .route(&["std::time::time"], Clock::real_time(&res))
+            .unwrap();
+        router
+            .handle_event(set_timer(&res, 10, "tick"))
+            .await
+            .unwrap();
+        assert_eq!(wait(&mut router).await, Ok(fired(&res, "tick")));
+        assert!(!router.is_closed());
+        writer.write_all(b"a\n").unwrap();
+        assert_eq!(
+            wait(&mut router).await,
+            Ok(runtime::make_getch_event(
+                'a',
+                &res.symbols["std::io::io"],
+                &res.symbols["std::io::stdin::stdin"],
+                &res.symbols["std::io::stdin::getch"],
+            ))
+        );
+
+        // A handler that fails leaves the others to answer, and the router
+        // fails only once all of them have.
+        let mut router = Router::new(&res)
+            .route(&["std::io::io"], Broken("no input"))
+            .unwrap()
+            .route(&["std::time::time"], Clock::real_time(&res))
+            .unwrap();
+        router
+            .handle_event(set_timer(&res, 10, "tick"))
+            .await
+            .unwrap();
+        assert_eq!(wait(&mut router).await, Ok(fired(&res, "tick")));
+        let mut router = Router::new(&res)
+            .route(&["std::io::io"], Broken("no input"))
+            .unwrap()
+            .route(&["std::time::time"], Broken("no clock"))
+            .unwrap();
+        assert_eq!(
+            wait(&mut router).await,
+            Err("no input\nno clock".to_string())
+        );
+
+        // Closed handlers are not waited on, and once all of them are the
+        // router is closed.
+        let mut router = Router::new(&res)
+            .route(&["std::io::io"], Log::default())
+            .unwrap()
+            .route(&["std::time::time"], Clock::virtual_time(&res))
+            .unwrap();
+        router
+            .handle_event(set_timer(&res, 10, "tick"))
+            .await
+            .unwrap();
+        assert!(!router.is_closed());
+        assert_eq!(router.wait_for_event().await, Ok(fired(&res, "tick")));
+        assert!(router.is_closed());
+    }
+
+    /// Exits at once, with as many as the arguments it was given.
+    const EXITS: &str = r#"
+        mod std {
+            mod process {
+                symbol process
+                symbol exit
+            }
+        }
+
+        mod main {
+            export function init {
+                tuple_length
+            }
+
+            export sentence accept {
+                untuple 2
+                drop 0
+                drop 0
+                push false
+            }
+
+            export function tau_reduce {
+                push false
+                tuple 2
+            }
+
+            export function emit {
+                push crate::std::process::exit
+                tuple 2
+                push crate::std::process::process
+                tuple 2
+                push true
+                tuple 2
+            }
+
+            export function process {
+                untuple 2
+                drop 1
+            }
+
+            export function is_done {
+                drop 0
+                push false
+            }
+
+            export function is_ready_to_finish {
+                drop 0
+                push false
+            }
+        }
+    "#;
+
+    #[tokio::test]
+    async fn test_runtime_passes_args_and_exits_with_a_code() {
+        let res = assemble(EXITS).unwrap();
+        let env = DefaultEnvironment::with_capture(&res);
+        let mut runtime = Runtime::new(res.clone(), "main", env).unwrap();
+        runtime.set_args(vec!["one".to_string(), "two".to_string()]);
+        assert_eq!(runtime.run().await, Ok(2));
+
+        let env = DefaultEnvironment::with_capture(&res);
+        let mut runtime = Runtime::new(res, "main", env).unwrap();
+        assert_eq!(runtime.run().await, Ok(0));
+    }
+
+    /// Plays `script` against [`ECHO`], judging the run.
+    async fn play_echo(script: &str) -> Result<(), String> {
+        let res = assemble(ECHO).unwrap();
+        let script = Script::parse(script, &res)?;
+        let env = ScriptedEnvironment::new(&script);
+        let mut runtime = Runtime::new(res, &script.machine, env).unwrap();
+        let run = runtime.run().await;
+        runtime.environment.verdict(run)
+    }
+
+    #[tokio::test]
+    async fn test_script_drives_a_machine_event_by_event() {
+        let script = r#"
+            // 'h' in, 'h' out, and done once stdin ends.
+            machine main
+            offer (((((), 104), std::io::stdin::getch), std::io::stdin::stdin), std::io::io)
+            emit (((((), 104), std::io::stdout::putch), std::io::stdout::stdout), std::io::io)
+            offer ((((), std::io::stdin::eof), std::io::stdin::stdin), std::io::io)
+            done
+        "#;
+        assert_eq!(play_echo(script).await, Ok(()));
+
+        // The first place the emitted event parts from the expected one.
+        let wrong = script.replace("emit (((((), 104)", "emit (((((), 105)");
+        let err = play_echo(&wrong).await.unwrap_err();
+        assert!(
+            err.starts_with("line 5: expected the machine to emit"),
+            "{}",
+            err
+        );
+        assert!(
+            err.ends_with("first differ at [0][0][0][1]: 105 against 104"),
+            "{}",
+            err
+        );
+
+        let longer = script.replace("done", "offer prelude::none");
+        assert!(play_echo(&longer).await.is_err());
+        let early = script.replace("done", "emit std::io::io");
+        let err = play_echo(&early).await.unwrap_err();
+        assert_eq!(
+            err,
+            "the machine finished early: the script still expects line 7: emit std::io::io"
+        );
+
+        // Once the script ends, a machine that would wait has to be ready to
+        // finish, and the echo is not while stdin is open.
+        let short = script.replace("offer ((((), std::io::stdin::eof)", "// ");
+        let err = play_echo(&short.replace("done", "")).await.unwrap_err();
+        assert!(
+            err.starts_with("The environment has closed, and the machine is not ready to finish"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_script_values_read_as_they_print() {
+        let res = assemble(FS_SYMBOLS).unwrap();
+        let map = Value::Map([(Value::Int(1), Value::Bool(true))].into_iter().collect());
+        for value in [
+            Value::Tuple(vec![]),
+            Value::Tuple(vec![Value::Int(-7)]),
+            Value::ConstString("say \"hi\"\n\u{7f}".to_string()),
+            Value::Tuple(vec![map, res.symbols["std::fs::open"].clone()]),
+        ] {
+            let text = value.to_string();
+            let (read, rest) = script::value(&text, &res).unwrap();
+            assert_eq!(read, value, "{}", text);
+            assert_eq!(rest, "");
+        }
+
+        let err = Script::parse("machine main\noffer nowhere::at_all", &res).err();
+        assert_eq!(
+            err.as_deref(),
+            Some("line 2: no symbol is called `nowhere::at_all`")
+        );
+        let err = Script::parse("offer 1", &res).err();
+        assert_eq!(
+            err.as_deref(),
+            Some("a script starts by naming its `machine`")
+        );
+
+        // A `//` inside a string is part of it, and only the one after starts
+        // a comment.
+        let script = Script::parse(
+            "machine main // the echo\noffer \"http://x \\\"//\\\"\" // a \"url\"",
+            &res,
+        )
+        .unwrap();
+        assert_eq!(script.machine, "main");
+        assert_eq!(
+            ScriptedEnvironment::new(&script).wait_for_event().await,
+            Ok(Value::ConstString("http://x \"//\"".to_string()))
+        );
+    }
+
+    /// A writer whose bytes can still be read once a runtime owns it.
+    #[derive(Clone, Default)]
+    struct Shared(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs [`ECHO`] on `input`, recording it.
+    async fn record_echo(input: &str) -> String {
+        let res = assemble(ECHO).unwrap();
+        let env = DefaultEnvironment::with_script(&res, input);
+        let mut runtime = Runtime::new(res, "main", env).unwrap();
+        let log = Shared::default();
+        runtime.record_to(log.clone()).unwrap();
+        runtime.run().await.unwrap();
+        String::from_utf8(log.0.lock().unwrap().clone()).unwrap()
+    }
+
+    async fn replay(code: &str, recording: &str) -> Result<(), String> {
+        let res = assemble(code).unwrap();
+        let env = ReplayEnvironment::parse(recording, &res)?;
+        let machine = env.machine().to_string();
+        let mut runtime = Runtime::new(res, &machine, env).unwrap();
+        let run = runtime.run().await;
+        runtime.environment.verdict(run)
+    }
+
+    #[tokio::test]
+    async fn test_a_recording_replays_to_the_same_session() {
+        let recording = record_echo("hi").await;
+        assert_eq!(recording, record_echo("hi").await);
+
+        let mut lines = recording.lines();
+        assert_eq!(
+            lines.next(),
+            Some(r#"{"hanoi_recording":1,"machine":"main"}"#)
+        );
+        assert_eq!(
+            lines.next(),
+            Some(r#"{"hook":"init","input":[],"output":-1}"#)
+        );
+        assert!(recording.contains(
+            r#"{"offered":[[[[[],104],{"symbol":"std::io::stdin::getch"}],{"symbol":"std::io::stdin::stdin"}],{"symbol":"std::io::io"}]}"#
+        ));
+
+        assert_eq!(replay(ECHO, &recording).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_a_replay_reports_the_first_divergence() {
+        let recording = record_echo("hi").await;
+
+        // Writing each character back one higher changes what emit answers
+        // before it changes any event.
+        let res = assemble(&echo_variants()).unwrap();
+        let env = ReplayEnvironment::parse(&recording, &res).unwrap();
+        let mut runtime = Runtime::new(res, "off_by_one", env).unwrap();
+        let run = runtime.run().await;
+        let err = runtime.environment.verdict(run).unwrap_err();
+        assert!(err.contains("what emit answered was"), "{}", err);
+        assert!(
+            err.ends_with("at [0][0][0][0][1]: 104 against 105"),
+            "{}",
+            err
+        );
+
+        // A recording cut short leaves the machine waiting where it stopped,
+        // which a replay reproduces.
+        let cut: String = recording
+            .lines()
+            .take_while(|line| !line.contains("eof"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert_eq!(replay(ECHO, &cut).await, Ok(()));
+
+        let err = replay(ECHO, "{\"hanoi_recording\":2,\"machine\":\"main\"}").await;
+        assert!(err.unwrap_err().starts_with("line 1 of the recording"));
+    }
+
+    /// Counts 0, 1, 2 out on `chan::put`, and is done.
+    const SOURCE: &str = r#"
+        mod chan {
+            symbol put
+        }
+
+        mod source {
+            export function init {
+                untuple 0
+                push 0
+            }
+
+            export function accept {
+                drop 0
+                push false
+            }
+
+            export function tau_reduce {
+                push false
+                tuple 2
+            }
+
+            export function emit {
+                copy
+                push 3
+                less
+                branch {
+                    push crate::chan::put
+                    tuple 2
+                    push true
+                    tuple 2
+                } {
+                    drop 0
+                    push ()
+                    push false
+                    tuple 2
+                }
+            }
+
+            export function process {
+                untuple 2
+                drop 1
+                push 1
+                add
+            }
+
+            export function is_done {
+                push 3
+                equal
+            }
+
+            export function is_ready_to_finish {
+                push 3
+                equal
+            }
+        }
+    "#;
+
+    /// Takes a digit on `inbox::put` and prints it, for ever. State -1 is
+    /// waiting, and any other is the character to print.
+    const SINK: &str = r#"
+        mod std {
+            mod io {
+                symbol io
+                mod stdout {
+                    symbol stdout
+                    symbol putch
+                }
+            }
+        }
+
+        mod inbox {
+            symbol put
+            symbol other
+        }
+
+        mod sink {
+            export function init {
+                untuple 0
+                push -1
+            }
+
+            export function accept {
+                untuple 2
+                push -1
+                equal
+                branch {
+                    untuple 2
+                    push crate::inbox::put
+                    equal
+                    drop 1
+                } {
+                    drop 0
+                    push false
+                }
+            }
+
+            export function tau_reduce {
+                push false
+                tuple 2
+            }
+
+            export function emit {
+                copy
+                push 0
+                less
+                branch {
+                    drop 0
+                    push ()
+                    push false
+                    tuple 2
+                } {
+                    push ()
+                    roll 1
+                    tuple 2
+                    push crate::std::io::stdout::putch
+                    tuple 2
+                    push crate::std::io::stdout::stdout
+                    tuple 2
+                    push crate::std::io::io
+                    tuple 2
+                    push true
+                    tuple 2
+                }
+            }
+
+            export function process {
+                untuple 2
+                push -1
+                equal
+                branch {
+                    untuple 2
+                    drop 0
+                    push 48
+                    add
+                } {
+                    drop 0
+                    push -1
+                }
+            }
+
+            export function is_done {
+                drop 0
+                push false
+            }
+
+            export function is_ready_to_finish {
+                push -1
+                equal
+            }
+        }
+    "#;
+
+    /// [`SOURCE`] into [`SINK`], each compiled on its own, with the sink's
+    /// `port` on the channel.
+    fn counting_system(port: &str) -> System<DefaultEnvironment> {
+        let source = assemble(SOURCE).unwrap();
+        let sink = assemble(SINK).unwrap();
+        let mut system = System::new(&sink, DefaultEnvironment::with_capture(&sink));
+        system.add_process("source", source, "source").unwrap();
+        system.add_process("sink", sink, "sink").unwrap();
+        system
+            .add_channel("numbers", ("source", "chan::put"), ("sink", port))
+            .unwrap();
+        system
+    }
+
+    #[tokio::test]
+    async fn test_system_hands_events_across_channels() {
+        let mut system = counting_system("inbox::put");
+        assert_eq!(system.run().await, Ok(0));
+        assert_eq!(system.environment.captured_output(), Some("012"));
+
+        let mut system = counting_system("inbox::put");
+        assert_eq!(
+            system.add_channel("again", ("source", "chan::put"), ("sink", "inbox::other")),
+            Err("source's port chan::put is already on a channel".to_string())
+        );
+        assert_eq!(
+            system.add_external("sink", "nowhere"),
+            Err("sink has no symbol nowhere".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_system_exits_with_the_code_of_a_machine() {
+        // Every machine is given the arguments, and the first to exit ends
+        // the system there, before the sink has printed anything.
+        let mut system = counting_system("inbox::put");
+        system
+            .add_process("exits", assemble(EXITS).unwrap(), "main")
+            .unwrap();
+        system.set_args(vec!["one".to_string(), "two".to_string()]);
+        assert_eq!(system.run().await, Ok(2));
+        assert_eq!(system.environment.captured_output(), Some(""));
+    }
+
+    #[tokio::test]
+    async fn test_system_reports_where_it_is_stuck() {
+        // The sink only takes `inbox::put`, so the source can never hand its
+        // first number over.
+        let mut system = counting_system("inbox::other");
+        assert_eq!(
+            system.run().await,
+            Err("The system is stuck, and the environment has closed:\n    \
+                 source, in state 0, is emitting (0, chan::put) on channel numbers"
+                .to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_system_loads_a_topology_file() {
+        let dir = scratch_dir("system");
+        std::fs::write(dir.join("echo.hana"), ECHO).unwrap();
+        std::fs::write(dir.join("source.hana"), SOURCE).unwrap();
+        std::fs::write(dir.join("sink.hana"), SINK).unwrap();
+        std::fs::write(
+            dir.join("count.system"),
+            "// The echo reads stdin, while the sink prints what it is given.\n\
+             process echo echo.hana main\n\
+             process source source.hana source\n\
+             process sink sink.hana sink // prints what it is given\n\
+             \n\
+             channel numbers source chan::put sink inbox::put\n\
+             external echo std::io::io\n",
+        )
+        .unwrap();
+
+        let mut system = System::load(&dir.join("count.system"), |library| {
+            DefaultEnvironment::with_script(library, "hi")
+        })
+        .unwrap();
+        assert_eq!(system.run().await, Ok(0));
+        // The environment is only waited on once nothing inside can move.
+        assert_eq!(system.environment.captured_output(), Some("012hi"));
+
+        std::fs::write(
+            dir.join("bad.system"),
+            "process echo echo.hana main\nchannel x\n",
+        )
+        .unwrap();
+        let err = System::load(&dir.join("bad.system"), DefaultEnvironment::with_capture);
+        assert!(err.is_err_and(|e| e.ends_with("bad.system:2: expected `channel NAME A PORT B PORT` or `external NAME PORT`, found `channel x`")));
+
+        // The environment has to know every `std` symbol a machine could
+        // hand it, and no one library here declares them all.
+        std::fs::write(dir.join("exits.hana"), EXITS).unwrap();
+        std::fs::write(
+            dir.join("mixed.system"),
+            "process exits exits.hana main\nprocess sink sink.hana sink\n",
+        )
+        .unwrap();
+        let err = System::load(&dir.join("mixed.system"), DefaultEnvironment::with_capture).err();
+        assert!(
+            err.as_ref().is_some_and(|e| e.ends_with(
+                "no process declares every `std` symbol the system uses, so no library \
+                 can serve as the environment's: sink declares std::io::io, and exits does not"
+            )),
+            "{:?}",
+            err
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
 
 /// What the movement macros mean, measured against the machine.
@@ -0,0 +1,344 @@
+//! Recording a runtime session, and replaying it.
+//!
+//! A recording is JSON Lines. The first line says what it is:
+//!
+//! ```text
+//! {"hanoi_recording":1,"machine":"main"}
+//! ```
+//!
+//! and each line after it is one thing that happened, in the order it
+//! happened:
+//!
+//! ```text
+//! {"hook":"process","input":[…],"output":…}
+//! {"emitted":…}
+//! {"offered":…}
+//! ```
+//!
+//! A hook line is one call of a machine sentence, with the value it was given
+//! and the one it answered; `emitted` is an event the machine handed the
+//! environment, and `offered` one the environment handed the machine. Values
+//! are written as JSON: an `Int` as a number, a `Bool` as itself, a const
+//! string as a string, a tuple as an array, a symbol as `{"symbol":path}` and
+//! a map as `{"map":[[key,value],…]}`. Keys are written in the order above
+//! and nothing is written that is not needed, so one session records the same
+//! way every time.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use bytecode::json::{self, Json};
+use bytecode::{Library, Value};
+
+use crate::runtime::Environment;
+use crate::script::difference;
+
+/// The version a recording's first line carries.
+const VERSION: i64 = 1;
+
+/// One line of a recording, after the first.
+pub(crate) enum Record {
+    Hook {
+        hook: String,
+        input: Value,
+        output: Value,
+    },
+    Emitted(Value),
+    Offered(Value),
+}
+
+impl Record {
+    pub(crate) fn to_json(&self) -> String {
+        match self {
+            Record::Hook {
+                hook,
+                input,
+                output,
+            } => format!(
+                "{{\"hook\":{},\"input\":{},\"output\":{}}}",
+                string(hook),
+                value(input),
+                value(output)
+            ),
+            Record::Emitted(v) => format!("{{\"emitted\":{}}}", value(v)),
+            Record::Offered(v) => format!("{{\"offered\":{}}}", value(v)),
+        }
+    }
+
+    fn parse(line: &str, library: &Library) -> Result<Record, String> {
+        let fields = object(parse(line)?)?;
+        match &fields[..] {
+            [(k, hook), (i, input), (o, output)]
+                if k == "hook" && i == "input" && o == "output" =>
+            {
+                let Json::Str(hook) = hook else {
+                    return Err("a hook is named by a string".to_string());
+                };
+                Ok(Record::Hook {
+                    hook: hook.clone(),
+                    input: to_value(input, library)?,
+                    output: to_value(output, library)?,
+                })
+            }
+            [(k, v)] if k == "emitted" => Ok(Record::Emitted(to_value(v, library)?)),
+            [(k, v)] if k == "offered" => Ok(Record::Offered(to_value(v, library)?)),
+            _ => Err("expected a hook, `emitted` or `offered` line".to_string()),
+        }
+    }
+}
+
+/// The first line of a recording of `machine`.
+pub(crate) fn header(machine: &str) -> String {
+    format!(
+        "{{\"hanoi_recording\":{},\"machine\":{}}}",
+        VERSION,
+        string(machine)
+    )
+}
+
+/// An [`Environment`] that plays a recording back: it offers the recorded
+/// events, and checks every hook the machine runs and every event it emits
+/// against the recording, failing at the first that differs.
+pub struct ReplayEnvironment {
+    machine: String,
+    /// What is still to happen, with the line of the recording it is on.
+    records: VecDeque<(usize, Record)>,
+    ran_out: bool,
+}
+
+impl ReplayEnvironment {
+    /// Reads a recording, naming its symbols out of `library`.
+    pub fn parse(text: &str, library: &Library) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let (_, first) = lines.next().ok_or("a recording is never empty")?;
+        let at = |n: usize| move |e: String| format!("line {} of the recording: {}", n + 1, e);
+        let fields = parse(first).and_then(object).map_err(at(0))?;
+        let machine = match &fields[..] {
+            [(k, Json::Int(VERSION)), (m, Json::Str(machine))]
+                if k == "hanoi_recording" && m == "machine" =>
+            {
+                machine.clone()
+            }
+            _ => {
+                return Err(at(0)(format!(
+                    "expected {{\"hanoi_recording\":{},\"machine\":…}}",
+                    VERSION
+                )));
+            }
+        };
+        let mut records = VecDeque::new();
+        for (n, line) in lines {
+            records.push_back((n + 1, Record::parse(line, library).map_err(at(n))?));
+        }
+        Ok(Self {
+            machine,
+            records,
+            ran_out: false,
+        })
+    }
+
+    /// The machine the recording is of.
+    pub fn machine(&self) -> &str {
+        &self.machine
+    }
+
+    /// What the replay came to: `run` is what
+    /// [`Runtime::run`](crate::Runtime::run) answered. A recording that ends
+    /// with the machine waiting is one whose session stopped there, and a
+    /// replay that gets that far has reproduced all of it.
+    pub fn verdict<T>(&self, run: Result<T, String>) -> Result<(), String> {
+        match run {
+            Err(_) if self.ran_out => Ok(()),
+            Err(e) => Err(e),
+            Ok(_) => match self.records.front() {
+                None => Ok(()),
+                Some((line, _)) => Err(format!(
+                    "the machine finished, where the recording goes on at line {}",
+                    line
+                )),
+            },
+        }
+    }
+
+    fn diverged(line: usize, what: &str, expected: &Value, found: &Value) -> String {
+        format!(
+            "line {} of the recording: {} was\n    {}\nand is now\n    {}{}",
+            line,
+            what,
+            expected,
+            found,
+            difference(expected, found)
+        )
+    }
+}
+
+impl Environment for ReplayEnvironment {
+    async fn handle_event(&mut self, event: Value) -> Result<(), String> {
+        match self.records.pop_front() {
+            Some((_, Record::Emitted(recorded))) if recorded == event => Ok(()),
+            Some((line, Record::Emitted(recorded))) => {
+                Err(Self::diverged(line, "the event emitted", &recorded, &event))
+            }
+            Some((line, _)) => Err(format!(
+                "line {} of the recording: the machine emitted {}, which it did not do here",
+                line, event
+            )),
+            None => Err(format!(
+                "the machine emitted {} after the recording ended",
+                event
+            )),
+        }
+    }
+
+    async fn wait_for_event(&mut self) -> Result<Value, String> {
+        match self.records.pop_front() {
+            Some((_, Record::Offered(event))) => Ok(event),
+            Some((line, _)) => Err(format!(
+                "line {} of the recording: the machine is waiting for an event, which it did not do here",
+                line
+            )),
+            None => {
+                self.ran_out = true;
+                Err("the recording has ended, and the machine is waiting".to_string())
+            }
+        }
+    }
+
+    /// The environment closed where the runtime went on to ask whether the
+    /// machine was ready to finish, which it asks only then.
+    fn is_closed(&self) -> bool {
+        matches!(
+            self.records.front(),
+            Some((_, Record::Hook { hook, .. })) if hook == "is_ready_to_finish"
+        )
+    }
+
+    /// Every hook was recorded, so every one is checked.
+    fn observes_hooks(&self) -> bool {
+        true
+    }
+
+    fn observe_hook(&mut self, hook: &str, input: &Value, output: &Value) -> Result<(), String> {
+        match self.records.pop_front() {
+            Some((
+                line,
+                Record::Hook {
+                    hook: recorded,
+                    input: recorded_input,
+                    output: recorded_output,
+                },
+            )) if recorded == hook => {
+                if &recorded_input != input {
+                    Err(Self::diverged(
+                        line,
+                        &format!("what {} was given", hook),
+                        &recorded_input,
+                        input,
+                    ))
+                } else if &recorded_output != output {
+                    Err(Self::diverged(
+                        line,
+                        &format!("what {} answered", hook),
+                        &recorded_output,
+                        output,
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            Some((line, _)) => Err(format!(
+                "line {} of the recording: the machine ran {}, which it did not do here",
+                line, hook
+            )),
+            None => Err(format!(
+                "the machine ran {} after the recording ended",
+                hook
+            )),
+        }
+    }
+}
+
+fn string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn value(v: &Value) -> String {
+    let list = |items: Vec<String>| format!("[{}]", items.join(","));
+    match v {
+        Value::Bool(b) => b.to_string(),
+        Value::Int(n) => n.to_string(),
+        Value::ConstString(s) => string(s),
+        Value::Tuple(elements) => list(elements.iter().map(value).collect()),
+        Value::Symbol(sym) => format!("{{\"symbol\":{}}}", string(&sym.path)),
+        Value::Map(entries) => format!(
+            "{{\"map\":{}}}",
+            list(
+                entries
+                    .iter()
+                    .map(|(k, v)| format!("[{},{}]", value(k), value(v)))
+                    .collect()
+            )
+        ),
+    }
+}
+
+fn object(json: Json) -> Result<Vec<(String, Json)>, String> {
+    match json {
+        Json::Object(fields) => Ok(fields),
+        _ => Err("expected an object".to_string()),
+    }
+}
+
+fn to_value(json: &Json, library: &Library) -> Result<Value, String> {
+    Ok(match json {
+        Json::Bool(b) => Value::Bool(*b),
+        Json::Int(n) => Value::Int(*n),
+        Json::Str(s) => Value::ConstString(s.clone()),
+        Json::Array(elements) => Value::Tuple(
+            elements
+                .iter()
+                .map(|e| to_value(e, library))
+                .collect::<Result<_, _>>()?,
+        ),
+        Json::Object(fields) => match &fields[..] {
+            [(k, Json::Str(path))] if k == "symbol" => library
+                .symbols
+                .get(path)
+                .cloned()
+                .ok_or_else(|| format!("no symbol is called `{}`", path))?,
+            [(k, Json::Array(entries))] if k == "map" => {
+                let mut map = BTreeMap::new();
+                for entry in entries {
+                    let Json::Array(pair) = entry else {
+                        return Err("a map entry is a [key, value] pair".to_string());
+                    };
+                    let [key, v] = &pair[..] else {
+                        return Err("a map entry is a [key, value] pair".to_string());
+                    };
+                    map.insert(to_value(key, library)?, to_value(v, library)?);
+                }
+                Value::Map(map)
+            }
+            _ => return Err("expected a symbol or a map".to_string()),
+        },
+    })
+}
+
+fn parse(text: &str) -> Result<Json, String> {
+    json::parse(text).map_err(|e| e.to_string())
+}
@@ -0,0 +1,244 @@
+//! An environment made of other environments, each handed the events under
+//! its own tags.
+//!
+//! A route is a tag path: the outermost tags of an event in path notation,
+//! written as the symbol paths the library keys them under, outermost first.
+//! `["std::io::io"]` takes every `io.…` event, and `["std::io::io",
+//! "std::io::stdout::stdout"]` only the `io.stdout.…` ones. An event the
+//! machine emits goes to the handler whose route matches the most of its
+//! tags, and is handed over whole, so the devices this crate already has can
+//! be routed to as they are:
+//!
+//! ```text
+//! let env = Router::new(&library)
+//!     .route(&["std::io::io"], DefaultEnvironment::new(&library))?
+//!     .route(&["std::fs::fs"], Filesystem::new(&library, root)?)?
+//!     .route(&["std::time::time"], Clock::real_time(&library))?;
+//! ```
+//!
+//! Waiting waits on every handler that has not closed at once, and offers
+//! whatever the first of them answers. Handlers that answer together are
+//! taken in the order they were routed, and the rest are dropped mid-wait,
+//! so a handler has to lose nothing when its `wait_for_event` is dropped. A
+//! handler that fails has nothing to offer this time, and the wait goes on
+//! with the rest: the router only fails once every one of them has.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use bytecode::{Library, Value};
+
+use crate::runtime::Environment;
+
+type Boxed<'a, T, E = String> = Pin<Box<dyn Future<Output = Result<T, E>> + 'a>>;
+
+/// [`Environment`], in a form that can be boxed: its `async fn`s are what
+/// keep it from being a trait object.
+trait Handler {
+    fn handle_event(&mut self, event: Value) -> Boxed<'_, ()>;
+    fn wait_for_event(&mut self) -> Boxed<'_, Value>;
+    fn is_closed(&self) -> bool;
+    fn observes_hooks(&self) -> bool;
+    fn observe_hook(&mut self, hook: &str, input: &Value, output: &Value) -> Result<(), String>;
+    fn refused(&self, event: &Value) -> String;
+}
+
+impl<E: Environment> Handler for E {
+    fn handle_event(&mut self, event: Value) -> Boxed<'_, ()> {
+        Box::pin(Environment::handle_event(self, event))
+    }
+
+    fn wait_for_event(&mut self) -> Boxed<'_, Value> {
+        Box::pin(Environment::wait_for_event(self))
+    }
+
+    fn is_closed(&self) -> bool {
+        Environment::is_closed(self)
+    }
+
+    fn observes_hooks(&self) -> bool {
+        Environment::observes_hooks(self)
+    }
+
+    fn observe_hook(&mut self, hook: &str, input: &Value, output: &Value) -> Result<(), String> {
+        Environment::observe_hook(self, hook, input, output)
+    }
+
+    fn refused(&self, event: &Value) -> String {
+        Environment::refused(self, event)
+    }
+}
+
+struct Route {
+    tags: Vec<Value>,
+    handler: Box<dyn Handler>,
+}
+
+/// An [`Environment`] that hands each event to the handler routed its tags,
+/// and offers whatever any handler has to give.
+pub struct Router {
+    symbols: HashMap<String, Value>,
+    routes: Vec<Route>,
+    /// The route that offered the last event, to say why it was refused.
+    offered_by: Option<usize>,
+}
+
+impl Router {
+    /// A router with no routes, for a program compiled to `library`.
+    pub fn new(library: &Library) -> Self {
+        Self {
+            symbols: library.symbols.clone(),
+            routes: Vec::new(),
+            offered_by: None,
+        }
+    }
+
+    /// Hands the events under `tags`, outermost first, to `handler`.
+    pub fn route(
+        mut self,
+        tags: &[&str],
+        handler: impl Environment + 'static,
+    ) -> Result<Self, String> {
+        if tags.is_empty() {
+            return Err("a route names at least one tag".to_string());
+        }
+        let tags = tags
+            .iter()
+            .map(|path| {
+                self.symbols
+                    .get(*path)
+                    .cloned()
+                    .ok_or_else(|| format!("this program declares no symbol {}", path))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if self.routes.iter().any(|route| route.tags == tags) {
+            return Err(format!(
+                "{} is already routed",
+                tags.iter()
+                    .map(|tag| tag.to_string())
+                    .collect::<Vec<_>>()
+                    .join(".")
+            ));
+        }
+        self.routes.push(Route {
+            tags,
+            handler: Box::new(handler),
+        });
+        Ok(self)
+    }
+}
+
+/// Whether `event` is under `tags` in path notation: its outermost tag is
+/// the first, the one inside that the second, and so on.
+fn is_under(mut event: &Value, tags: &[Value]) -> bool {
+    for tag in tags {
+        let Value::Tuple(pair) = event else {
+            return false;
+        };
+        let [inner, outer] = &pair[..] else {
+            return false;
+        };
+        if outer != tag {
+            return false;
+        }
+        event = inner;
+    }
+    true
+}
+
+/// The first event any of `handlers` offers, and the route it came from, or
+/// else what each of them failed with, in route order.
+fn first_of<'a, 'h>(
+    handlers: &'a mut [(usize, &'h mut Box<dyn Handler>)],
+) -> Boxed<'a, (usize, Value), Vec<String>>
+where
+    'h: 'a,
+{
+    Box::pin(async move {
+        match handlers {
+            [] => unreachable!("only handlers that are open are waited on"),
+            [(route, only)] => match only.wait_for_event().await {
+                Ok(event) => Ok((*route, event)),
+                Err(e) => Err(vec![e]),
+            },
+            // Whichever side fails first, the other is waited on alone.
+            [(route, first), rest @ ..] => tokio::select! {
+                biased;
+                event = first.wait_for_event() => match event {
+                    Ok(event) => Ok((*route, event)),
+                    Err(e) => first_of(rest).await.map_err(|mut errors| {
+                        errors.insert(0, e);
+                        errors
+                    }),
+                },
+                event = first_of(rest) => match event {
+                    Ok(event) => Ok(event),
+                    Err(mut errors) => match first.wait_for_event().await {
+                        Ok(event) => Ok((*route, event)),
+                        Err(e) => {
+                            errors.insert(0, e);
+                            Err(errors)
+                        }
+                    },
+                },
+            },
+        }
+    })
+}
+
+impl Environment for Router {
+    async fn handle_event(&mut self, event: Value) -> Result<(), String> {
+        let route = self
+            .routes
+            .iter_mut()
+            .filter(|route| is_under(&event, &route.tags))
+            .max_by_key(|route| route.tags.len())
+            .ok_or_else(|| format!("The router has no route for {}", event))?;
+        route.handler.handle_event(event).await
+    }
+
+    async fn wait_for_event(&mut self) -> Result<Value, String> {
+        let mut open: Vec<_> = self
+            .routes
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, route)| !route.handler.is_closed())
+            .map(|(index, route)| (index, &mut route.handler))
+            .collect();
+        if open.is_empty() {
+            return Err("Every handler of the router has closed".to_string());
+        }
+        let (route, event) = first_of(&mut open)
+            .await
+            .map_err(|errors| errors.join("\n"))?;
+        self.offered_by = Some(route);
+        Ok(event)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.routes.iter().all(|route| route.handler.is_closed())
+    }
+
+    fn observes_hooks(&self) -> bool {
+        self.routes
+            .iter()
+            .any(|route| route.handler.observes_hooks())
+    }
+
+    fn observe_hook(&mut self, hook: &str, input: &Value, output: &Value) -> Result<(), String> {
+        for route in &mut self.routes {
+            if route.handler.observes_hooks() {
+                route.handler.observe_hook(hook, input, output)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn refused(&self, event: &Value) -> String {
+        match self.offered_by {
+            Some(route) => self.routes[route].handler.refused(event),
+            None => format!("The machine refused {}", event),
+        }
+    }
+}
@@ -1,5 +1,15 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs::File;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::path::PathBuf;
+
 use crate::VM;
+use crate::clock::Clock;
+use crate::fs::Filesystem;
+use crate::record::{self, Record};
 use bytecode::{Library, SentenceIndex, Value};
+use tokio::sync::mpsc;
 
 /// An Environment is a Hanoi CSP machine implemented in async Rust.
 #[allow(async_fn_in_trait)]
@@ -9,7 +19,41 @@ pub trait Environment {
 
     /// Asynchronously waits for an event.
     /// The runtime will verify whether the returned event is accepted by the main machine.
+    /// A [`Router`](crate::Router) drops the future when another handler
+    /// answers first, so nothing should be lost by dropping it.
     async fn wait_for_event(&mut self) -> Result<Value, String>;
+
+    /// Whether the environment has closed: it will offer nothing more, so a
+    /// machine about to wait on it finishes if it is ready to, and fails
+    /// otherwise. Asked each time the machine would wait.
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    /// Whether the environment wants to [observe](Self::observe_hook) the
+    /// machine's hooks. Seeing one costs a copy of the state it was given,
+    /// so it is only shown to environments that ask.
+    fn observes_hooks(&self) -> bool {
+        false
+    }
+
+    /// Sees each call of a machine sentence, if the environment
+    /// [observes hooks](Self::observes_hooks): `hook` names it (`init`,
+    /// `accept`, …), and `input` and `output` are what it was given and
+    /// answered. An error stops the run.
+    fn observe_hook(&mut self, hook: &str, input: &Value, output: &Value) -> Result<(), String> {
+        let _ = (hook, input, output);
+        Ok(())
+    }
+
+    /// What to say when the main machine does not accept `event`, the one
+    /// [`wait_for_event`](Environment::wait_for_event) last returned.
+    fn refused(&self, event: &Value) -> String {
+        format!(
+            "Environment returned event {:?}, which is not accepted by the machine",
+            event
+        )
+    }
 }
 
 pub fn extract_putch_char(
@@ -40,34 +84,212 @@ pub fn extract_putch_char(
     None
 }
 
+/// Builds the event `(((((), ch), getch), stdin), io)` that offers `ch` on
+/// standard input — the same nesting [`extract_putch_char`] reads on the way
+/// out.
+pub fn make_getch_event(ch: char, std_io: &Value, std_stdin: &Value, std_getch: &Value) -> Value {
+    let pair = |inner: Value, tag: &Value| Value::Tuple(vec![inner, tag.clone()]);
+    pair(
+        pair(
+            pair(pair(Value::unit(), &Value::Int(ch as i64)), std_getch),
+            std_stdin,
+        ),
+        std_io,
+    )
+}
+
+/// Builds the event `((((), eof), stdin), io)` that says standard input has
+/// no more characters to offer.
+pub fn make_eof_event(std_io: &Value, std_stdin: &Value, std_eof: &Value) -> Value {
+    let pair = |inner: Value, tag: &Value| Value::Tuple(vec![inner, tag.clone()]);
+    pair(pair(pair(Value::unit(), std_eof), std_stdin), std_io)
+}
+
+/// Where a [`DefaultEnvironment`] reads the characters it offers on
+/// `std::io::stdin`.
+pub enum Input {
+    /// The process's standard input.
+    Stdin,
+    /// A file, opened the first time the machine waits for input.
+    File(PathBuf),
+    /// A fixed text, for tests.
+    Scripted(String),
+    /// Anything else that can be read, such as a pipe.
+    Reader(Box<dyn Read + Send>),
+}
+
+/// An [`Input`] read a line at a time, as the machine asks for it.
+///
+/// Reading blocks, so it is done on a thread of its own, which reads a line
+/// only once one is asked for. All [`InputReader::next_char`] does is wait for
+/// the answer, so a wait that is dropped loses nothing: the line it asked for
+/// is the next one's.
+struct InputReader {
+    /// Until the machine first waits for input.
+    source: Option<Input>,
+    lines: Option<Lines>,
+    pending: VecDeque<char>,
+    ended: bool,
+}
+
+/// The thread reading an [`Input`], and whether a line has been asked of it
+/// and not yet taken.
+struct Lines {
+    ask: std::sync::mpsc::Sender<()>,
+    answers: mpsc::UnboundedReceiver<Result<String, String>>,
+    asked: bool,
+}
+
+impl Lines {
+    fn spawn(mut reader: Box<dyn BufRead + Send>) -> Self {
+        let (ask, asked) = std::sync::mpsc::channel();
+        let (answer, answers) = mpsc::unbounded_channel();
+        // Left behind if the run ends first, as a read of stdin may never
+        // finish.
+        std::thread::spawn(move || {
+            while asked.recv().is_ok() {
+                let mut line = String::new();
+                let read = reader
+                    .read_line(&mut line)
+                    .map(|_| line)
+                    .map_err(|e| format!("Could not read input: {}", e));
+                let last = !matches!(&read, Ok(line) if !line.is_empty());
+                if answer.send(read).is_err() || last {
+                    break;
+                }
+            }
+        });
+        Self {
+            ask,
+            answers,
+            asked: false,
+        }
+    }
+}
+
+impl InputReader {
+    fn new(source: Input) -> Self {
+        Self {
+            source: Some(source),
+            lines: None,
+            pending: VecDeque::new(),
+            ended: false,
+        }
+    }
+
+    fn open(source: Input) -> Result<Box<dyn BufRead + Send>, String> {
+        Ok(match source {
+            Input::Stdin => Box::new(BufReader::new(std::io::stdin())),
+            Input::File(path) => {
+                Box::new(BufReader::new(File::open(&path).map_err(|e| {
+                    format!("Could not open input file '{}': {}", path.display(), e)
+                })?))
+            }
+            Input::Scripted(text) => Box::new(Cursor::new(text.into_bytes())),
+            Input::Reader(reader) => Box::new(BufReader::new(reader)),
+        })
+    }
+
+    /// The next character, or `None` once the input has run out.
+    async fn next_char(&mut self) -> Result<Option<char>, String> {
+        while self.pending.is_empty() {
+            let lines = match &mut self.lines {
+                Some(lines) => lines,
+                None => {
+                    let source = self.source.take().ok_or("The input could not be opened")?;
+                    self.lines.insert(Lines::spawn(Self::open(source)?))
+                }
+            };
+            let stopped = || "Could not read input: its reader has stopped".to_string();
+            if !lines.asked {
+                lines.ask.send(()).map_err(|_| stopped())?;
+                lines.asked = true;
+            }
+            let line = lines.answers.recv().await.ok_or_else(stopped)?;
+            lines.asked = false;
+            let line = line?;
+            if line.is_empty() {
+                return Ok(None);
+            }
+            self.pending.extend(line.chars());
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
 /// A default environment that accepts CSP-style stdout events and prints them to stdout,
 /// optionally capturing them in an internal buffer.
+///
+/// It also offers the characters of an [`Input`] on `std::io::stdin`, one
+/// `getch` event at a time and then a single `eof`, whenever the machine waits
+/// for the environment. A program that declares no `std::io::stdin` channel
+/// gets no input events. Given a [`Filesystem`], it answers `std::fs`
+/// requests too, and offers an answer before any input. Given a [`Clock`], it
+/// tells the time, and while a timer is set it waits on the timer and the
+/// input at once, offering whichever comes first.
 pub struct DefaultEnvironment {
     capture_buffer: Option<String>,
     std_io: Option<Value>,
     std_stdout: Option<Value>,
     std_putch: Option<Value>,
+    std_stdin: Option<Value>,
+    std_getch: Option<Value>,
+    std_eof: Option<Value>,
+    input: InputReader,
+    fs: Option<Filesystem>,
+    clock: Option<Clock>,
 }
 
 impl DefaultEnvironment {
-    /// Creates a new DefaultEnvironment that writes directly to stdout.
+    /// Creates a new DefaultEnvironment that writes directly to stdout and
+    /// reads from stdin.
     pub fn new(library: &Library) -> Self {
         Self {
             capture_buffer: None,
             std_io: library.symbols.get("std::io::io").cloned(),
             std_stdout: library.symbols.get("std::io::stdout::stdout").cloned(),
             std_putch: library.symbols.get("std::io::stdout::putch").cloned(),
+            std_stdin: library.symbols.get("std::io::stdin::stdin").cloned(),
+            std_getch: library.symbols.get("std::io::stdin::getch").cloned(),
+            std_eof: library.symbols.get("std::io::stdin::eof").cloned(),
+            input: InputReader::new(Input::Stdin),
+            fs: None,
+            clock: None,
         }
     }
 
     /// Creates a new DefaultEnvironment that captures stdout in a string buffer.
+    /// It has no input: a machine that waits for some is offered `eof`.
     pub fn with_capture(library: &Library) -> Self {
         Self {
             capture_buffer: Some(String::new()),
-            std_io: library.symbols.get("std::io::io").cloned(),
-            std_stdout: library.symbols.get("std::io::stdout::stdout").cloned(),
-            std_putch: library.symbols.get("std::io::stdout::putch").cloned(),
+            ..Self::new(library)
         }
+        .with_input(Input::Scripted(String::new()))
+    }
+
+    /// Creates a new DefaultEnvironment that captures stdout and offers
+    /// `input` on stdin, for tests.
+    pub fn with_script(library: &Library, input: &str) -> Self {
+        Self::with_capture(library).with_input(Input::Scripted(input.to_string()))
+    }
+
+    /// Reads stdin from `source` instead.
+    pub fn with_input(mut self, source: Input) -> Self {
+        self.input = InputReader::new(source);
+        self
+    }
+
+    /// Answers `std::fs` requests with `fs`.
+    pub fn with_fs(mut self, fs: Filesystem) -> Self {
+        self.fs = Some(fs);
+        self
+    }
+
+    /// Tells the time and fires timers with `clock`.
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = Some(clock);
+        self
     }
 
     /// Returns the captured output, if capture was enabled.
@@ -88,12 +310,110 @@ impl Environment for DefaultEnvironment {
                 print!("{}", ch);
                 let _ = std::io::stdout().flush();
             }
+        } else {
+            // Each device answers only requests under its own symbols.
+            if let Some(fs) = &mut self.fs {
+                fs.request(&event)?;
+            }
+            if let Some(clock) = &mut self.clock {
+                clock.request(&event)?;
+            }
         }
         Ok(())
     }
 
     async fn wait_for_event(&mut self) -> Result<Value, String> {
-        Err("DefaultEnvironment does not support input events".to_string())
+        if let Some(reply) = self.fs.as_mut().and_then(Filesystem::next_reply) {
+            return Ok(reply);
+        }
+        if let Some(reply) = self.clock.as_mut().and_then(Clock::next_reply) {
+            return Ok(reply);
+        }
+        let stdin = match (
+            &self.std_io,
+            &self.std_stdin,
+            &self.std_getch,
+            &self.std_eof,
+        ) {
+            (Some(io), Some(stdin), Some(getch), Some(eof)) => Some((io, stdin, getch, eof)),
+            _ => None,
+        };
+        let timer = self.clock.as_mut().filter(|clock| clock.has_timer());
+        let input = &mut self.input;
+        match (timer, stdin) {
+            (Some(clock), Some(stdin)) if !input.ended => tokio::select! {
+                // A virtual clock's timer is always due, so it comes first.
+                biased;
+                Some(fired) = clock.next_timer() => Ok(fired),
+                event = read_stdin(input, stdin) => event,
+            },
+            (Some(clock), _) => clock
+                .next_timer()
+                .await
+                .ok_or_else(|| "The clock has no timer to fire".to_string()),
+            (None, Some(stdin)) => read_stdin(input, stdin).await,
+            (None, None) => Err("DefaultEnvironment only offers input on std::io::stdin, \
+                 and this program declares no std::io::stdin::{stdin, getch, eof}"
+                .to_string()),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        // Answers and timers still to come keep it open, and after them only
+        // stdin could offer anything more.
+        let stdin_open = self.std_io.is_some()
+            && self.std_stdin.is_some()
+            && self.std_getch.is_some()
+            && self.std_eof.is_some()
+            && !self.input.ended;
+        !stdin_open
+            && self.fs.as_ref().is_none_or(Filesystem::is_closed)
+            && self.clock.as_ref().is_none_or(Clock::is_closed)
+    }
+}
+
+/// The arguments and op of `event`, if it is a request `((args, op), tag)`
+/// to the device under `tag`.
+pub(crate) fn device_request<'v>(event: &'v Value, tag: &Value) -> Option<(&'v Value, &'v Value)> {
+    let Value::Tuple(outer) = event else {
+        return None;
+    };
+    let [request, to] = &outer[..] else {
+        return None;
+    };
+    let Value::Tuple(request) = request else {
+        return None;
+    };
+    match &request[..] {
+        [args, op] if to == tag => Some((args, op)),
+        _ => None,
+    }
+}
+
+/// The code `event` exits with, if it is `((code, exit), process)` with
+/// `process` and `exit` the symbols of `std::process` in its library.
+pub(crate) fn exit_code(event: &Value, process: &Value, exit: &Value) -> Option<i64> {
+    match device_request(event, process)? {
+        (Value::Int(code), op) if op == exit => Some(*code),
+        _ => None,
+    }
+}
+
+/// The next `getch` from `input`, or its `eof`, with `stdin` the symbols
+/// `(io, stdin, getch, eof)` they are made of.
+async fn read_stdin(
+    input: &mut InputReader,
+    (io, stdin, getch, eof): (&Value, &Value, &Value, &Value),
+) -> Result<Value, String> {
+    if input.ended {
+        return Err("The machine is waiting for input, and stdin has already ended".to_string());
+    }
+    match input.next_char().await? {
+        Some(ch) => Ok(make_getch_event(ch, io, stdin, getch)),
+        None => {
+            input.ended = true;
+            Ok(make_eof_event(io, stdin, eof))
+        }
     }
 }
 
@@ -107,8 +427,13 @@ pub struct Runtime<E: Environment> {
     main_emit: SentenceIndex,
     main_process: SentenceIndex,
     main_is_done: SentenceIndex,
-    #[allow(dead_code)]
     main_is_ready_to_finish: SentenceIndex,
+    args: Vec<String>,
+    alphabet: Vec<Value>,
+    std_process: Option<Value>,
+    std_exit: Option<Value>,
+    prefix: String,
+    recording: Option<Box<dyn Write + Send>>,
 }
 
 impl<E: Environment> Runtime<E> {
@@ -118,6 +443,10 @@ impl<E: Environment> Runtime<E> {
     /// * `library` - The compiled bytecode library.
     /// * `prefix` - The namespace of the main Hanoi machine (e.g., "main").
     /// * `environment` - The async Rust environment instance.
+    ///
+    /// Each sentence is looked up among the exports, and otherwise by its
+    /// fully qualified name, so a machine need not export its sentences to be
+    /// driven from outside.
     pub fn new(library: Library, prefix: &str, environment: E) -> Result<Self, String> {
         let resolve = |suffix: &str| -> Result<SentenceIndex, String> {
             let name = format!("{}::{}", prefix, suffix);
@@ -125,6 +454,12 @@ impl<E: Environment> Runtime<E> {
                 .exports
                 .get(&name)
                 .copied()
+                .or_else(|| {
+                    library
+                        .names
+                        .iter_enumerated()
+                        .find_map(|(index, n)| (*n == name).then_some(index))
+                })
                 .ok_or_else(|| format!("Could not find sentence export '{}'", name))
         };
 
@@ -136,6 +471,9 @@ impl<E: Environment> Runtime<E> {
         let main_is_done = resolve("is_done")?;
         let main_is_ready_to_finish = resolve("is_ready_to_finish")?;
 
+        let std_process = library.symbols.get("std::process::process").cloned();
+        let std_exit = library.symbols.get("std::process::exit").cloned();
+
         Ok(Self {
             vm: VM::new(library),
             environment,
@@ -146,9 +484,98 @@ impl<E: Environment> Runtime<E> {
             main_process,
             main_is_done,
             main_is_ready_to_finish,
+            args: Vec::new(),
+            alphabet: Vec::new(),
+            std_process,
+            std_exit,
+            prefix: prefix.to_string(),
+            recording: None,
         })
     }
 
+    /// Sets the arguments `init` is given, as a tuple of const strings. With
+    /// none set it is given `()`.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// Declares the events the environment could offer. A machine that is
+    /// left waiting on a closed environment is reported with the ones it
+    /// would accept.
+    pub fn set_alphabet(&mut self, alphabet: Vec<Value>) {
+        self.alphabet = alphabet;
+    }
+
+    /// Records the session to `out`, as JSON Lines a
+    /// [`ReplayEnvironment`](crate::ReplayEnvironment) can play back: every
+    /// call of a machine sentence, and every event that passes between the
+    /// machine and the environment.
+    pub fn record_to(&mut self, out: impl Write + Send + 'static) -> Result<(), String> {
+        let mut out: Box<dyn Write + Send> = Box::new(out);
+        writeln!(out, "{}", record::header(&self.prefix))
+            .map_err(|e| format!("Could not write the recording: {}", e))?;
+        self.recording = Some(out);
+        Ok(())
+    }
+
+    fn note(&mut self, record: Record) -> Result<(), String> {
+        if let Some(out) = &mut self.recording {
+            // Flushed line by line, so a run that goes wrong leaves all of
+            // itself behind.
+            writeln!(out, "{}", record.to_json())
+                .and_then(|()| out.flush())
+                .map_err(|e| format!("Could not write the recording: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Whether a hook's input has to be kept to be seen after the call: by
+    /// the recording, or by the environment.
+    fn watching(&self) -> bool {
+        self.recording.is_some() || self.environment.observes_hooks()
+    }
+
+    fn observe(&mut self, hook: &str, input: Value, output: &Value) -> Result<(), String> {
+        let record = |input| Record::Hook {
+            hook: hook.to_string(),
+            input,
+            output: output.clone(),
+        };
+        if !self.environment.observes_hooks() {
+            return self.note(record(input));
+        }
+        if self.recording.is_some() {
+            self.note(record(input.clone()))?;
+        }
+        self.environment.observe_hook(hook, &input, output)
+    }
+
+    /// Runs the machine's `hook`, the sentence at `sentence`, on `input`,
+    /// and answers what it left. The input is moved onto the stack, and only
+    /// copied first when something is [watching](Self::watching).
+    fn call(&mut self, hook: &str, sentence: SentenceIndex, input: Value) -> Result<Value, String> {
+        if !self.vm.stack.is_empty() {
+            return Err(format!(
+                "Stack not empty before {}: {:?}",
+                hook, self.vm.stack
+            ));
+        }
+        let kept = self.watching().then(|| input.clone());
+        self.vm.stack.push(input);
+        self.vm.execute(sentence)?;
+        let res = self.vm.pop()?;
+        if let Some(input) = kept {
+            self.observe(hook, input, &res)?;
+        }
+        if !self.vm.stack.is_empty() {
+            return Err(format!(
+                "Stack not empty after {}: {:?}",
+                hook, self.vm.stack
+            ));
+        }
+        Ok(res)
+    }
+
     /// Access the underlying VM.
     pub fn vm(&self) -> &VM {
         &self.vm
@@ -159,49 +586,60 @@ impl<E: Environment> Runtime<E> {
         &mut self.vm
     }
 
-    /// Runs the coordinate execution loop until the main Hanoi machine terminates (is_done returns true).
-    pub async fn run(&mut self) -> Result<(), String> {
+    /// Runs the coordinate execution loop until the main Hanoi machine terminates (is_done returns true),
+    /// emits `((code, exit), process)` under `std::process`, or is about to
+    /// wait on an environment that has closed while it is ready to finish.
+    ///
+    /// Returns the exit code: the one the machine exited with, or 0 if it
+    /// terminated or finished without one.
+    pub async fn run(&mut self) -> Result<i64, String> {
         let mut state = self.execute_init()?;
 
         loop {
             // 1. Fixed-point tau reductions
-            loop {
-                let (new_state, did_reduce) = self.execute_tau_reduce(state.clone())?;
-                state = new_state;
-                if !did_reduce {
-                    break;
-                }
-            }
+            state = self.settle(state)?;
 
             // 2. Check if done
             if self.execute_is_done(state.clone())? {
-                break;
+                return Ok(0);
             }
 
             // 3. Check if the hanoi machine wants to emit an event
             let (event, has_event) = self.execute_emit(state.clone())?;
             if has_event {
+                // Exiting ends the run there and then: nothing is left to
+                // take the event.
+                if let Some(code) = self.exit_code(&event) {
+                    return Ok(code);
+                }
                 // Pass event off to the environment
+                self.note(Record::Emitted(event.clone()))?;
                 self.environment.handle_event(event.clone()).await?;
                 // Transition Main state
                 state = self.execute_process(state, event)?;
                 continue;
             }
 
-            // 4. Asynchronously wait for the environment to pass an event
+            // 4. A closed environment offers nothing more: finish, if the
+            // machine is ready to.
+            if self.environment.is_closed() {
+                return if self.execute_is_ready_to_finish(state.clone())? {
+                    Ok(0)
+                } else {
+                    Err(self.blocked(&state)?)
+                };
+            }
+
+            // 5. Asynchronously wait for the environment to pass an event
             let event = self.environment.wait_for_event().await?;
+            self.note(Record::Offered(event.clone()))?;
             if !self.execute_accept(state.clone(), event.clone())? {
-                return Err(format!(
-                    "Environment returned event {:?}, which is not accepted by the machine",
-                    event
-                ));
+                return Err(self.environment.refused(&event));
             }
 
             // 6. Transition Main state
             state = self.execute_process(state, event)?;
         }
-
-        Ok(())
     }
 
     /// Runs the coordinate execution loop for a test.
@@ -215,13 +653,7 @@ impl<E: Environment> Runtime<E> {
         let mut state = self.execute_init()?;
 
         // 1. Initial fixed-point tau reductions
-        loop {
-            let (new_state, did_reduce) = self.execute_tau_reduce(state.clone())?;
-            state = new_state;
-            if !did_reduce {
-                break;
-            }
-        }
+        state = self.settle(state)?;
 
         // 2. Check if start_val is accepted
         if !self.execute_accept(state.clone(), start_val.clone())? {
@@ -237,13 +669,7 @@ impl<E: Environment> Runtime<E> {
         // 4. Run coordinated loop
         loop {
             // A. Fixed-point tau reductions
-            loop {
-                let (new_state, did_reduce) = self.execute_tau_reduce(state.clone())?;
-                state = new_state;
-                if !did_reduce {
-                    break;
-                }
-            }
+            state = self.settle(state)?;
 
             // B. Check if the hanoi machine wants to emit an event
             let (event, has_event) = self.execute_emit(state.clone())?;
@@ -255,6 +681,7 @@ impl<E: Environment> Runtime<E> {
                 }
 
                 // Pass event off to the environment
+                self.note(Record::Emitted(event.clone()))?;
                 self.environment.handle_event(event.clone()).await?;
                 // Transition Main state
                 state = self.execute_process(state, event)?;
@@ -268,13 +695,23 @@ impl<E: Environment> Runtime<E> {
                 );
             }
 
-            // D. Asynchronously wait for the environment to pass an event
+            // D. A closed environment offers nothing more, so the machine
+            // finishes there, without having passed or failed.
+            if self.environment.is_closed() {
+                return Err(if self.execute_is_ready_to_finish(state.clone())? {
+                    "Test machine finished when the environment closed, \
+                     without emitting pass or fail event"
+                        .to_string()
+                } else {
+                    self.blocked(&state)?
+                });
+            }
+
+            // E. Asynchronously wait for the environment to pass an event
             let event = self.environment.wait_for_event().await?;
+            self.note(Record::Offered(event.clone()))?;
             if !self.execute_accept(state.clone(), event.clone())? {
-                return Err(format!(
-                    "Environment returned event {:?}, which is not accepted by the machine",
-                    event
-                ));
+                return Err(self.environment.refused(&event));
             }
 
             // F. Transition Main state
@@ -282,36 +719,107 @@ impl<E: Environment> Runtime<E> {
         }
     }
 
-    // Helper sentence execution wrappers
+    pub(crate) fn settle(&mut self, mut state: Value) -> Result<Value, String> {
+        // Only the hashes of the states passed through are kept. When one
+        // comes round again, the state is followed once more: if it returns,
+        // the states on the way are the cycle.
+        let mut seen = HashSet::new();
+        let mut steps = 0;
+        loop {
+            let hash = {
+                let mut hasher = DefaultHasher::new();
+                state.hash(&mut hasher);
+                hasher.finish()
+            };
+            if !seen.insert(hash) {
+                let start = state.clone();
+                let mut lap = Vec::new();
+                // A cycle is no longer than the way here; a state that does
+                // not come back within that only shared a hash.
+                while lap.len() < steps {
+                    lap.push(state.clone());
+                    let (new_state, did_reduce) = self.execute_tau_reduce(state)?;
+                    state = new_state;
+                    if !did_reduce {
+                        return Ok(state);
+                    }
+                    if state == start {
+                        return Err(Self::cycle(&lap));
+                    }
+                }
+                steps += lap.len();
+                continue;
+            }
+            let (new_state, did_reduce) = self.execute_tau_reduce(state)?;
+            state = new_state;
+            steps += 1;
+            if !did_reduce {
+                return Ok(state);
+            }
+        }
+    }
 
-    fn execute_init(&mut self) -> Result<Value, String> {
-        if !self.vm.stack.is_empty() {
-            return Err(format!("Stack not empty before init: {:?}", self.vm.stack));
+    fn cycle(states: &[Value]) -> String {
+        const SHOWN: usize = 8;
+        let mut message = format!(
+            "tau_reduce goes round a cycle of {} state{} and never settles:",
+            states.len(),
+            if states.len() == 1 { "" } else { "s" }
+        );
+        for state in states.iter().take(SHOWN) {
+            message.push_str(&format!("\n    {}", state));
         }
-        self.vm.stack.push(Value::Tuple(Vec::new()));
-        self.vm.execute(self.main_init)?;
-        let res = self.vm.pop()?;
-        if !self.vm.stack.is_empty() {
-            return Err(format!("Stack not empty after init: {:?}", self.vm.stack));
+        if states.len() > SHOWN {
+            message.push_str(&format!("\n    … and {} more", states.len() - SHOWN));
         }
-        Ok(res)
+        message
     }
 
-    fn execute_accept(&mut self, state: Value, event: Value) -> Result<bool, String> {
-        if !self.vm.stack.is_empty() {
-            return Err(format!(
-                "Stack not empty before accept: {:?}",
-                self.vm.stack
-            ));
+    /// Why the machine is stuck in `state`, waiting on an environment that
+    /// has closed: the state, and which events of the alphabet it would take.
+    fn blocked(&mut self, state: &Value) -> Result<String, String> {
+        let mut message = format!(
+            "The environment has closed, and the machine is not ready to finish, in state {}",
+            state
+        );
+        if self.alphabet.is_empty() {
+            return Ok(message);
         }
-        // The state is the top of the pair, so it is the *last* element.
-        let pair = Value::Tuple(vec![event, state]);
-        self.vm.stack.push(pair);
-        self.vm.execute(self.main_accept)?;
-        let res = self.vm.pop()?;
-        if !self.vm.stack.is_empty() {
-            return Err(format!("Stack not empty after accept: {:?}", self.vm.stack));
+        let mut accepted = Vec::new();
+        for event in self.alphabet.clone() {
+            if self.execute_accept(state.clone(), event.clone())? {
+                accepted.push(event);
+            }
+        }
+        if accepted.is_empty() {
+            message.push_str("\nIt would accept none of the declared alphabet");
+        } else {
+            message.push_str("\nOf the declared alphabet, it would accept:");
+            for event in accepted {
+                message.push_str(&format!("\n    {}", event));
+            }
         }
+        Ok(message)
+    }
+
+    /// The code `event` exits with, if it is `((code, exit), process)`.
+    fn exit_code(&self, event: &Value) -> Option<i64> {
+        exit_code(event, self.std_process.as_ref()?, self.std_exit.as_ref()?)
+    }
+
+    // Helper sentence execution wrappers
+
+    pub(crate) fn execute_init(&mut self) -> Result<Value, String> {
+        let args = self.args.iter().cloned().map(Value::ConstString).collect();
+        let input = Value::Tuple(args);
+        let res = self.call("init", self.main_init, input)?;
+        Ok(res)
+    }
+
+    pub(crate) fn execute_accept(&mut self, state: Value, event: Value) -> Result<bool, String> {
+        // The state is the top of the pair, so it is the *last* element.
+        let input = Value::Tuple(vec![event, state]);
+        let res = self.call("accept", self.main_accept, input)?;
         match res {
             Value::Bool(b) => Ok(b),
             v => Err(format!("Expected Value::Bool from accept, found {:?}", v)),
@@ -319,21 +827,7 @@ impl<E: Environment> Runtime<E> {
     }
 
     fn execute_tau_reduce(&mut self, state: Value) -> Result<(Value, bool), String> {
-        if !self.vm.stack.is_empty() {
-            return Err(format!(
-                "Stack not empty before tau_reduce: {:?}",
-                self.vm.stack
-            ));
-        }
-        self.vm.stack.push(state);
-        self.vm.execute(self.main_tau_reduce)?;
-        let res = self.vm.pop()?;
-        if !self.vm.stack.is_empty() {
-            return Err(format!(
-                "Stack not empty after tau_reduce: {:?}",
-                self.vm.stack
-            ));
-        }
+        let res = self.call("tau_reduce", self.main_tau_reduce, state)?;
         match res {
             Value::Tuple(mut elems) if elems.len() == 2 => {
                 // The flag is on top, so it is the last element.
@@ -351,16 +845,8 @@ impl<E: Environment> Runtime<E> {
         }
     }
 
-    fn execute_emit(&mut self, state: Value) -> Result<(Value, bool), String> {
-        if !self.vm.stack.is_empty() {
-            return Err(format!("Stack not empty before emit: {:?}", self.vm.stack));
-        }
-        self.vm.stack.push(state);
-        self.vm.execute(self.main_emit)?;
-        let res = self.vm.pop()?;
-        if !self.vm.stack.is_empty() {
-            return Err(format!("Stack not empty after emit: {:?}", self.vm.stack));
-        }
+    pub(crate) fn execute_emit(&mut self, state: Value) -> Result<(Value, bool), String> {
+        let res = self.call("emit", self.main_emit, state)?;
         match res {
             Value::Tuple(mut elems) if elems.len() == 2 => {
                 // The flag is on top, so it is the last element.
@@ -378,44 +864,27 @@ impl<E: Environment> Runtime<E> {
         }
     }
 
-    fn execute_process(&mut self, state: Value, event: Value) -> Result<Value, String> {
-        if !self.vm.stack.is_empty() {
-            return Err(format!(
-                "Stack not empty before process: {:?}",
-                self.vm.stack
-            ));
-        }
-        self.vm.stack.push(Value::Tuple(vec![event, state]));
-        self.vm.execute(self.main_process)?;
-        let res = self.vm.pop()?;
-        if !self.vm.stack.is_empty() {
-            return Err(format!(
-                "Stack not empty after process: {:?}",
-                self.vm.stack
-            ));
-        }
-        Ok(res)
+    pub(crate) fn execute_process(&mut self, state: Value, event: Value) -> Result<Value, String> {
+        let input = Value::Tuple(vec![event, state]);
+        self.call("process", self.main_process, input)
     }
 
-    fn execute_is_done(&mut self, state: Value) -> Result<bool, String> {
-        if !self.vm.stack.is_empty() {
-            return Err(format!(
-                "Stack not empty before is_done: {:?}",
-                self.vm.stack
-            ));
-        }
-        self.vm.stack.push(state);
-        self.vm.execute(self.main_is_done)?;
-        let res = self.vm.pop()?;
-        if !self.vm.stack.is_empty() {
-            return Err(format!(
-                "Stack not empty after is_done: {:?}",
-                self.vm.stack
-            ));
-        }
+    pub(crate) fn execute_is_done(&mut self, state: Value) -> Result<bool, String> {
+        let res = self.call("is_done", self.main_is_done, state)?;
         match res {
             Value::Bool(b) => Ok(b),
             v => Err(format!("Expected bool from is_done, found {:?}", v)),
         }
     }
+
+    pub(crate) fn execute_is_ready_to_finish(&mut self, state: Value) -> Result<bool, String> {
+        let res = self.call("is_ready_to_finish", self.main_is_ready_to_finish, state)?;
+        match res {
+            Value::Bool(b) => Ok(b),
+            v => Err(format!(
+                "Expected bool from is_ready_to_finish, found {:?}",
+                v
+            )),
+        }
+    }
 }
@@ -0,0 +1,374 @@
+//! Driving a machine from a script of the events it should trade.
+//!
+//! A script names the machine it drives, then lists, one per line, what
+//! happens next:
+//!
+//! ```text
+//! // Two values pushed onto the queue come back off its front in order.
+//! machine queue::queue
+//! offer ((1,), queue::Event::PushBack::tag)
+//! emit ((((((), 1), prelude::some), prelude::resp),), queue::Event::PopFront::tag)
+//! done
+//! ```
+//!
+//! - `offer v`: the environment offers `v`, and the machine has to accept it;
+//! - `emit v`: the machine has to emit `v`, and nothing else, next;
+//! - `done`: the machine has to be done.
+//!
+//! Values are written the way [`Value`] prints them, and a symbol by the path
+//! the library keys it under. Once the last step is taken the environment
+//! has closed: a machine about to wait then has to be ready to finish, and
+//! the run fails where it is blocked otherwise.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+use bytecode::{Library, Value};
+
+use crate::runtime::Environment;
+
+/// A parsed script: the machine it drives, and the steps it expects.
+pub struct Script {
+    /// The module of the machine, as [`Runtime::new`](crate::Runtime::new)
+    /// takes it.
+    pub machine: String,
+    steps: Vec<Step>,
+}
+
+#[derive(Clone)]
+struct Step {
+    line: usize,
+    kind: StepKind,
+}
+
+#[derive(Clone)]
+enum StepKind {
+    Offer(Value),
+    Emit(Value),
+    Done,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            StepKind::Offer(v) => write!(f, "line {}: offer {}", self.line, v),
+            StepKind::Emit(v) => write!(f, "line {}: emit {}", self.line, v),
+            StepKind::Done => write!(f, "line {}: done", self.line),
+        }
+    }
+}
+
+impl Script {
+    /// Reads a script, naming its symbols out of `library`. `//` outside a
+    /// string starts a comment, and blank lines are skipped.
+    pub fn parse(text: &str, library: &Library) -> Result<Script, String> {
+        let mut machine = None;
+        let mut steps = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let at = |e: String| format!("line {}: {}", line_no, e);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let kind = match keyword {
+                "machine" if machine.is_none() && steps.is_empty() => {
+                    machine = Some(rest.trim().to_string());
+                    continue;
+                }
+                "machine" => return Err(at("the machine is named once, first".to_string())),
+                "offer" => StepKind::Offer(whole_value(rest, library).map_err(at)?),
+                "emit" => StepKind::Emit(whole_value(rest, library).map_err(at)?),
+                "done" if rest.is_empty() => StepKind::Done,
+                _ => {
+                    return Err(at(format!(
+                        "expected `offer`, `emit` or `done`, found `{}`",
+                        line
+                    )));
+                }
+            };
+            steps.push(Step {
+                line: line_no,
+                kind,
+            });
+        }
+        let machine = machine.ok_or("a script starts by naming its `machine`")?;
+        Ok(Script { machine, steps })
+    }
+}
+
+/// An [`Environment`] that plays a [`Script`], failing at the first step the
+/// machine does not take.
+pub struct ScriptedEnvironment {
+    steps: VecDeque<Step>,
+    /// The offer the machine was last handed, to say which line it refused.
+    offered: Option<Step>,
+}
+
+impl ScriptedEnvironment {
+    pub fn new(script: &Script) -> Self {
+        Self {
+            steps: script.steps.iter().cloned().collect(),
+            offered: None,
+        }
+    }
+
+    /// What the run came to, judged against the script: `run` is what
+    /// [`Runtime::run`](crate::Runtime::run) answered.
+    pub fn verdict<T>(&self, run: Result<T, String>) -> Result<(), String> {
+        match run {
+            Err(e) => Err(e),
+            Ok(_) => match self.steps.front() {
+                None => Ok(()),
+                Some(Step {
+                    kind: StepKind::Done,
+                    ..
+                }) if self.steps.len() == 1 => Ok(()),
+                Some(step) => Err(format!(
+                    "the machine finished early: the script still expects {}",
+                    step
+                )),
+            },
+        }
+    }
+}
+
+impl Environment for ScriptedEnvironment {
+    async fn handle_event(&mut self, event: Value) -> Result<(), String> {
+        match self.steps.pop_front() {
+            Some(Step {
+                kind: StepKind::Emit(expected),
+                ..
+            }) if expected == event => Ok(()),
+            Some(Step {
+                line,
+                kind: StepKind::Emit(expected),
+            }) => Err(format!(
+                "line {}: expected the machine to emit\n    {}\nbut it emitted\n    {}{}",
+                line,
+                expected,
+                event,
+                difference(&expected, &event)
+            )),
+            Some(step) => Err(format!(
+                "the machine emitted {}, where the script expects {}",
+                event, step
+            )),
+            None => Err(format!(
+                "the machine emitted {} after the script ended",
+                event
+            )),
+        }
+    }
+
+    async fn wait_for_event(&mut self) -> Result<Value, String> {
+        match self.steps.pop_front() {
+            Some(Step {
+                line,
+                kind: StepKind::Offer(event),
+            }) => {
+                self.offered = Some(Step {
+                    line,
+                    kind: StepKind::Offer(event.clone()),
+                });
+                Ok(event)
+            }
+            Some(step) => Err(format!(
+                "the machine is waiting for an event, where the script expects {}",
+                step
+            )),
+            None => Err("the script has ended, and the machine is waiting".to_string()),
+        }
+    }
+
+    /// The script closes the environment once every step is taken.
+    fn is_closed(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    fn refused(&self, event: &Value) -> String {
+        match &self.offered {
+            Some(step) => format!("the machine refused {}", step),
+            None => format!("the machine refused {}", event),
+        }
+    }
+}
+
+/// Where two values first part, as a path of tuple indices, when both are
+/// tuples of one length there.
+pub(crate) fn difference(expected: &Value, found: &Value) -> String {
+    let mut path = String::new();
+    let (mut expected, mut found) = (expected, found);
+    while let (Value::Tuple(e), Value::Tuple(f)) = (expected, found)
+        && e.len() == f.len()
+    {
+        let i = e.iter().zip(f).position(|(e, f)| e != f).unwrap();
+        path.push_str(&format!("[{}]", i));
+        (expected, found) = (&e[i], &f[i]);
+    }
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\nthey first differ at {}: {} against {}",
+            path, expected, found
+        )
+    }
+}
+
+/// `line` without the `//` comment that ends it, if one does. A `//` inside
+/// a string literal is part of the string.
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '/' if !in_string && line[i + 1..].starts_with('/') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// One value, and nothing after it.
+fn whole_value(text: &str, library: &Library) -> Result<Value, String> {
+    let (value, rest) = value(text, library)?;
+    if !rest.trim().is_empty() {
+        return Err(format!("unexpected `{}` after the value", rest.trim()));
+    }
+    Ok(value)
+}
+
+/// Reads a value the way [`Value`]'s `Display` writes one.
+pub fn value<'t>(text: &'t str, library: &Library) -> Result<(Value, &'t str), String> {
+    let text = text.trim_start();
+    if let Some(after) = text.strip_prefix('"') {
+        return string(after);
+    }
+    if let Some(after) = text.strip_prefix('(') {
+        // `(x,)` is a tuple of one, and `()` of none.
+        let (elements, rest) = sequence(after, ')', library, |text, library| value(text, library))?;
+        return Ok((Value::Tuple(elements), rest));
+    }
+    if let Some(after) = text.strip_prefix('{') {
+        let (entries, rest) = sequence(after, '}', library, |text, library| {
+            let (key, rest) = value(text, library)?;
+            let rest = rest
+                .trim_start()
+                .strip_prefix(':')
+                .ok_or_else(|| format!("expected `:` after the key {}", key))?;
+            let (v, rest) = value(rest, library)?;
+            Ok((Value::Tuple(vec![key, v]), rest))
+        })?;
+        let entries = entries.into_iter().map(|entry| match entry {
+            Value::Tuple(mut pair) => {
+                let v = pair.pop().unwrap();
+                (pair.pop().unwrap(), v)
+            }
+            _ => unreachable!(),
+        });
+        return Ok((Value::Map(entries.collect::<BTreeMap<_, _>>()), rest));
+    }
+    if text.starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+        let len = 1 + text[1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len() - 1);
+        let (digits, rest) = text.split_at(len);
+        return digits
+            .parse()
+            .map(|n| (Value::Int(n), rest))
+            .map_err(|_| format!("`{}` is not an int", digits));
+    }
+    // A path is names joined by `::`; a lone `:` is a map's, and ends it.
+    let mut len = 0;
+    loop {
+        len += text[len..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(text.len() - len);
+        if text[len..].starts_with("::") {
+            len += 2;
+        } else {
+            break;
+        }
+    }
+    let (word, rest) = text.split_at(len);
+    match word {
+        "" => Err(format!("expected a value, found `{}`", text)),
+        "true" => Ok((Value::Bool(true), rest)),
+        "false" => Ok((Value::Bool(false), rest)),
+        _ => {
+            let path = word.strip_prefix("crate::").unwrap_or(word);
+            match library.symbols.get(path) {
+                Some(symbol) => Ok((symbol.clone(), rest)),
+                None => Err(format!("no symbol is called `{}`", path)),
+            }
+        }
+    }
+}
+
+/// Comma-separated elements up to `close`, which may follow a trailing comma.
+fn sequence<'t>(
+    mut text: &'t str,
+    close: char,
+    library: &Library,
+    element: impl Fn(&'t str, &Library) -> Result<(Value, &'t str), String>,
+) -> Result<(Vec<Value>, &'t str), String> {
+    let mut elements = Vec::new();
+    loop {
+        text = text.trim_start();
+        if let Some(rest) = text.strip_prefix(close) {
+            return Ok((elements, rest));
+        }
+        let (v, rest) = element(text, library)?;
+        elements.push(v);
+        let rest = rest.trim_start();
+        if let Some(rest) = rest.strip_prefix(',') {
+            text = rest;
+        } else if let Some(rest) = rest.strip_prefix(close) {
+            return Ok((elements, rest));
+        } else {
+            return Err(format!("expected `,` or `{}`, found `{}`", close, rest));
+        }
+    }
+}
+
+/// The rest of a const string, after its opening quote, with the escapes
+/// `Display` writes.
+fn string(text: &str) -> Result<(Value, &str), String> {
+    let mut out = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((Value::ConstString(out), &text[i + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('0') => out.push('\0'),
+                Some(c @ ('\\' | '"' | '\'')) => out.push(c),
+                Some('u') => {
+                    let rest = &text[i + 2..];
+                    let code = rest
+                        .strip_prefix('{')
+                        .and_then(|r| r.split_once('}'))
+                        .and_then(|(hex, _)| u32::from_str_radix(hex, 16).ok())
+                        .and_then(char::from_u32)
+                        .ok_or("expected `\\u{...}` naming a character")?;
+                    out.push(code);
+                    for (_, c) in chars.by_ref() {
+                        if c == '}' {
+                            break;
+                        }
+                    }
+                }
+                _ => return Err("unknown escape in a const string".to_string()),
+            },
+            c => out.push(c),
+        }
+    }
+    Err("a const string that never closes its quote".to_string())
+}
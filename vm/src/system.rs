@@ -0,0 +1,690 @@
+//! Several machines, each in its own VM, talking over channels.
+//!
+//! `compose_concurrent` builds one machine out of two at compile time, and a
+//! system of any size ends up as one state tuple in one VM. A [`System`] runs
+//! the machines apart instead: each is a [`Runtime`] of its own, on a tokio
+//! task of its own, and they meet only at channels.
+//!
+//! A channel joins two ports, a port being a symbol in one machine's library.
+//! A machine sends on a port by emitting `(message, port)`, and the machine at
+//! the other end is offered `(message, its_port)`. That is a handshake: both
+//! move on together if the receiver accepts, and neither does if it refuses,
+//! so the sender stays committed to its emission until the receiver comes
+//! round to taking it. Whatever a machine emits on no channel goes to the
+//! [`Environment`], and whatever the environment offers under a port bound
+//! with [`System::add_external`] goes to the machine it is bound to. The
+//! environment is only waited on once nothing inside the system can move.
+//! A machine that exits through `std::process` ends the whole system with its
+//! code, as a thread that exits ends its process.
+//!
+//! The machines may come from libraries compiled apart. A symbol is only
+//! itself in the library that declared it, so whatever crosses from one
+//! library into another has its symbols swapped for the ones that library
+//! keeps under the same paths.
+//!
+//! A system can also be read from a topology file:
+//!
+//! ```text
+//! // A source counting into a sink, which prints what it is given.
+//! process source counter.hana source
+//! process sink printer.hana sink
+//! channel numbers source chan::put sink inbox::put
+//! external sink std::io::io
+//! ```
+//!
+//! - `process NAME FILE MODULE`: runs the machine in `MODULE` of `FILE`, a
+//!   path relative to the topology file, and calls it `NAME`;
+//! - `channel NAME A PORT B PORT`: joins a port of `A` to a port of `B`;
+//! - `external NAME PORT`: hands what the environment offers under `PORT` to
+//!   `NAME`.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+use bytecode::{Library, SourceMap, Value};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::runtime::{Environment, Runtime, exit_code};
+use crate::script::strip_comment;
+
+/// The environment of a machine in a system, which the system stands in
+/// for: the machine's own runtime never waits or hands an event on.
+struct Detached;
+
+impl Environment for Detached {
+    async fn handle_event(&mut self, _event: Value) -> Result<(), String> {
+        Err("a machine in a system hands its events to the system".to_string())
+    }
+
+    async fn wait_for_event(&mut self) -> Result<Value, String> {
+        Err("a machine in a system is offered events by the system".to_string())
+    }
+}
+
+/// What the system asks of a machine's task, about the state it last
+/// settled in.
+enum Command {
+    Accept(Value, oneshot::Sender<Result<bool, String>>),
+    ReadyToFinish(oneshot::Sender<Result<bool, String>>),
+    /// Take the event, and settle again.
+    Process(Value),
+}
+
+#[derive(Clone)]
+enum Status {
+    Done,
+    Emitting(Value),
+    Waiting,
+}
+
+/// Where a machine has settled: once it reports, its task waits to be told
+/// what to do.
+#[derive(Clone)]
+struct Report {
+    state: Value,
+    status: Status,
+}
+
+struct Process {
+    name: String,
+    symbols: HashMap<String, Value>,
+    /// Until the system runs, and hands it to the machine's task.
+    runtime: Option<Runtime<Detached>>,
+    /// Its ports, and the channel each is on.
+    ports: Vec<(Value, usize)>,
+}
+
+struct Channel {
+    name: String,
+    ends: [(usize, Value); 2],
+}
+
+/// Machines in VMs of their own, joined by channels, and the [`Environment`]
+/// they share.
+pub struct System<E: Environment> {
+    pub environment: E,
+    /// The symbols of the library the environment was built for.
+    symbols: HashMap<String, Value>,
+    processes: Vec<Process>,
+    channels: Vec<Channel>,
+    /// Ports the environment offers events under, in its library, and the
+    /// machine each goes to.
+    externals: Vec<(Value, usize)>,
+}
+
+impl<E: Environment> System<E> {
+    /// A system with no machines yet, around `environment`, which was built
+    /// for `library`.
+    pub fn new(library: &Library, environment: E) -> Self {
+        Self {
+            environment,
+            symbols: library.symbols.clone(),
+            processes: Vec::new(),
+            channels: Vec::new(),
+            externals: Vec::new(),
+        }
+    }
+
+    /// Adds the machine in module `prefix` of `library`, and calls it `name`.
+    pub fn add_process(
+        &mut self,
+        name: &str,
+        library: Library,
+        prefix: &str,
+    ) -> Result<(), String> {
+        if self.process(name).is_ok() {
+            return Err(format!("there is already a process called {}", name));
+        }
+        let symbols = library.symbols.clone();
+        let runtime =
+            Runtime::new(library, prefix, Detached).map_err(|e| format!("{}: {}", name, e))?;
+        self.processes.push(Process {
+            name: name.to_string(),
+            symbols,
+            runtime: Some(runtime),
+            ports: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Joins port `a.1` of process `a.0` to port `b.1` of process `b.0`,
+    /// each port named by its path in its process's library.
+    pub fn add_channel(
+        &mut self,
+        name: &str,
+        a: (&str, &str),
+        b: (&str, &str),
+    ) -> Result<(), String> {
+        let channel = self.channels.len();
+        let mut ends = Vec::new();
+        for (process, port) in [a, b] {
+            let index = self.process(process)?;
+            let symbol = self.processes[index]
+                .symbols
+                .get(port)
+                .cloned()
+                .ok_or_else(|| format!("{} has no symbol {} to use as a port", process, port))?;
+            if self.processes[index]
+                .ports
+                .iter()
+                .any(|(p, _)| p == &symbol)
+            {
+                return Err(format!(
+                    "{}'s port {} is already on a channel",
+                    process, port
+                ));
+            }
+            ends.push((index, symbol));
+        }
+        if ends[0].0 == ends[1].0 {
+            return Err(format!("channel {} joins {} to itself", name, a.0));
+        }
+        let ends: [(usize, Value); 2] = ends.try_into().unwrap();
+        for (index, port) in &ends {
+            self.processes[*index].ports.push((port.clone(), channel));
+        }
+        self.channels.push(Channel {
+            name: name.to_string(),
+            ends,
+        });
+        Ok(())
+    }
+
+    /// Hands what the environment offers under `port` to `process`.
+    pub fn add_external(&mut self, process: &str, port: &str) -> Result<(), String> {
+        let index = self.process(process)?;
+        if !self.processes[index].symbols.contains_key(port) {
+            return Err(format!("{} has no symbol {}", process, port));
+        }
+        let symbol = self
+            .symbols
+            .get(port)
+            .cloned()
+            .ok_or_else(|| format!("the environment has no symbol {}", port))?;
+        if self.externals.iter().any(|(p, _)| p == &symbol) {
+            return Err(format!("{} is already handed to a process", port));
+        }
+        self.externals.push((symbol, index));
+        Ok(())
+    }
+
+    /// Sets the arguments every machine's `init` is given, as
+    /// [`Runtime::set_args`] does for one.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        for process in &mut self.processes {
+            if let Some(runtime) = &mut process.runtime {
+                runtime.set_args(args.clone());
+            }
+        }
+    }
+
+    fn process(&self, name: &str) -> Result<usize, String> {
+        self.processes
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| format!("there is no process called {}", name))
+    }
+
+    /// Runs every machine until all of them are done, or the environment has
+    /// closed and every one left is ready to finish, and answers the exit
+    /// code: that of the first machine to exit, and otherwise 0.
+    pub async fn run(&mut self) -> Result<i64, String> {
+        let (report_to, mut reports_from) = mpsc::unbounded_channel();
+        let mut commands = Vec::new();
+        for (index, process) in self.processes.iter_mut().enumerate() {
+            let runtime = process.runtime.take().ok_or("a system only runs once")?;
+            let (command_to, commands_from) = mpsc::unbounded_channel();
+            tokio::spawn(serve(runtime, index, commands_from, report_to.clone()));
+            commands.push(command_to);
+        }
+        drop(report_to);
+
+        let count = self.processes.len();
+        // `None` while a machine is busy.
+        let mut reports: Vec<Option<Report>> = vec![None; count];
+        // Events from the environment, as it offered them and as its machine
+        // is to be offered them, not yet taken.
+        let mut pending: Vec<VecDeque<(Value, Value)>> = vec![VecDeque::new(); count];
+        loop {
+            while reports.iter().any(Option::is_none) {
+                let (index, report) = reports_from.recv().await.ok_or("every process stopped")?;
+                let report =
+                    report.map_err(|e| format!("{}: {}", self.processes[index].name, e))?;
+                reports[index] = Some(report);
+            }
+            if reports
+                .iter()
+                .flatten()
+                .all(|r| matches!(r.status, Status::Done))
+            {
+                return Ok(0);
+            }
+
+            // Each machine moves at most once a round, and those that move
+            // settle in parallel before the next.
+            for sender in 0..count {
+                let Some(Report {
+                    status: Status::Emitting(event),
+                    ..
+                }) = reports[sender].clone()
+                else {
+                    continue;
+                };
+                match self.channel_of(sender, &event) {
+                    Some((channel, receiver)) => {
+                        if !matches!(
+                            reports[receiver],
+                            Some(Report {
+                                status: Status::Waiting,
+                                ..
+                            })
+                        ) {
+                            continue;
+                        }
+                        let offered = self.across(channel, receiver, &event)?;
+                        let accepted = ask(&commands[receiver], |reply| {
+                            Command::Accept(offered.clone(), reply)
+                        })
+                        .await
+                        .map_err(|e| format!("{}: {}", self.processes[receiver].name, e))?;
+                        if accepted {
+                            self.tell(&commands, sender, Command::Process(event))?;
+                            self.tell(&commands, receiver, Command::Process(offered))?;
+                            reports[sender] = None;
+                            reports[receiver] = None;
+                        }
+                    }
+                    None => {
+                        if let Some(code) = self.exit_code(sender, &event) {
+                            return Ok(code);
+                        }
+                        let handed = translate(&event, &self.symbols)
+                            .map_err(|e| format!("{}: {}", self.processes[sender].name, e))?;
+                        self.environment.handle_event(handed).await?;
+                        self.tell(&commands, sender, Command::Process(event))?;
+                        reports[sender] = None;
+                    }
+                }
+            }
+            for receiver in 0..count {
+                let Some(Report {
+                    status: Status::Waiting,
+                    ..
+                }) = reports[receiver]
+                else {
+                    continue;
+                };
+                let Some((original, event)) = pending[receiver].pop_front() else {
+                    continue;
+                };
+                let accepted = ask(&commands[receiver], |reply| {
+                    Command::Accept(event.clone(), reply)
+                })
+                .await
+                .map_err(|e| format!("{}: {}", self.processes[receiver].name, e))?;
+                if !accepted {
+                    return Err(format!(
+                        "{}: {}",
+                        self.processes[receiver].name,
+                        self.environment.refused(&original)
+                    ));
+                }
+                self.tell(&commands, receiver, Command::Process(event))?;
+                reports[receiver] = None;
+            }
+            if reports.iter().any(Option::is_none) {
+                continue;
+            }
+
+            if self.environment.is_closed() {
+                return self.finish(&commands, &reports, &pending).await.map(|()| 0);
+            }
+            let event = self.environment.wait_for_event().await?;
+            let receiver = self
+                .externals
+                .iter()
+                .find(
+                    |(port, _)| matches!(&event, Value::Tuple(e) if e.len() == 2 && &e[1] == port),
+                )
+                .map(|(_, index)| *index)
+                .ok_or_else(|| {
+                    format!("the environment offered {}, and no process takes it", event)
+                })?;
+            let offered = translate(&event, &self.processes[receiver].symbols)
+                .map_err(|e| format!("{}: {}", self.processes[receiver].name, e))?;
+            pending[receiver].push_back((event, offered));
+        }
+    }
+
+    /// The channel `event` is sent on, if it is `(message, port)` with
+    /// `port` one of `sender`'s, and the machine at the other end.
+    fn channel_of(&self, sender: usize, event: &Value) -> Option<(usize, usize)> {
+        let Value::Tuple(event) = event else {
+            return None;
+        };
+        let [_, tag] = &event[..] else {
+            return None;
+        };
+        let (_, channel) = self.processes[sender]
+            .ports
+            .iter()
+            .find(|(port, _)| port == tag)?;
+        let (receiver, _) = self.channels[*channel]
+            .ends
+            .iter()
+            .find(|(index, _)| *index != sender)?;
+        Some((*channel, *receiver))
+    }
+
+    /// The code `event` exits with, if `sender` emits it under
+    /// `std::process`.
+    fn exit_code(&self, sender: usize, event: &Value) -> Option<i64> {
+        let symbols = &self.processes[sender].symbols;
+        exit_code(
+            event,
+            symbols.get("std::process::process")?,
+            symbols.get("std::process::exit")?,
+        )
+    }
+
+    /// `event`, sent on `channel`, as `receiver` is offered it.
+    fn across(&self, channel: usize, receiver: usize, event: &Value) -> Result<Value, String> {
+        let Value::Tuple(event) = event else {
+            unreachable!("channel_of found its port");
+        };
+        let (_, port) = self.channels[channel]
+            .ends
+            .iter()
+            .find(|(index, _)| *index == receiver)
+            .unwrap();
+        let message = translate(&event[0], &self.processes[receiver].symbols).map_err(|e| {
+            format!(
+                "channel {} into {}: {}",
+                self.channels[channel].name, self.processes[receiver].name, e
+            )
+        })?;
+        Ok(Value::Tuple(vec![message, port.clone()]))
+    }
+
+    fn tell(
+        &self,
+        commands: &[mpsc::UnboundedSender<Command>],
+        index: usize,
+        command: Command,
+    ) -> Result<(), String> {
+        commands[index]
+            .send(command)
+            .map_err(|_| format!("{} stopped", self.processes[index].name))
+    }
+
+    /// The environment has closed, and nothing can move: a success if every
+    /// machine left is ready to finish, and otherwise where each is stuck.
+    async fn finish(
+        &self,
+        commands: &[mpsc::UnboundedSender<Command>],
+        reports: &[Option<Report>],
+        pending: &[VecDeque<(Value, Value)>],
+    ) -> Result<(), String> {
+        let mut stuck = Vec::new();
+        for (index, report) in reports.iter().enumerate() {
+            let Some(Report { state, status }) = report else {
+                continue;
+            };
+            let name = &self.processes[index].name;
+            let mut line = match status {
+                Status::Done => continue,
+                Status::Emitting(event) => {
+                    let (channel, _) = self.channel_of(index, event).unwrap();
+                    format!(
+                        "{}, in state {}, is emitting {} on channel {}",
+                        name, state, event, self.channels[channel].name
+                    )
+                }
+                Status::Waiting => {
+                    let ready = ask(&commands[index], Command::ReadyToFinish)
+                        .await
+                        .map_err(|e| format!("{}: {}", name, e))?;
+                    if ready {
+                        continue;
+                    }
+                    format!(
+                        "{}, in state {}, is waiting, and is not ready to finish",
+                        name, state
+                    )
+                }
+            };
+            if !pending[index].is_empty() {
+                line.push_str(&format!(
+                    ", with {} event(s) from the environment still to offer it",
+                    pending[index].len()
+                ));
+            }
+            stuck.push(line);
+        }
+        if stuck.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "The system is stuck, and the environment has closed:\n    {}",
+            stuck.join("\n    ")
+        ))
+    }
+
+    /// Builds the system the topology file at `path` describes. Each file
+    /// is assembled once, however many processes it holds. The environment
+    /// is built for the library of the first process that declares every
+    /// `std` symbol any of them does, so that whatever a machine hands it is
+    /// something it knows.
+    pub fn load(path: &Path, environment: impl FnOnce(&Library) -> E) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading file '{}': {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut libraries: HashMap<PathBuf, Library> = HashMap::new();
+        let mut processes = Vec::new();
+        let mut wiring = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let at = |e: String| format!("{}:{}: {}", path.display(), i + 1, e);
+            let line = strip_comment(line).trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                [] => {}
+                ["process", name, file, module] => {
+                    let file = dir.join(file);
+                    if !libraries.contains_key(&file) {
+                        let library = assemble_file(&file).map_err(at)?;
+                        libraries.insert(file.clone(), library);
+                    }
+                    processes.push((i + 1, name, file, module));
+                }
+                ["channel", ..] | ["external", ..] => wiring.push((i + 1, words)),
+                _ => {
+                    return Err(at(format!(
+                        "expected `process NAME FILE MODULE`, `channel NAME A PORT B PORT` \
+                         or `external NAME PORT`, found `{}`",
+                        line
+                    )));
+                }
+            }
+        }
+        if processes.is_empty() {
+            return Err(format!("{}: a system has a process", path.display()));
+        }
+        let library = environment_library(&processes, &libraries)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut system = System::new(library, environment(library));
+        for (line, name, file, module) in processes {
+            system
+                .add_process(name, libraries[&file].clone(), module)
+                .map_err(|e| format!("{}:{}: {}", path.display(), line, e))?;
+        }
+        for (line, words) in wiring {
+            let at = |e: String| format!("{}:{}: {}", path.display(), line, e);
+            match words[..] {
+                ["channel", name, a, a_port, b, b_port] => system
+                    .add_channel(name, (a, a_port), (b, b_port))
+                    .map_err(at)?,
+                ["external", name, port] => system.add_external(name, port).map_err(at)?,
+                _ => {
+                    return Err(at(format!(
+                        "expected `channel NAME A PORT B PORT` or `external NAME PORT`, found `{}`",
+                        words.join(" ")
+                    )));
+                }
+            }
+        }
+        Ok(system)
+    }
+}
+
+/// The library of the first of `processes` that declares every `std`
+/// symbol any of them declares.
+fn environment_library<'l>(
+    processes: &[(usize, &str, PathBuf, &str)],
+    libraries: &'l HashMap<PathBuf, Library>,
+) -> Result<&'l Library, String> {
+    let std_symbols = |file: &PathBuf| {
+        libraries[file]
+            .symbols
+            .keys()
+            .filter(|path| path.starts_with("std::"))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let mut wanted: Vec<(String, &str)> = Vec::new();
+    for (_, name, file, _) in processes {
+        for path in std_symbols(file) {
+            if !wanted.iter().any(|(p, _)| p == &path) {
+                wanted.push((path, name));
+            }
+        }
+    }
+    wanted.sort();
+    for (_, _, file, _) in processes {
+        let library = &libraries[file];
+        if wanted
+            .iter()
+            .all(|(path, _)| library.symbols.contains_key(path))
+        {
+            return Ok(library);
+        }
+    }
+    let (_, first, file, _) = &processes[0];
+    let (path, name) = wanted
+        .iter()
+        .find(|(path, _)| !libraries[file].symbols.contains_key(path))
+        .unwrap();
+    Err(format!(
+        "no process declares every `std` symbol the system uses, so no library \
+         can serve as the environment's: {} declares {}, and {} does not",
+        name, path, first
+    ))
+}
+
+fn assemble_file(path: &Path) -> Result<Library, String> {
+    let code = std::fs::read_to_string(path)
+        .map_err(|e| format!("Error reading file '{}': {}", path.display(), e))?;
+    let mut sources = SourceMap::new();
+    let root = sources.add_path(path, code);
+    crate::assemble_source(&mut sources, root, path.parent()).map_err(|e| sources.render(&e))
+}
+
+/// Runs one machine on its own task: it settles, reports where, and answers
+/// the system's questions about that state until it is told what happened.
+async fn serve(
+    mut runtime: Runtime<Detached>,
+    index: usize,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    reports: mpsc::UnboundedSender<(usize, Result<Report, String>)>,
+) {
+    let mut state = match runtime.execute_init() {
+        Ok(state) => state,
+        Err(e) => {
+            let _ = reports.send((index, Err(e)));
+            return;
+        }
+    };
+    loop {
+        let report = match settle(&mut runtime, state) {
+            Ok(report) => report,
+            Err(e) => {
+                let _ = reports.send((index, Err(e)));
+                return;
+            }
+        };
+        state = report.state.clone();
+        if reports.send((index, Ok(report))).is_err() {
+            return;
+        }
+        loop {
+            match commands.recv().await {
+                None => return,
+                Some(Command::Accept(event, reply)) => {
+                    let _ = reply.send(runtime.execute_accept(state.clone(), event));
+                }
+                Some(Command::ReadyToFinish(reply)) => {
+                    let _ = reply.send(runtime.execute_is_ready_to_finish(state.clone()));
+                }
+                Some(Command::Process(event)) => {
+                    match runtime.execute_process(state.clone(), event) {
+                        Ok(next) => {
+                            state = next;
+                            break;
+                        }
+                        Err(e) => {
+                            let _ = reports.send((index, Err(e)));
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn settle(runtime: &mut Runtime<Detached>, state: Value) -> Result<Report, String> {
+    let state = runtime.settle(state)?;
+    let status = if runtime.execute_is_done(state.clone())? {
+        Status::Done
+    } else {
+        match runtime.execute_emit(state.clone())? {
+            (event, true) => Status::Emitting(event),
+            (_, false) => Status::Waiting,
+        }
+    };
+    Ok(Report { state, status })
+}
+
+async fn ask(
+    commands: &mpsc::UnboundedSender<Command>,
+    command: impl FnOnce(oneshot::Sender<Result<bool, String>>) -> Command,
+) -> Result<bool, String> {
+    let (reply, answer) = oneshot::channel();
+    commands
+        .send(command(reply))
+        .map_err(|_| "stopped".to_string())?;
+    answer.await.map_err(|_| "stopped".to_string())?
+}
+
+/// `value`, with each symbol swapped for the one `symbols` keeps under its
+/// path.
+fn translate(value: &Value, symbols: &HashMap<String, Value>) -> Result<Value, String> {
+    Ok(match value {
+        Value::Symbol(symbol) => symbols
+            .get(&symbol.path)
+            .cloned()
+            .ok_or_else(|| format!("{} is declared on one side only", symbol.path))?,
+        Value::Tuple(elements) => Value::Tuple(
+            elements
+                .iter()
+                .map(|v| translate(v, symbols))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Map(entries) => Value::Map(
+            entries
+                .iter()
+                .map(|(k, v)| Ok((translate(k, symbols)?, translate(v, symbols)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        other => other.clone(),
+    })
+}